@@ -0,0 +1,793 @@
+use crate::conversation::{limit_history, ConversationBuffer};
+use crate::error::{OllmError, Result};
+use crate::llm::{ChatResponse, LlmProvider, StopReason, TokenUsage};
+use crate::mcp::McpManager;
+use crate::tools::ToolExecutor;
+use crate::types::{ContentBlock, Message, ToolResultContent};
+use crate::ui::renderer::{render_response, Renderer, RenderingAccumulator};
+use futures::stream::{FuturesOrdered, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::info;
+
+/// Default cap on model calls per `Agent::run`
+pub const DEFAULT_MAX_ITERATIONS: usize = 20;
+
+/// Progress of an `Agent::run`, reported through `Agent::on_event`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// A model call is about to be made (1-based)
+    ModelTurn { iteration: usize },
+    /// The model asked for a tool call
+    ToolCall { name: String, args: Value },
+    /// A tool call finished; `ok` is false if it returned an error
+    ToolResult { name: String, ok: bool },
+    /// The model gave its final answer
+    Done { usage: TokenUsage },
+}
+
+/// Subscriber registered with `Agent::on_event`
+type EventHandler<'a> = Box<dyn Fn(&AgentEvent) + Send + Sync + 'a>;
+
+/// Results of tool calls already made, keyed by tool-use id
+type ToolResults = HashMap<String, Option<ContentBlock>>;
+
+/// Runs the model-tool loop: send the conversation, execute any tool calls
+/// through MCP, feed the results back, and repeat until the model answers
+/// without calling tools
+pub struct Agent<'a> {
+    provider: &'a dyn LlmProvider,
+    mcp: &'a McpManager,
+    executor: ToolExecutor,
+    max_iterations: usize,
+    max_history_messages: Option<usize>,
+    early_tool_dispatch: bool,
+    refresh_tools: bool,
+    stream_buffer_chunks: Option<usize>,
+    on_event: Option<EventHandler<'a>>,
+}
+
+/// Result of a completed `Agent::run`
+#[derive(Debug, Clone)]
+pub struct AgentOutcome {
+    /// The final response (the one without tool calls)
+    pub response: ChatResponse,
+    /// The full conversation, including tool calls, results, and the answer
+    pub messages: Vec<Message>,
+    /// Number of model calls made
+    pub iterations: usize,
+    /// Usage summed over all model calls
+    pub usage: TokenUsage,
+}
+
+impl<'a> Agent<'a> {
+    pub fn new(provider: &'a dyn LlmProvider, mcp: &'a McpManager, executor: ToolExecutor) -> Self {
+        Self {
+            provider,
+            mcp,
+            executor,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            max_history_messages: None,
+            early_tool_dispatch: false,
+            refresh_tools: false,
+            stream_buffer_chunks: None,
+            on_event: None,
+        }
+    }
+
+    /// Fail if the model is still calling tools after this many calls
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations.max(1);
+        self
+    }
+
+    /// Send only the most recent `max` messages (plus the system prompt)
+    /// on each call; the returned conversation still has everything
+    pub fn max_history_messages(mut self, max: Option<usize>) -> Self {
+        self.max_history_messages = max;
+        self
+    }
+
+    /// Stream each response and start every tool call as soon as its block
+    /// is complete, while the rest of the response is still arriving
+    ///
+    /// Has no effect with providers that can't stream. Calls already
+    /// started still count if the model then stops for another reason.
+    pub fn early_tool_dispatch(mut self, enabled: bool) -> Self {
+        self.early_tool_dispatch = enabled;
+        self
+    }
+
+    /// Re-read the MCP servers' tools before each model call, so tools a
+    /// server announces mid-run (`notifications/tools/list_changed`) are
+    /// offered on the next call
+    pub fn refresh_tools(mut self, enabled: bool) -> Self {
+        self.refresh_tools = enabled;
+        self
+    }
+
+    /// Read streamed responses through a buffer of `capacity` chunks, so
+    /// the provider pauses while rendering falls behind
+    pub fn stream_buffer_chunks(mut self, capacity: usize) -> Self {
+        self.stream_buffer_chunks = Some(capacity);
+        self
+    }
+
+    /// Call `f` with each `AgentEvent` as the run progresses
+    pub fn on_event(mut self, f: impl Fn(&AgentEvent) + Send + Sync + 'a) -> Self {
+        self.on_event = Some(Box::new(f));
+        self
+    }
+
+    fn emit(&self, event: AgentEvent) {
+        if let Some(f) = &self.on_event {
+            f(&event);
+        }
+    }
+
+    /// Run the loop on `messages` until the model gives a final answer
+    pub async fn run(&self, messages: Vec<Message>) -> Result<AgentOutcome> {
+        self.run_turns(&mut ConversationBuffer::from(messages), None)
+            .await
+    }
+
+    /// Like `run`, but stream each response to `renderer` as it arrives and
+    /// report tool results to it
+    ///
+    /// Responses from providers that can't stream are rendered whole.
+    pub async fn run_rendered(
+        &self,
+        messages: Vec<Message>,
+        renderer: &mut dyn Renderer,
+    ) -> Result<AgentOutcome> {
+        self.run_turns(&mut ConversationBuffer::from(messages), Some(renderer))
+            .await
+    }
+
+    /// Like `run_rendered`, but continue `conversation` in place, so the
+    /// prefix sent in earlier turns stays marked for prompt caching
+    ///
+    /// The model's replies and tool results are appended to `conversation`;
+    /// after an error it may end part way through a turn.
+    pub async fn run_conversation(
+        &self,
+        conversation: &mut ConversationBuffer,
+        renderer: &mut dyn Renderer,
+    ) -> Result<AgentOutcome> {
+        self.run_turns(conversation, Some(renderer)).await
+    }
+
+    async fn run_turns(
+        &self,
+        conversation: &mut ConversationBuffer,
+        mut renderer: Option<&mut (dyn Renderer + '_)>,
+    ) -> Result<AgentOutcome> {
+        let mut usage = TokenUsage::default();
+        let streaming = self.provider.supports_streaming()
+            && (self.early_tool_dispatch || renderer.is_some());
+
+        for iteration in 1..=self.max_iterations {
+            let request = match self.max_history_messages {
+                Some(max) => limit_history(conversation.request(), max),
+                None => conversation.request(),
+            };
+            let refreshed;
+            let executor = if self.refresh_tools {
+                refreshed = self.executor.with_tools(self.mcp.get_all_tools());
+                &refreshed
+            } else {
+                &self.executor
+            };
+
+            self.emit(AgentEvent::ModelTurn { iteration });
+            let (response, mut dispatched) = if streaming {
+                self.stream_turn(request, executor, renderer.as_deref_mut())
+                    .await?
+            } else {
+                let response = self
+                    .provider
+                    .chat(request, executor.tools().to_vec())
+                    .await?;
+                if let Some(renderer) = renderer.as_deref_mut() {
+                    render_response(&response, renderer);
+                }
+                (response, ToolResults::new())
+            };
+            usage.input_tokens += response.usage.input_tokens;
+            usage.output_tokens += response.usage.output_tokens;
+
+            let tool_uses = response.tool_uses();
+            if tool_uses.is_empty() || response.stop_reason != Some(StopReason::ToolUse) {
+                // A response cut short (e.g. at max_tokens) can still hold
+                // tool calls. Keep only those already dispatched, followed by
+                // their results, so no tool_use is left without a result
+                let content: Vec<ContentBlock> = response
+                    .content
+                    .iter()
+                    .filter(|block| match block {
+                        ContentBlock::ToolUse { id, .. } => {
+                            matches!(dispatched.get(id), Some(Some(_)))
+                        }
+                        _ => true,
+                    })
+                    .cloned()
+                    .collect();
+                let kept: Vec<&ContentBlock> = content
+                    .iter()
+                    .filter(|block| matches!(block, ContentBlock::ToolUse { .. }))
+                    .collect();
+                let results: Vec<Option<ContentBlock>> = kept
+                    .iter()
+                    .map(|block| match block {
+                        ContentBlock::ToolUse { id, .. } => dispatched.remove(id).flatten(),
+                        _ => None,
+                    })
+                    .collect();
+                self.report_results(&kept, &results, renderer.as_deref_mut());
+
+                if !content.is_empty() {
+                    conversation.push(Message::new_assistant(content.clone()));
+                }
+                if !kept.is_empty() {
+                    conversation.push(Message::new_tool_results(
+                        results.into_iter().flatten().collect(),
+                    ));
+                }
+                self.emit(AgentEvent::Done {
+                    usage: usage.clone(),
+                });
+                return Ok(AgentOutcome {
+                    response,
+                    messages: conversation.to_messages(),
+                    iterations: iteration,
+                    usage,
+                });
+            }
+
+            conversation.push(Message::new_assistant(response.content.clone()));
+
+            info!(
+                "Iteration {}: running {} tool call(s)",
+                iteration,
+                tool_uses.len()
+            );
+
+            let remaining: Vec<&ContentBlock> = tool_uses
+                .iter()
+                .copied()
+                .filter(|block| {
+                    !matches!(block, ContentBlock::ToolUse { id, .. } if dispatched.contains_key(id))
+                })
+                .collect();
+            for block in &remaining {
+                if let ContentBlock::ToolUse { name, input, .. } = block {
+                    self.emit(AgentEvent::ToolCall {
+                        name: name.clone(),
+                        args: input.clone(),
+                    });
+                }
+            }
+
+            // Calls to different servers run concurrently
+            let mut late = futures::future::join_all(
+                remaining
+                    .iter()
+                    .map(|block| executor.execute(self.mcp, block)),
+            )
+            .await
+            .into_iter();
+
+            // Results go back in the order the model made the calls
+            let results: Vec<Option<ContentBlock>> = tool_uses
+                .iter()
+                .map(|block| match block {
+                    ContentBlock::ToolUse { id, .. } if dispatched.contains_key(id) => {
+                        dispatched.remove(id).flatten()
+                    }
+                    _ => late.next().flatten(),
+                })
+                .collect();
+
+            self.report_results(&tool_uses, &results, renderer.as_deref_mut());
+            conversation.push(Message::new_tool_results(results.into_iter().flatten().collect()));
+        }
+
+        Err(OllmError::ToolExecution(format!(
+            "Model was still calling tools after {} iterations",
+            self.max_iterations
+        )))
+    }
+
+    /// Show each tool call's result to `renderer` (if any) and subscribers
+    fn report_results(
+        &self,
+        tool_uses: &[&ContentBlock],
+        results: &[Option<ContentBlock>],
+        mut renderer: Option<&mut (dyn Renderer + '_)>,
+    ) {
+        for (block, result) in tool_uses.iter().zip(results) {
+            if let (
+                ContentBlock::ToolUse { name, .. },
+                Some(ContentBlock::ToolResult {
+                    content, is_error, ..
+                }),
+            ) = (block, result)
+            {
+                if let Some(renderer) = renderer.as_deref_mut() {
+                    renderer.on_tool_result(name, &result_text(content), *is_error == Some(true));
+                }
+                self.emit(AgentEvent::ToolResult {
+                    name: name.clone(),
+                    ok: *is_error != Some(true),
+                });
+            }
+        }
+    }
+
+    /// Stream one response to `renderer` (if any); with early dispatch,
+    /// execute each tool call as soon as its block stops. Returns the
+    /// response and the results of the calls already made
+    async fn stream_turn(
+        &self,
+        request: Vec<Message>,
+        executor: &ToolExecutor,
+        renderer: Option<&mut (dyn Renderer + '_)>,
+    ) -> Result<(ChatResponse, ToolResults)> {
+        let mut silent = SilentRenderer;
+        let renderer = renderer.unwrap_or(&mut silent);
+
+        let tools = executor.tools().to_vec();
+        let mut stream = match self.stream_buffer_chunks {
+            Some(capacity) => {
+                self.provider
+                    .buffered_stream_chat(request, tools, capacity)
+                    .await?
+            }
+            None => self.provider.stream_chat(request, tools).await?,
+        };
+        let mut accumulator = RenderingAccumulator::new(self.provider.model().to_string(), renderer);
+        let mut running = FuturesOrdered::new();
+        let mut results = ToolResults::new();
+
+        loop {
+            tokio::select! {
+                chunk = stream.next() => {
+                    let Some(chunk) = chunk else { break };
+                    accumulator.push(chunk?)?;
+                    if !self.early_tool_dispatch {
+                        continue;
+                    }
+
+                    for block in accumulator.take_completed_tool_uses() {
+                        let ContentBlock::ToolUse { id, name, input } = &block else {
+                            continue;
+                        };
+                        info!("Dispatching tool '{}' while the response streams", name);
+                        self.emit(AgentEvent::ToolCall {
+                            name: name.clone(),
+                            args: input.clone(),
+                        });
+
+                        let id = id.clone();
+                        let mcp = self.mcp;
+                        running.push_back(async move { (id, executor.execute(mcp, &block).await) });
+                    }
+                }
+                Some((id, result)) = running.next(), if !running.is_empty() => {
+                    results.insert(id, result);
+                }
+            }
+        }
+
+        while let Some((id, result)) = running.next().await {
+            results.insert(id, result);
+        }
+
+        Ok((accumulator.finish()?, results))
+    }
+}
+
+/// Renderer for streamed turns nobody is watching
+struct SilentRenderer;
+
+impl Renderer for SilentRenderer {
+    fn on_text_delta(&mut self, _text: &str) {}
+
+    fn on_tool_use(&mut self, _name: &str, _input: &Value) {}
+
+    fn on_tool_result(&mut self, _name: &str, _content: &str, _is_error: bool) {}
+
+    fn on_complete(&mut self, _usage: &TokenUsage) {}
+}
+
+/// The text parts of a tool result, for display
+fn result_text(content: &[ToolResultContent]) -> String {
+    content
+        .iter()
+        .filter_map(|part| match part {
+            ToolResultContent::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{McpServerConfig, ToolsConfig};
+    use crate::llm::{ChatChunk, ContentDelta, MessageDelta, StreamAccumulator};
+    use crate::types::{Role, Tool};
+    use async_trait::async_trait;
+    use futures::Stream;
+    use serde_json::json;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    /// One streamed response: each chunk after its delay in milliseconds
+    type Turn = Vec<(u64, ChatChunk)>;
+
+    /// Provider that streams scripted turns, pausing between chunks
+    struct ScriptedProvider {
+        turns: Mutex<VecDeque<Turn>>,
+        /// Names of the tools offered on each call
+        offered: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl ScriptedProvider {
+        fn new(turns: Vec<Turn>) -> Self {
+            Self {
+                turns: Mutex::new(turns.into()),
+                offered: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn next_turn(&self, tools: &[Tool]) -> Turn {
+            let mut names: Vec<String> = tools.iter().map(|t| t.name.clone()).collect();
+            names.sort();
+            self.offered.lock().unwrap().push(names);
+            self.turns.lock().unwrap().pop_front().expect("no turns left")
+        }
+    }
+
+    #[async_trait]
+    impl LlmProvider for ScriptedProvider {
+        async fn chat(&self, _messages: Vec<Message>, tools: Vec<Tool>) -> Result<ChatResponse> {
+            let mut accumulator = StreamAccumulator::new("scripted".to_string());
+            for (delay, chunk) in self.next_turn(&tools) {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                accumulator.push(chunk)?;
+            }
+            accumulator.finish()
+        }
+
+        async fn stream_chat(
+            &self,
+            _messages: Vec<Message>,
+            tools: Vec<Tool>,
+        ) -> Result<Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>> {
+            let chunks = futures::stream::iter(self.next_turn(&tools)).then(|(delay, chunk)| async move {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                Ok(chunk)
+            });
+            Ok(Box::new(Box::pin(chunks)))
+        }
+
+        fn supports_tools(&self) -> bool {
+            true
+        }
+
+        fn max_tokens(&self) -> usize {
+            4096
+        }
+
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        fn model(&self) -> &str {
+            "scripted"
+        }
+    }
+
+    /// A call to `tool`, then `tail_ms` more streaming before `stop_reason`
+    fn tool_call_turn(tool: &str, stop_reason: StopReason, tail_ms: u64) -> Turn {
+        vec![
+            (
+                0,
+                ChatChunk::ContentBlockStart {
+                    index: 0,
+                    content_block: ContentBlock::ToolUse {
+                        id: "call_1".to_string(),
+                        name: tool.to_string(),
+                        input: json!({}),
+                    },
+                },
+            ),
+            (
+                0,
+                ChatChunk::ContentBlockDelta {
+                    index: 0,
+                    delta: ContentDelta::InputJsonDelta {
+                        partial_json: "{}".to_string(),
+                    },
+                },
+            ),
+            (0, ChatChunk::ContentBlockStop { index: 0 }),
+            (
+                tail_ms,
+                ChatChunk::MessageDelta {
+                    delta: MessageDelta {
+                        stop_reason: Some(stop_reason),
+                        usage: None,
+                    },
+                },
+            ),
+            (0, ChatChunk::MessageStop),
+        ]
+    }
+
+    fn text_turn(text: &str) -> Turn {
+        vec![
+            (
+                0,
+                ChatChunk::ContentBlockStart {
+                    index: 0,
+                    content_block: ContentBlock::Text {
+                        text: text.to_string(),
+                    },
+                },
+            ),
+            (0, ChatChunk::ContentBlockStop { index: 0 }),
+            (
+                0,
+                ChatChunk::MessageDelta {
+                    delta: MessageDelta {
+                        stop_reason: Some(StopReason::EndTurn),
+                        usage: None,
+                    },
+                },
+            ),
+            (0, ChatChunk::MessageStop),
+        ]
+    }
+
+    const INITIALIZE: &str = r#"{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05","capabilities":{},"serverInfo":{"name":"fake","version":"1.0"}}}"#;
+
+    /// A manager running one fake server "a" that is initialized, then runs
+    /// `script` under `sh`
+    async fn fake_server(script: &str) -> McpManager {
+        let script = format!("read -r _\necho '{}'\nread -r _\n{}", INITIALIZE, script);
+
+        let mut manager = McpManager::new();
+        manager
+            .start_servers(
+                vec![McpServerConfig {
+                    name: "a".to_string(),
+                    command: "sh".to_string(),
+                    args: vec!["-c".to_string(), script],
+                    env: HashMap::new(),
+                    inherit_env: true,
+                    omit_empty_arguments: false,
+                    content_length_framing: false,
+                }],
+                true,
+                Duration::from_secs(5),
+                1,
+            )
+            .await
+            .unwrap();
+        manager
+    }
+
+    /// A server whose one tool, `wait`, answers "done" after `secs`
+    async fn waiting_server(secs: &str) -> McpManager {
+        fake_server(&format!(
+            r#"
+            read -r _
+            echo '{{"jsonrpc":"2.0","id":2,"result":{{"tools":[{{"name":"wait","description":"Wait","inputSchema":{{"type":"object"}}}}]}}}}'
+            id=3
+            while read -r _; do
+                sleep {secs}
+                echo '{{"jsonrpc":"2.0","id":'$id',"result":{{"content":[{{"type":"text","text":"done"}}]}}}}'
+                id=$((id + 1))
+            done
+            "#
+        ))
+        .await
+    }
+
+    #[tokio::test]
+    async fn tool_call_is_dispatched_while_the_response_streams() {
+        let mcp = waiting_server("0.5").await;
+        let executor = ToolExecutor::new(mcp.get_all_tools(), &ToolsConfig::default());
+        let provider = ScriptedProvider::new(vec![
+            tool_call_turn("a::wait", StopReason::ToolUse, 500),
+            text_turn("finished"),
+        ]);
+
+        let started = Instant::now();
+        let dispatched_at = Mutex::new(None);
+        let agent = Agent::new(&provider, &mcp, executor)
+            .early_tool_dispatch(true)
+            .on_event(|event| {
+                if let AgentEvent::ToolCall { .. } = event {
+                    dispatched_at.lock().unwrap().get_or_insert(started.elapsed());
+                }
+            });
+        let outcome = agent.run(vec![Message::new_user("go".to_string())]).await.unwrap();
+
+        // The call went out before the rest of the response arrived, so the
+        // tool's half second overlapped with the stream's
+        let dispatched_at = dispatched_at.lock().unwrap().unwrap();
+        assert!(dispatched_at < Duration::from_millis(250), "{:?}", dispatched_at);
+        assert!(started.elapsed() < Duration::from_millis(900), "{:?}", started.elapsed());
+
+        assert_eq!(outcome.response.text(), "finished");
+        let roles: Vec<Role> = outcome.messages.iter().map(|m| m.role.clone()).collect();
+        assert_eq!(roles, [Role::User, Role::Assistant, Role::User, Role::Assistant]);
+        assert!(matches!(
+            &outcome.messages[2].content[0],
+            ContentBlock::ToolResult { tool_use_id, .. } if tool_use_id == "call_1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn response_cut_short_leaves_no_tool_call_without_a_result() {
+        // Not dispatched early: the unfinished call is dropped
+        let mcp = waiting_server("0").await;
+        let executor = ToolExecutor::new(mcp.get_all_tools(), &ToolsConfig::default());
+        let provider =
+            ScriptedProvider::new(vec![tool_call_turn("a::wait", StopReason::MaxTokens, 0)]);
+        let outcome = Agent::new(&provider, &mcp, executor)
+            .run(vec![Message::new_user("go".to_string())])
+            .await
+            .unwrap();
+        assert_eq!(outcome.messages.len(), 1);
+
+        // Dispatched early: the call stays, followed by its result
+        let executor = ToolExecutor::new(mcp.get_all_tools(), &ToolsConfig::default());
+        let provider =
+            ScriptedProvider::new(vec![tool_call_turn("a::wait", StopReason::MaxTokens, 0)]);
+        let outcome = Agent::new(&provider, &mcp, executor)
+            .early_tool_dispatch(true)
+            .run(vec![Message::new_user("go".to_string())])
+            .await
+            .unwrap();
+        assert_eq!(outcome.messages.len(), 3);
+        assert!(matches!(
+            &outcome.messages[1].content[..],
+            [ContentBlock::ToolUse { id, .. }] if id == "call_1"
+        ));
+        assert!(matches!(
+            &outcome.messages[2].content[..],
+            [ContentBlock::ToolResult { tool_use_id, .. }] if tool_use_id == "call_1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn binary_tool_output_becomes_a_placeholder_and_the_run_continues() {
+        let mcp = fake_server(
+            r#"
+            read -r _
+            echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"record","description":"Record","inputSchema":{"type":"object"}}]}}'
+            read -r _
+            echo '{"jsonrpc":"2.0","id":3,"result":{"content":[{"type":"text","text":"Recorded:"},{"type":"audio","data":"UklGRg==","mimeType":"audio/wav"},{"type":"resource","resource":{"uri":"file:///a.bin","mimeType":"application/octet-stream","blob":"AAECAwQF"}}]}}'
+            cat >/dev/null
+            "#,
+        )
+        .await;
+        let executor = ToolExecutor::new(mcp.get_all_tools(), &ToolsConfig::default());
+        let provider = ScriptedProvider::new(vec![
+            tool_call_turn("a::record", StopReason::ToolUse, 0),
+            text_turn("finished"),
+        ]);
+
+        let outcome = Agent::new(&provider, &mcp, executor)
+            .run(vec![Message::new_user("go".to_string())])
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.response.text(), "finished");
+        let [ContentBlock::ToolResult {
+            content, is_error, ..
+        }] = &outcome.messages[2].content[..]
+        else {
+            panic!("expected one tool result: {:?}", outcome.messages[2]);
+        };
+        assert_ne!(*is_error, Some(true));
+        assert_eq!(
+            ToolResultContent::joined_text(content),
+            "Recorded:\n[binary content: 4 bytes, mime audio/wav]\n[binary content: 6 bytes, mime application/octet-stream]"
+        );
+    }
+
+    /// `turn` with `usage` reported alongside its stop reason
+    fn with_usage(mut turn: Turn, input_tokens: usize, output_tokens: usize) -> Turn {
+        for (_, chunk) in &mut turn {
+            if let ChatChunk::MessageDelta { delta } = chunk {
+                delta.usage = Some(TokenUsage {
+                    input_tokens,
+                    output_tokens,
+                });
+            }
+        }
+        turn
+    }
+
+    #[tokio::test]
+    async fn one_tool_call_turn_emits_events_in_order() {
+        let mcp = waiting_server("0").await;
+
+        for streamed in [false, true] {
+            let executor = ToolExecutor::new(mcp.get_all_tools(), &ToolsConfig::default());
+            let provider = ScriptedProvider::new(vec![
+                with_usage(tool_call_turn("a::wait", StopReason::ToolUse, 0), 10, 5),
+                with_usage(text_turn("finished"), 20, 3),
+            ]);
+            let events = Mutex::new(Vec::new());
+            Agent::new(&provider, &mcp, executor)
+                .early_tool_dispatch(streamed)
+                .on_event(|event| {
+                    events
+                        .lock()
+                        .unwrap()
+                        .push(serde_json::to_value(event).unwrap())
+                })
+                .run(vec![Message::new_user("go".to_string())])
+                .await
+                .unwrap();
+
+            assert_eq!(
+                events.into_inner().unwrap(),
+                [
+                    json!({ "type": "model_turn", "iteration": 1 }),
+                    json!({ "type": "tool_call", "name": "a::wait", "args": {} }),
+                    json!({ "type": "tool_result", "name": "a::wait", "ok": true }),
+                    json!({ "type": "model_turn", "iteration": 2 }),
+                    json!({ "type": "done", "usage": { "input_tokens": 30, "output_tokens": 8 } }),
+                ],
+                "streamed: {}",
+                streamed
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn tools_announced_mid_run_are_offered_on_the_next_call() {
+        // Calling `wait` makes the server announce a new tool; the refresh
+        // (id 4) is answered before the call (id 3)
+        let mcp = fake_server(
+            r#"
+            read -r _
+            echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"wait","description":"Wait","inputSchema":{"type":"object"}}]}}'
+            read -r _
+            echo '{"jsonrpc":"2.0","method":"notifications/tools/list_changed"}'
+            read -r _
+            echo '{"jsonrpc":"2.0","id":4,"result":{"tools":[{"name":"wait","description":"Wait","inputSchema":{"type":"object"}},{"name":"added","description":"Added","inputSchema":{"type":"object"}}]}}'
+            sleep 0.2
+            echo '{"jsonrpc":"2.0","id":3,"result":{"content":[{"type":"text","text":"done"}]}}'
+            cat >/dev/null
+            "#,
+        )
+        .await;
+        let executor = ToolExecutor::new(mcp.get_all_tools(), &ToolsConfig::default());
+        let provider = ScriptedProvider::new(vec![
+            tool_call_turn("a::wait", StopReason::ToolUse, 0),
+            text_turn("finished"),
+        ]);
+
+        Agent::new(&provider, &mcp, executor)
+            .refresh_tools(true)
+            .run(vec![Message::new_user("go".to_string())])
+            .await
+            .unwrap();
+
+        let offered = provider.offered.lock().unwrap();
+        assert_eq!(offered[0], ["a::wait"]);
+        assert_eq!(offered[1], ["a::added", "a::wait"]);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::config::Config;
+use crate::error::{OllmError, Result};
+use crate::llm::{ChatChunk, ChatResponse, LlmProvider, ProviderRegistry};
+use crate::types::{Message, Tool};
+use futures::Stream;
+
+/// Entry point for using ollm as a library: a configured provider plus the
+/// registry it was resolved from
+pub struct OllmClient {
+    config: Config,
+    registry: ProviderRegistry,
+    provider: Box<dyn LlmProvider>,
+}
+
+impl OllmClient {
+    pub fn builder() -> OllmClientBuilder {
+        OllmClientBuilder::default()
+    }
+
+    /// Send messages to the configured provider
+    pub async fn chat(&self, messages: Vec<Message>, tools: Vec<Tool>) -> Result<ChatResponse> {
+        self.provider.chat(messages, tools).await
+    }
+
+    /// Stream the response, buffering up to `llm.stream_buffer_chunks`
+    /// chunks ahead of the caller
+    pub async fn stream_chat(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>> {
+        self.provider
+            .buffered_stream_chat(messages, tools, self.config.llm.stream_buffer_chunks)
+            .await
+    }
+
+    /// Send messages with `prefill` as the start of the assistant's reply
+    ///
+    /// The response contains only the continuation, not the prefill itself.
+    pub async fn chat_with_prefill(
+        &self,
+        mut messages: Vec<Message>,
+        tools: Vec<Tool>,
+        prefill: &str,
+    ) -> Result<ChatResponse> {
+        crate::llm::push_prefill(self.provider.as_ref(), &mut messages, prefill);
+        self.provider.chat(messages, tools).await
+    }
+
+    pub fn provider(&self) -> &dyn LlmProvider {
+        self.provider.as_ref()
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn registry(&self) -> &ProviderRegistry {
+        &self.registry
+    }
+}
+
+/// Builder for `OllmClient`
+#[derive(Default)]
+pub struct OllmClientBuilder {
+    config: Option<Config>,
+    registry: ProviderRegistry,
+}
+
+impl OllmClientBuilder {
+    /// Use this config (required)
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Register a custom provider, selectable via `llm.provider = "<name>"`
+    pub fn register_provider<F>(mut self, name: impl Into<String>, factory: F) -> Self
+    where
+        F: Fn(&Config) -> Result<Box<dyn LlmProvider>> + Send + Sync + 'static,
+    {
+        self.registry.register(name, factory);
+        self
+    }
+
+    /// Construct the provider named in the config
+    pub fn build(self) -> Result<OllmClient> {
+        let config = self
+            .config
+            .ok_or_else(|| OllmError::Config("OllmClient requires a config".to_string()))?;
+        let provider = self.registry.create(&config)?;
+
+        Ok(OllmClient {
+            config,
+            registry: self.registry,
+            provider,
+        })
+    }
+}
@@ -1,25 +1,66 @@
 use crate::error::{OllmError, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+pub mod wizard;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
+    /// Start the configured MCP servers; when false none are spawned and no
+    /// tools are offered
+    #[serde(default = "default_true")]
+    pub mcp_enabled: bool,
+    /// Abort MCP startup on the first server that fails instead of skipping it
+    #[serde(default)]
+    pub mcp_fail_fast: bool,
+    /// Seconds to wait for an MCP server to answer a request before failing it
+    #[serde(default = "default_mcp_request_timeout_secs")]
+    pub mcp_request_timeout_secs: u64,
+    /// How many MCP servers to start at once
+    #[serde(default = "default_mcp_startup_concurrency")]
+    pub mcp_startup_concurrency: usize,
+    /// Never start MCP servers or offer tools to the model
+    #[serde(default)]
+    pub disable_tools: bool,
     pub llm: LlmConfig,
     #[serde(default)]
+    pub anthropic: AnthropicConfig,
+    #[serde(default)]
     pub ollama: OllamaConfig,
     #[serde(default)]
     pub huggingface: HuggingFaceConfig,
+    #[serde(default)]
+    pub router: RouterConfig,
     pub opensearch: OpenSearchConfig,
     #[serde(default)]
+    pub http: HttpConfig,
+    #[serde(default)]
+    pub tools: ToolsConfig,
+    #[serde(default)]
+    pub session: SessionConfig,
+    #[serde(default)]
+    pub display: DisplayConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub embeddings: EmbeddingsConfig,
+    /// Named system prompts, selected with `--preset <name>`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub presets: HashMap<String, String>,
+    #[serde(default)]
     pub mcp_servers: Vec<McpServerConfig>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct LlmConfig {
-    /// Provider: "anthropic", "ollama", or "huggingface"
+    /// Provider: "anthropic", "ollama", "huggingface", or "router" (pick one
+    /// of the `[router]` backends per request)
     pub provider: String,
-    /// Model name
+    /// Model name, sent by every provider. When empty, the ollama and
+    /// huggingface providers fall back to their own section's `model` (see
+    /// `Config::effective_model`)
     pub model: String,
     /// Environment variable name for API key (for Anthropic and HuggingFace)
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -27,29 +68,490 @@ pub struct LlmConfig {
     /// Max tokens in response
     #[serde(default = "default_max_tokens")]
     pub max_tokens: usize,
+    /// Lower max_tokens to the model's known output limit (set false to send
+    /// max_tokens unchanged, e.g. for a model with a newer, higher limit)
+    #[serde(default = "default_true")]
+    pub clamp_max_tokens: bool,
+    /// Sampling temperature (provider default when unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Sampling seed for reproducible output, best-effort: sent to Ollama
+    /// and HuggingFace, ignored by Anthropic
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Seconds to wait for a connection, for the response to start, and
+    /// between chunks of a response (no timeout when unset); a long stream
+    /// that keeps producing output is never cut off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// Retries for timeouts, connection failures, and 408/429/5xx responses
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// System prompt sent with every conversation (overridden by `--system`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Tool choice: "auto" (default), "any" (must call some tool), "none", or
+    /// a tool name to force that tool
+    #[serde(default)]
+    #[schemars(with = "String")]
+    pub tool_choice: ToolChoice,
+    /// Retry once when the model returns an empty or whitespace-only response
+    #[serde(default)]
+    pub retry_on_empty: bool,
+    /// When a streaming connection drops after partial output, re-request
+    /// with the partial text as a prefix and continue (Ollama only)
+    #[serde(default)]
+    pub resume_streaming: bool,
+    /// Send at most this many of the most recent messages (the system
+    /// prompt is always kept)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_history_messages: Option<usize>,
+    /// Maximum characters of each tool description sent to the model; longer
+    /// descriptions are cut with an ellipsis. `list-tools` still shows them
+    /// in full
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tool_description_chars: Option<usize>,
+    /// Output format: plain text (default), any JSON object, or JSON
+    /// matching a schema
+    #[serde(default, skip_serializing_if = "ResponseFormat::is_text")]
+    pub response_format: ResponseFormat,
+    /// For providers without native tool use (Ollama, HuggingFace), describe
+    /// the tools in the system prompt and parse tool calls out of the reply
+    #[serde(default)]
+    pub tool_prompting: bool,
+    /// Abort a response whose output grows past this many bytes (guards
+    /// against endpoints that stream without end)
+    #[serde(default = "default_max_response_bytes")]
+    pub max_response_bytes: usize,
+    /// Chunks a streamed response reads ahead of its consumer before
+    /// pausing (`run`, `test`, the REPL, and the library client)
+    #[serde(default = "default_stream_buffer_chunks")]
+    pub stream_buffer_chunks: usize,
+}
+
+/// How the model may use the tools offered to it
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ToolChoice {
+    /// The model decides whether to call a tool
+    #[default]
+    Auto,
+    /// The model must call one of the tools
+    Any,
+    /// The model must not call any tool
+    None,
+    /// The model must call this tool
+    Tool(String),
+}
+
+impl ToolChoice {
+    /// Check that a forced tool is among the tools being offered
+    pub fn validate(&self, tools: &[crate::types::Tool]) -> Result<()> {
+        match self {
+            ToolChoice::Tool(name) if !tools.iter().any(|t| &t.name == name) => {
+                Err(OllmError::Config(format!(
+                    "tool_choice names '{}', which is not among the available tools",
+                    name
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// Output format requested from the model
+///
+/// Sent as `response_format` to OpenAI-compatible APIs and as `format` to
+/// Ollama. Anthropic has no JSON mode, so it's approximated by forcing a
+/// tool whose input is the answer.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    #[default]
+    Text,
+    /// Any valid JSON object
+    JsonObject,
+    /// JSON matching `schema` (structured outputs)
+    JsonSchema {
+        /// Name for the schema (letters, digits, `_` and `-`)
+        name: String,
+        schema: serde_json::Value,
+    },
+}
+
+impl ResponseFormat {
+    pub fn is_text(&self) -> bool {
+        *self == ResponseFormat::Text
+    }
+
+    /// The `type` name, as written in config
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ResponseFormat::Text => "text",
+            ResponseFormat::JsonObject => "json_object",
+            ResponseFormat::JsonSchema { .. } => "json_schema",
+        }
+    }
+
+    /// Schema the output must match (`{"type": "object"}` for JSON mode)
+    pub fn schema(&self) -> Option<serde_json::Value> {
+        match self {
+            ResponseFormat::Text => None,
+            ResponseFormat::JsonObject => Some(serde_json::json!({ "type": "object" })),
+            ResponseFormat::JsonSchema { schema, .. } => Some(schema.clone()),
+        }
+    }
+}
+
+impl From<String> for ToolChoice {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "auto" => ToolChoice::Auto,
+            "any" => ToolChoice::Any,
+            "none" => ToolChoice::None,
+            _ => ToolChoice::Tool(value),
+        }
+    }
+}
+
+impl From<ToolChoice> for String {
+    fn from(choice: ToolChoice) -> Self {
+        match choice {
+            ToolChoice::Auto => "auto".to_string(),
+            ToolChoice::Any => "any".to_string(),
+            ToolChoice::None => "none".to_string(),
+            ToolChoice::Tool(name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct AnthropicConfig {
+    /// Token budget for extended thinking (disabled when unset); while
+    /// enabled, `llm.temperature` is ignored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_budget_tokens: Option<usize>,
+    /// Service tier: "auto" (may use priority capacity) or "standard_only"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<ServiceTier>,
+    /// Opaque end-user id sent as `metadata.user_id` for abuse monitoring
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceTier {
+    Auto,
+    StandardOnly,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct OllamaConfig {
     /// Ollama API endpoint
     #[serde(default = "default_ollama_endpoint")]
     pub endpoint: String,
-    /// Model to use (e.g., "codellama:13b")
-    #[serde(default = "default_ollama_model")]
-    pub model: String,
+    /// Model to use when `llm.model` is empty (e.g., "codellama:13b")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Endpoint: "chat" (`/api/chat`, a message list) or "generate"
+    /// (`/api/generate`, the messages flattened into one prompt)
+    #[serde(default)]
+    pub api: OllamaApi,
+    /// Prompt template; anything but "raw" renders the prompt locally and
+    /// sends it via `/api/generate` in raw mode
+    #[serde(default)]
+    pub prompt_template: PromptTemplate,
+    /// Send tools to `/api/chat` and parse the model's `tool_calls` (needs a
+    /// model with tool support, e.g. llama3.1 or qwen2.5; "chat" API and
+    /// "raw" template only)
+    #[serde(default)]
+    pub native_tools: bool,
+    /// Extra model parameters merged into the request's `options` (e.g.
+    /// `num_ctx`, `repeat_penalty`); `num_predict` (from max_tokens),
+    /// `temperature`, and `seed` come from their typed settings when set
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub options: serde_json::Map<String, serde_json::Value>,
+    /// How long Ollama keeps the model loaded after a request: a duration
+    /// ("5m", "1h30m"), seconds ("300"), "-1" to keep it loaded, or "0" to
+    /// unload it right away. Unset leaves Ollama's default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct HuggingFaceConfig {
     /// HuggingFace API endpoint (default is Inference API)
     #[serde(default = "default_huggingface_endpoint")]
     pub endpoint: String,
-    /// Model to use (e.g., "codellama/CodeLlama-7b-Instruct-hf")
-    #[serde(default = "default_huggingface_model")]
+    /// Model to use when `llm.model` is empty (e.g.,
+    /// "codellama/CodeLlama-7b-Instruct-hf")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// API style: "chat" (OpenAI-compatible) or "tgi" (text-generation-inference)
+    #[serde(default)]
+    pub api_style: HuggingFaceApiStyle,
+    /// Prompt template used with the "tgi" API style
+    #[serde(default)]
+    pub prompt_template: PromptTemplate,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HuggingFaceApiStyle {
+    /// OpenAI-compatible `/chat/completions`
+    #[default]
+    Chat,
+    /// TGI `/generate` and `/generate_stream`
+    Tgi,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OllamaApi {
+    /// `/api/chat` with the message list
+    #[default]
+    Chat,
+    /// `/api/generate` with a single prompt (older Ollama versions)
+    Generate,
+}
+
+/// Chat template used when rendering messages into a single prompt
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptTemplate {
+    /// No model-specific template (role-labeled plain text)
+    #[default]
+    Raw,
+    /// `<|im_start|>role ... <|im_end|>` (Qwen, many fine-tunes)
+    ChatMl,
+    /// Llama 2 `[INST] ... [/INST]`
+    Llama,
+    /// Alpaca `### Instruction:` / `### Response:`
+    Alpaca,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct HttpConfig {
+    /// Maximum idle connections kept per host (reqwest default when unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Seconds an idle pooled connection is kept alive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_idle_timeout_secs: Option<u64>,
+    /// TCP keep-alive interval in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tcp_keepalive_secs: Option<u64>,
+    /// Extra headers sent with every provider and OpenSearch request (e.g.
+    /// `x-org-id`); a credential header a client sets itself (Anthropic's
+    /// `x-api-key`, HuggingFace's and OpenSearch's `authorization`) is
+    /// ignored for that client
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ToolsConfig {
+    /// Validate tool input against the tool's JSON schema before dispatching
+    #[serde(default = "default_true")]
+    pub validate_input: bool,
+    /// Maximum characters of a tool result (all text parts combined) fed
+    /// back to the model; longer results keep their head and tail around a
+    /// truncation marker
+    #[serde(default = "default_max_tool_result_chars")]
+    pub max_result_chars: usize,
+    /// Stream model responses in `run` and `repl` and start each tool call
+    /// as soon as its arguments are complete, instead of after the whole
+    /// response
+    #[serde(default)]
+    pub early_dispatch: bool,
+    /// Print model turns, tool calls, and tool results to stderr as `run`
+    /// and `repl` progress
+    #[serde(default = "default_true")]
+    pub trace: bool,
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self {
+            validate_input: true,
+            max_result_chars: default_max_tool_result_chars(),
+            early_dispatch: false,
+            trace: true,
+        }
+    }
+}
+
+/// Backends and rules for `llm.provider = "router"`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct RouterConfig {
+    /// Backend used when no rule matches
+    #[serde(default)]
+    pub default: String,
+    /// Named backends, each a provider and model
+    #[serde(default)]
+    pub backends: HashMap<String, RouterBackend>,
+    /// Rules checked in order against the latest user message; the first
+    /// match picks the backend
+    #[serde(default)]
+    pub rules: Vec<RouteRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RouterBackend {
+    /// "anthropic", "ollama", or "huggingface"
+    pub provider: String,
+    /// Model name (replaces the provider's configured model)
+    pub model: String,
+}
+
+/// A routing rule; every condition that is set must hold
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RouteRule {
+    /// Backend to use when the rule matches
+    pub backend: String,
+    /// Match when the message contains any of these (case-insensitive)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+    /// Match when the message is at least this many characters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_chars: Option<usize>,
+    /// Match when the message is at most this many characters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_chars: Option<usize>,
+    /// Match when this regular expression matches the message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+}
+
+impl RouterConfig {
+    /// Problems with the backends and rules, for `Config::validate`
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if !self.backends.contains_key(&self.default) {
+            problems.push(format!(
+                "router.default: '{}' is not a configured backend",
+                self.default
+            ));
+        }
+        for (name, backend) in &self.backends {
+            if !matches!(backend.provider.as_str(), "anthropic" | "ollama" | "huggingface") {
+                problems.push(format!(
+                    "router.backends.{}: unknown provider '{}'",
+                    name, backend.provider
+                ));
+            }
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            if !self.backends.contains_key(&rule.backend) {
+                problems.push(format!(
+                    "router.rules[{}]: '{}' is not a configured backend",
+                    i, rule.backend
+                ));
+            }
+            if let Some(pattern) = &rule.pattern {
+                if let Err(e) = regex::Regex::new(pattern) {
+                    problems.push(format!("router.rules[{}].pattern: {}", i, e));
+                }
+            }
+        }
+
+        problems
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct SessionConfig {
+    /// Directory for the file session store, where the REPL saves its
+    /// conversations (platform data dir when unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+    /// Ask the provider for a short title after the first exchange
+    /// (costs one extra request per session)
+    #[serde(default)]
+    pub auto_title: bool,
+}
+
+/// How turns are labeled in the REPL and exported transcripts
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DisplayConfig {
+    /// Label for your turns (the REPL prompt is `<user_name>>`)
+    #[serde(default = "default_user_name")]
+    pub user_name: String,
+    /// Label for the model's turns
+    #[serde(default = "default_assistant_name")]
+    pub assistant_name: String,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            user_name: default_user_name(),
+            assistant_name: default_assistant_name(),
+        }
+    }
+}
+
+/// On-disk cache of provider responses, for resending identical prompts
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
+pub struct CacheConfig {
+    /// Answer repeated requests from the cache instead of the provider
+    #[serde(default)]
+    pub enabled: bool,
+    /// Cache directory (platform cache dir when unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+    /// Also cache requests without `llm.temperature = 0`, whose output
+    /// would otherwise vary between calls
+    #[serde(default)]
+    pub nondeterministic: bool,
+}
+
+/// Embedding model used for semantic session search
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EmbeddingsConfig {
+    /// Embeddings API: "ollama" (`/api/embed`) or "openai" (`/v1/embeddings`)
+    #[serde(default)]
+    pub api: EmbeddingApi,
+    /// API endpoint (defaults to `ollama.endpoint` or https://api.openai.com)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(default = "default_embedding_model")]
     pub model: String,
+    /// Environment variable name for the API key (OpenAI-style APIs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+    /// Vector size produced by the model (used for the kNN index mapping)
+    #[serde(default = "default_embedding_dimensions")]
+    pub dimensions: usize,
+    /// Maximum inputs sent per embeddings request
+    #[serde(default = "default_embedding_batch_size")]
+    pub batch_size: usize,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Default for EmbeddingsConfig {
+    fn default() -> Self {
+        Self {
+            api: EmbeddingApi::default(),
+            endpoint: None,
+            model: default_embedding_model(),
+            api_key_env: None,
+            dimensions: default_embedding_dimensions(),
+            batch_size: default_embedding_batch_size(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingApi {
+    #[default]
+    Ollama,
+    OpenAi,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct OpenSearchConfig {
     pub endpoint: String,
     pub username: String,
@@ -59,7 +561,7 @@ pub struct OpenSearchConfig {
     pub index: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct McpServerConfig {
     pub name: String,
     pub command: String,
@@ -67,12 +569,78 @@ pub struct McpServerConfig {
     pub args: Vec<String>,
     #[serde(default)]
     pub env: HashMap<String, String>,
+    /// Pass this process's environment to the server; when false the server
+    /// sees only `env` (add PATH there if the server needs it)
+    #[serde(default = "default_true")]
+    pub inherit_env: bool,
+    /// Leave `arguments` out of `tools/call` when a tool gets none, for
+    /// servers that reject the default empty object
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub omit_empty_arguments: bool,
+    /// Send messages with `Content-Length` headers (LSP-style framing)
+    /// instead of one per line, for servers that only read that framing
+    ///
+    /// Servers that reply with `Content-Length` framing are switched to it
+    /// automatically.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub content_length_framing: bool,
 }
 
+/// Upper bound accepted for `max_tokens` overrides
+const MAX_TOKENS_LIMIT: usize = 200_000;
+
 fn default_max_tokens() -> usize {
     4096
 }
 
+fn default_max_retries() -> u32 {
+    2
+}
+
+fn default_max_response_bytes() -> usize {
+    10 * 1024 * 1024
+}
+
+fn default_user_name() -> String {
+    "you".to_string()
+}
+
+fn default_assistant_name() -> String {
+    "assistant".to_string()
+}
+
+fn default_stream_buffer_chunks() -> usize {
+    64
+}
+
+fn default_mcp_request_timeout_secs() -> u64 {
+    120
+}
+
+fn default_mcp_startup_concurrency() -> usize {
+    4
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Whether `value` looks like an Ollama `keep_alive`: whole seconds
+/// (possibly negative) or a Go duration such as "5m" or "1h30m"
+fn valid_keep_alive(value: &str) -> bool {
+    let value = value.trim();
+    if value.parse::<i64>().is_ok() {
+        return true;
+    }
+    regex::Regex::new(r"^(\d+(\.\d+)?(ns|us|µs|ms|s|m|h))+$")
+        .map(|re| re.is_match(value))
+        .unwrap_or(false)
+}
+
+fn default_max_tool_result_chars() -> usize {
+    100_000
+}
+
 fn default_ollama_endpoint() -> String {
     "http://localhost:11434".to_string()
 }
@@ -85,6 +653,18 @@ fn default_index() -> String {
     "ollm-sessions".to_string()
 }
 
+fn default_embedding_model() -> String {
+    "nomic-embed-text".to_string()
+}
+
+fn default_embedding_dimensions() -> usize {
+    768
+}
+
+fn default_embedding_batch_size() -> usize {
+    32
+}
+
 fn default_huggingface_endpoint() -> String {
     "https://router.huggingface.co/v1".to_string()
 }
@@ -96,13 +676,7 @@ fn default_huggingface_model() -> String {
 impl Config {
     /// Load configuration from file
     pub fn load(path: Option<PathBuf>) -> Result<Self> {
-        let config_path = path.unwrap_or_else(|| {
-            let mut p = dirs::home_dir().expect("Cannot determine home directory");
-            p.push(".config");
-            p.push("open-llm-code");
-            p.push("config.toml");
-            p
-        });
+        let config_path = path.unwrap_or_else(Self::default_path);
 
         if !config_path.exists() {
             return Err(OllmError::Config(format!(
@@ -122,22 +696,306 @@ impl Config {
         Ok(config)
     }
 
+    /// Where the config lives when `--config` isn't given:
+    /// `~/.config/open-llm-code/config.toml`
+    pub fn default_path() -> PathBuf {
+        let mut p = dirs::home_dir().expect("Cannot determine home directory");
+        p.push(".config");
+        p.push("open-llm-code");
+        p.push("config.toml");
+        p
+    }
+
+    /// Check the loaded config for problems serde can't catch: value ranges,
+    /// endpoint URLs, required secrets, and MCP server definitions
+    ///
+    /// Makes no network calls. All problems are reported in one error.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        match self.llm.provider.as_str() {
+            "anthropic" | "huggingface" => match &self.llm.api_key_env {
+                Some(env_var) if std::env::var_os(env_var).is_none() => {
+                    problems.push(format!("llm.api_key_env: {} is not set", env_var))
+                }
+                Some(_) => {}
+                None => problems.push(format!(
+                    "llm.api_key_env is required for the {} provider",
+                    self.llm.provider
+                )),
+            },
+            "ollama" => {}
+            "router" => problems.extend(self.router.problems()),
+            other => problems.push(format!(
+                "llm.provider: unknown provider '{}' (expected anthropic, ollama, huggingface, or router)",
+                other
+            )),
+        }
+
+        if self.llm.max_tokens == 0 || self.llm.max_tokens > MAX_TOKENS_LIMIT {
+            problems.push(format!(
+                "llm.max_tokens must be between 1 and {}, got {}",
+                MAX_TOKENS_LIMIT, self.llm.max_tokens
+            ));
+        }
+
+        if self.llm.timeout_secs == Some(0) {
+            problems.push("llm.timeout_secs must be at least 1".to_string());
+        }
+        if let Some(temperature) = self.llm.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                problems.push(format!(
+                    "llm.temperature must be between 0 and 2, got {}",
+                    temperature
+                ));
+            }
+        }
+        if self.llm.max_response_bytes == 0 {
+            problems.push("llm.max_response_bytes must be at least 1".to_string());
+        }
+        if self.llm.stream_buffer_chunks == 0 {
+            problems.push("llm.stream_buffer_chunks must be at least 1".to_string());
+        }
+        if self.mcp_request_timeout_secs == 0 {
+            problems.push("mcp_request_timeout_secs must be at least 1".to_string());
+        }
+        if self.mcp_startup_concurrency == 0 {
+            problems.push("mcp_startup_concurrency must be at least 1".to_string());
+        }
+
+        if let Some(budget) = self.anthropic.thinking_budget_tokens {
+            if budget >= self.llm.max_tokens {
+                problems.push(format!(
+                    "anthropic.thinking_budget_tokens ({}) must be below llm.max_tokens ({})",
+                    budget, self.llm.max_tokens
+                ));
+            }
+        }
+
+        let mut endpoints = vec![
+            ("ollama.endpoint", self.ollama.endpoint.as_str()),
+            ("huggingface.endpoint", self.huggingface.endpoint.as_str()),
+            ("opensearch.endpoint", self.opensearch.endpoint.as_str()),
+        ];
+        if let Some(endpoint) = &self.embeddings.endpoint {
+            endpoints.push(("embeddings.endpoint", endpoint));
+        }
+        for (field, endpoint) in endpoints {
+            match reqwest::Url::parse(endpoint) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                Ok(url) => problems.push(format!(
+                    "{}: expected an http(s) URL, got scheme '{}'",
+                    field,
+                    url.scheme()
+                )),
+                Err(e) => problems.push(format!("{}: invalid URL '{}': {}", field, endpoint, e)),
+            }
+        }
+
+        if let Some(keep_alive) = &self.ollama.keep_alive {
+            if !valid_keep_alive(keep_alive) {
+                problems.push(format!(
+                    "ollama.keep_alive: expected a duration like \"5m\" or seconds like \"-1\", got '{}'",
+                    keep_alive
+                ));
+            }
+        }
+
+        for (name, value) in &self.http.headers {
+            if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err()
+                || reqwest::header::HeaderValue::from_str(value).is_err()
+            {
+                problems.push(format!("http.headers.{}: invalid header name or value", name));
+            }
+        }
+
+        if self.tools.max_result_chars == 0 {
+            problems.push("tools.max_result_chars must be at least 1".to_string());
+        }
+        if self.llm.max_tool_description_chars == Some(0) {
+            problems.push("llm.max_tool_description_chars must be at least 1".to_string());
+        }
+
+        if self.embeddings.dimensions == 0 || self.embeddings.batch_size == 0 {
+            problems.push("embeddings.dimensions and batch_size must be at least 1".to_string());
+        }
+
+        let mut names = std::collections::HashSet::new();
+        for server in &self.mcp_servers {
+            if server.name.is_empty() || server.name.contains("::") {
+                problems.push(format!(
+                    "mcp_servers: invalid name '{}' (must be non-empty and not contain '::')",
+                    server.name
+                ));
+            }
+            if !names.insert(server.name.as_str()) {
+                problems.push(format!("mcp_servers: duplicate name '{}'", server.name));
+            }
+            if server.command.trim().is_empty() {
+                problems.push(format!("mcp_servers.{}: command is empty", server.name));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(OllmError::Config(format!(
+                "Invalid config:\n  - {}",
+                problems.join("\n  - ")
+            )))
+        }
+    }
+
+    /// The model the configured provider is built with: `llm.model`, or
+    /// when that's empty the ollama or huggingface section's `model` (then
+    /// that provider's default)
+    ///
+    /// Empty for other providers when `llm.model` is, leaving the provider
+    /// to pick its default.
+    pub fn effective_model(&self) -> String {
+        if !self.llm.model.is_empty() {
+            return self.llm.model.clone();
+        }
+
+        match self.llm.provider.as_str() {
+            "ollama" => self.ollama.model.clone().unwrap_or_else(default_ollama_model),
+            "huggingface" => self
+                .huggingface
+                .model
+                .clone()
+                .unwrap_or_else(default_huggingface_model),
+            _ => String::new(),
+        }
+    }
+
+    /// Settings that are valid but probably not what was meant
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        let section_model = match self.llm.provider.as_str() {
+            "ollama" => self.ollama.model.as_deref(),
+            "huggingface" => self.huggingface.model.as_deref(),
+            _ => None,
+        };
+        if let Some(section_model) = section_model {
+            if !self.llm.model.is_empty() && section_model != self.llm.model {
+                warnings.push(format!(
+                    "{}.model \"{}\" is ignored because llm.model \"{}\" takes precedence; remove one of them",
+                    self.llm.provider, section_model, self.llm.model
+                ));
+            }
+        }
+
+        warnings
+    }
+
+    /// Directory used by the file session store
+    pub fn session_dir(&self) -> PathBuf {
+        self.session
+            .dir
+            .clone()
+            .unwrap_or_else(crate::session::FileSessionStore::default_dir)
+    }
+
+    /// Directory used by the response cache
+    pub fn cache_dir(&self) -> PathBuf {
+        self.cache
+            .dir
+            .clone()
+            .unwrap_or_else(crate::llm::cache::ResponseCache::default_dir)
+    }
+
+    /// Override `llm.max_tokens` for a single run (e.g. from `--max-tokens`)
+    pub fn override_max_tokens(&mut self, max_tokens: usize) -> Result<()> {
+        if max_tokens == 0 || max_tokens > MAX_TOKENS_LIMIT {
+            return Err(OllmError::Config(format!(
+                "max_tokens must be between 1 and {}, got {}",
+                MAX_TOKENS_LIMIT, max_tokens
+            )));
+        }
+
+        self.llm.max_tokens = max_tokens;
+        Ok(())
+    }
+
+    /// Apply a command-line timeout override, rejecting zero
+    pub fn override_timeout(&mut self, timeout_secs: u64) -> Result<()> {
+        if timeout_secs == 0 {
+            return Err(OllmError::Config(
+                "timeout must be at least 1 second".to_string(),
+            ));
+        }
+
+        self.llm.timeout_secs = Some(timeout_secs);
+        Ok(())
+    }
+
+    /// Apply a command-line MCP request timeout override, rejecting zero
+    pub fn override_mcp_timeout(&mut self, timeout_secs: u64) -> Result<()> {
+        if timeout_secs == 0 {
+            return Err(OllmError::Config(
+                "mcp timeout must be at least 1 second".to_string(),
+            ));
+        }
+
+        self.mcp_request_timeout_secs = timeout_secs;
+        Ok(())
+    }
+
+    /// JSON Schema describing the config file, for editor validation
+    pub fn json_schema() -> String {
+        let schema = schemars::schema_for!(Config);
+        serde_json::to_string_pretty(&schema).unwrap()
+    }
+
     /// Generate example configuration
     pub fn example() -> String {
         let example = Config {
+            mcp_enabled: true,
+            mcp_fail_fast: false,
+            mcp_request_timeout_secs: 120,
+            mcp_startup_concurrency: 4,
+            disable_tools: false,
             llm: LlmConfig {
                 provider: "anthropic".to_string(),
                 model: "claude-sonnet-4".to_string(),
                 api_key_env: Some("ANTHROPIC_API_KEY".to_string()),
                 max_tokens: 4096,
+                clamp_max_tokens: true,
+                temperature: None,
+                seed: None,
+                timeout_secs: Some(120),
+                max_retries: 2,
+                system_prompt: None,
+                tool_choice: ToolChoice::Auto,
+                retry_on_empty: false,
+                resume_streaming: false,
+                max_history_messages: None,
+                max_tool_description_chars: None,
+                response_format: ResponseFormat::Text,
+                tool_prompting: false,
+                max_response_bytes: 10 * 1024 * 1024,
+                stream_buffer_chunks: 64,
+            },
+            anthropic: AnthropicConfig {
+                thinking_budget_tokens: None,
+                service_tier: None,
+                user_id: None,
             },
             ollama: OllamaConfig {
                 endpoint: "http://localhost:11434".to_string(),
-                model: "codellama:13b".to_string(),
+                model: None,
+                api: OllamaApi::Chat,
+                prompt_template: PromptTemplate::Raw,
+                native_tools: false,
+                options: serde_json::Map::new(),
+                keep_alive: Some("5m".to_string()),
             },
             huggingface: HuggingFaceConfig {
                 endpoint: "https://api-inference.huggingface.co".to_string(),
-                model: "codellama/CodeLlama-7b-Instruct-hf".to_string(),
+                model: None,
+                api_style: HuggingFaceApiStyle::Chat,
+                prompt_template: PromptTemplate::Raw,
             },
             opensearch: OpenSearchConfig {
                 endpoint: "https://search-example.us-west-2.es.amazonaws.com".to_string(),
@@ -145,12 +1003,68 @@ impl Config {
                 password_env: "OPENSEARCH_PASSWORD".to_string(),
                 index: "ollm-sessions".to_string(),
             },
+            http: HttpConfig {
+                pool_max_idle_per_host: Some(8),
+                pool_idle_timeout_secs: Some(90),
+                tcp_keepalive_secs: Some(60),
+                headers: HashMap::new(),
+            },
+            tools: ToolsConfig {
+                validate_input: true,
+                max_result_chars: 100_000,
+                early_dispatch: false,
+                trace: true,
+            },
+            session: SessionConfig {
+                dir: None,
+                auto_title: false,
+            },
+            display: DisplayConfig::default(),
+            cache: CacheConfig {
+                enabled: false,
+                dir: None,
+                nondeterministic: false,
+            },
+            router: RouterConfig {
+                default: "fast".to_string(),
+                backends: HashMap::from([
+                    (
+                        "fast".to_string(),
+                        RouterBackend {
+                            provider: "ollama".to_string(),
+                            model: "llama3.2:3b".to_string(),
+                        },
+                    ),
+                    (
+                        "strong".to_string(),
+                        RouterBackend {
+                            provider: "anthropic".to_string(),
+                            model: "claude-sonnet-4".to_string(),
+                        },
+                    ),
+                ]),
+                rules: vec![RouteRule {
+                    backend: "strong".to_string(),
+                    keywords: vec!["refactor".to_string(), "architecture".to_string()],
+                    min_chars: None,
+                    max_chars: None,
+                    pattern: None,
+                }],
+            },
+            embeddings: EmbeddingsConfig::default(),
+            presets: HashMap::from([(
+                "concise".to_string(),
+                "Answer in short bullet points.".to_string(),
+            )]),
             mcp_servers: vec![
                 McpServerConfig {
                     name: "claude-ltm".to_string(),
                     command: "cltm-server".to_string(),
                     args: vec![],
                     env: HashMap::new(),
+                    inherit_env: true,
+                    omit_empty_arguments: false,
+                    content_length_framing: false,
                 },
                 McpServerConfig {
                     name: "aws-eks".to_string(),
@@ -161,6 +1075,9 @@ impl Config {
                         env.insert("AWS_REGION".to_string(), "us-west-2".to_string());
                         env
                     },
+                    inherit_env: true,
+                    omit_empty_arguments: false,
+                    content_length_framing: false,
                 },
             ],
         };
@@ -168,3 +1085,219 @@ impl Config {
         toml::to_string_pretty(&example).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Config {
+        toml::from_str(&Config::example()).unwrap()
+    }
+
+    #[test]
+    fn llm_model_is_the_effective_model_for_every_provider() {
+        let mut config = example();
+        config.llm.model = "llama3.1".to_string();
+        config.ollama.model = Some("codellama:13b".to_string());
+
+        for provider in ["anthropic", "ollama", "huggingface"] {
+            config.llm.provider = provider.to_string();
+            assert_eq!(config.effective_model(), "llama3.1");
+        }
+    }
+
+    #[test]
+    fn empty_llm_model_falls_back_to_the_section_then_the_default() {
+        let mut config = example();
+        config.llm.model = String::new();
+        config.llm.provider = "ollama".to_string();
+        assert_eq!(config.effective_model(), default_ollama_model());
+
+        config.ollama.model = Some("qwen2.5-coder".to_string());
+        assert_eq!(config.effective_model(), "qwen2.5-coder");
+
+        config.llm.provider = "anthropic".to_string();
+        assert_eq!(config.effective_model(), "");
+    }
+
+    #[test]
+    fn default_path_is_under_the_home_config_dir() {
+        let path = Config::default_path();
+        assert!(path.starts_with(dirs::home_dir().unwrap()));
+        assert!(
+            path.ends_with(".config/open-llm-code/config.toml"),
+            "{}",
+            path.display()
+        );
+    }
+
+    #[test]
+    fn json_schema_is_valid_and_accepts_the_example() {
+        let schema: serde_json::Value = serde_json::from_str(&Config::json_schema()).unwrap();
+        assert!(jsonschema::meta::is_valid(&schema));
+        assert_eq!(schema["properties"]["mcp_servers"]["type"], "array");
+
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        let example: serde_json::Value = toml::from_str(&Config::example()).unwrap();
+        assert!(!example["mcp_servers"].as_array().unwrap().is_empty());
+        let errors: Vec<String> = validator
+            .iter_errors(&example)
+            .map(|e| e.to_string())
+            .collect();
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let mut broken = example.clone();
+        broken["mcp_servers"][0]["command"] = serde_json::json!(42);
+        assert!(!validator.is_valid(&broken));
+    }
+
+    #[test]
+    fn differing_section_model_is_warned_about() {
+        let mut config = example();
+        config.llm.provider = "ollama".to_string();
+        config.llm.model = "llama3.1".to_string();
+        assert!(config.warnings().is_empty());
+
+        config.ollama.model = Some("llama3.1".to_string());
+        assert!(config.warnings().is_empty());
+
+        config.ollama.model = Some("codellama:13b".to_string());
+        let warnings = config.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("ollama.model \"codellama:13b\" is ignored"));
+
+        // Another provider's section isn't used, so it isn't warned about
+        config.llm.provider = "huggingface".to_string();
+        assert!(config.warnings().is_empty());
+    }
+
+    /// An example config that passes validation without any secrets set
+    fn valid() -> Config {
+        let mut config = example();
+        config.llm.provider = "ollama".to_string();
+        config.llm.api_key_env = None;
+        config
+    }
+
+    /// The validation problems for `config` after `change`, one per line
+    fn problems(change: impl FnOnce(&mut Config)) -> String {
+        let mut config = valid();
+        change(&mut config);
+        match config.validate() {
+            Ok(()) => String::new(),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn example_config_is_valid() {
+        assert_eq!(problems(|_| {}), "");
+
+        // A provider that needs a key is valid once the variable is set
+        assert_eq!(
+            problems(|c| {
+                c.llm.provider = "anthropic".to_string();
+                c.llm.api_key_env = Some("PATH".to_string());
+            }),
+            ""
+        );
+    }
+
+    #[test]
+    fn unknown_provider_is_a_problem() {
+        assert!(problems(|c| c.llm.provider = "openai".to_string())
+            .contains("llm.provider: unknown provider 'openai'"));
+    }
+
+    #[test]
+    fn max_tokens_must_be_in_range() {
+        assert!(problems(|c| c.llm.max_tokens = 0).contains("llm.max_tokens must be between"));
+        assert!(problems(|c| c.llm.max_tokens = MAX_TOKENS_LIMIT + 1)
+            .contains("llm.max_tokens must be between"));
+        assert_eq!(problems(|c| c.llm.max_tokens = MAX_TOKENS_LIMIT), "");
+    }
+
+    #[test]
+    fn endpoints_must_be_http_urls() {
+        assert!(problems(|c| c.ollama.endpoint = "ftp://localhost:11434".to_string())
+            .contains("ollama.endpoint: expected an http(s) URL, got scheme 'ftp'"));
+        assert!(problems(|c| c.huggingface.endpoint = "not a url".to_string())
+            .contains("huggingface.endpoint: invalid URL"));
+    }
+
+    #[test]
+    fn mcp_server_names_must_be_unique_and_without_separator() {
+        let server = |name: &str| McpServerConfig {
+            name: name.to_string(),
+            command: "npx".to_string(),
+            args: Vec::new(),
+            env: HashMap::new(),
+            inherit_env: true,
+            omit_empty_arguments: false,
+            content_length_framing: false,
+        };
+
+        let duplicate = problems(|c| c.mcp_servers = vec![server("files"), server("files")]);
+        assert!(duplicate.contains("mcp_servers: duplicate name 'files'"));
+        let separator = problems(|c| c.mcp_servers = vec![server("a::b")]);
+        assert!(separator.contains("mcp_servers: invalid name 'a::b'"));
+    }
+
+    #[test]
+    fn providers_with_keys_need_api_key_env() {
+        assert!(problems(|c| c.llm.provider = "anthropic".to_string())
+            .contains("llm.api_key_env is required for the anthropic provider"));
+        assert!(problems(|c| {
+            c.llm.provider = "huggingface".to_string();
+            c.llm.api_key_env = Some("OLLM_TEST_UNSET_KEY".to_string());
+        })
+        .contains("llm.api_key_env: OLLM_TEST_UNSET_KEY is not set"));
+    }
+
+    #[test]
+    fn keep_alive_must_be_a_duration_or_seconds() {
+        for value in ["5m", "1h30m", "-1", "300"] {
+            assert_eq!(problems(|c| c.ollama.keep_alive = Some(value.to_string())), "");
+        }
+        assert!(problems(|c| c.ollama.keep_alive = Some("forever".to_string()))
+            .contains("ollama.keep_alive: expected a duration"));
+    }
+
+    #[test]
+    fn thinking_budget_must_be_below_max_tokens() {
+        assert!(problems(|c| {
+            c.llm.max_tokens = 4096;
+            c.anthropic.thinking_budget_tokens = Some(4096);
+        })
+        .contains("anthropic.thinking_budget_tokens (4096) must be below llm.max_tokens (4096)"));
+        assert_eq!(problems(|c| c.anthropic.thinking_budget_tokens = Some(1024)), "");
+    }
+
+    #[test]
+    fn router_problems_are_reported() {
+        let errors = problems(|c| {
+            c.llm.provider = "router".to_string();
+            c.router.default = "missing".to_string();
+            c.router.rules.clear();
+            c.router.backends.insert(
+                "local".to_string(),
+                RouterBackend {
+                    provider: "openai".to_string(),
+                    model: "gpt".to_string(),
+                },
+            );
+            c.router.rules.push(RouteRule {
+                backend: "nowhere".to_string(),
+                keywords: Vec::new(),
+                min_chars: None,
+                max_chars: None,
+                pattern: Some("(".to_string()),
+            });
+        });
+
+        assert!(errors.contains("router.default: 'missing' is not a configured backend"));
+        assert!(errors.contains("router.backends.local: unknown provider 'openai'"));
+        assert!(errors.contains("router.rules[0]: 'nowhere' is not a configured backend"));
+        assert!(errors.contains("router.rules[0].pattern:"));
+    }
+}
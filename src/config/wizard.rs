@@ -0,0 +1,342 @@
+//! Interactive first-run setup for `ollm init --interactive`
+
+use super::{Config, McpServerConfig};
+use crate::error::{OllmError, Result};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// Providers offered by the wizard; "router" needs backends configured by
+/// hand
+const PROVIDERS: &[&str] = &["anthropic", "ollama", "huggingface"];
+
+/// Ask for the provider, model, API key variable, and any MCP servers, then
+/// return the config as TOML, or `None` if the user declines to write it
+///
+/// Everything else keeps the values from `Config::example()`. Reads answers
+/// line by line from `input`, so it can be driven by a script.
+pub fn run<R: BufRead, W: Write>(input: &mut R, output: &mut W) -> Result<Option<String>> {
+    let mut config: Config = toml::from_str(&Config::example())
+        .map_err(|e| OllmError::Config(format!("Failed to parse example config: {}", e)))?;
+
+    let provider = ask(
+        input,
+        output,
+        &format!("Provider ({})", PROVIDERS.join("/")),
+        "anthropic",
+        |answer| {
+            if PROVIDERS.contains(&answer) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "unknown provider; choose one of {}",
+                    PROVIDERS.join(", ")
+                ))
+            }
+        },
+    )?;
+
+    let default_model = match provider.as_str() {
+        "ollama" => super::default_ollama_model(),
+        "huggingface" => super::default_huggingface_model(),
+        _ => config.llm.model.clone(),
+    };
+    let model = ask(input, output, "Model", &default_model, |answer| {
+        if answer.is_empty() {
+            Err("model can't be empty".to_string())
+        } else {
+            Ok(())
+        }
+    })?;
+
+    config.llm.api_key_env = match provider.as_str() {
+        // Local server, no key
+        "ollama" => None,
+        provider => {
+            let default_env = if provider == "huggingface" {
+                "HF_TOKEN"
+            } else {
+                "ANTHROPIC_API_KEY"
+            };
+            Some(ask(
+                input,
+                output,
+                "Environment variable holding the API key",
+                default_env,
+                |answer| {
+                    let mut chars = answer.chars();
+                    let valid = chars
+                        .next()
+                        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+                    if valid {
+                        Ok(())
+                    } else {
+                        Err("expected a variable name like MY_API_KEY".to_string())
+                    }
+                },
+            )?)
+        }
+    };
+
+    config.llm.provider = provider;
+    config.llm.model = model;
+
+    config.mcp_servers = Vec::new();
+    while confirm(input, output, "Add an MCP server?", false)? {
+        let taken: Vec<String> = config.mcp_servers.iter().map(|s| s.name.clone()).collect();
+        let name = ask(input, output, "  Server name", "", |answer| {
+            if answer.is_empty() || answer.contains("::") {
+                Err("name must be non-empty and not contain '::'".to_string())
+            } else if taken.iter().any(|name| name == answer) {
+                Err("a server with that name was already added".to_string())
+            } else {
+                Ok(())
+            }
+        })?;
+        let command = ask(input, output, "  Command", "", |answer| {
+            if answer.is_empty() {
+                Err("command can't be empty".to_string())
+            } else {
+                Ok(())
+            }
+        })?;
+        let args = ask(input, output, "  Arguments (space-separated)", "", |_| {
+            Ok(())
+        })?;
+
+        config.mcp_servers.push(McpServerConfig {
+            name,
+            command,
+            args: args.split_whitespace().map(String::from).collect(),
+            env: HashMap::new(),
+            inherit_env: true,
+            omit_empty_arguments: false,
+            content_length_framing: false,
+        });
+    }
+
+    let toml = toml::to_string_pretty(&config)
+        .map_err(|e| OllmError::Config(format!("Failed to serialize config: {}", e)))?;
+
+    writeln!(output)?;
+    writeln!(
+        output,
+        "Provider: {} ({})",
+        config.llm.provider, config.llm.model
+    )?;
+    if let Some(env_var) = &config.llm.api_key_env {
+        writeln!(output, "API key from: ${}", env_var)?;
+    }
+    writeln!(output, "MCP servers: {}", config.mcp_servers.len())?;
+    for server in &config.mcp_servers {
+        writeln!(
+            output,
+            "   {}: {} {}",
+            server.name,
+            server.command,
+            server.args.join(" ")
+        )?;
+    }
+
+    if confirm(input, output, "Write this config?", true)? {
+        Ok(Some(toml))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Fail if `path` exists, unless `force` allows replacing it
+pub fn check_overwrite(path: &Path, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        return Err(OllmError::Config(format!(
+            "{} already exists (use --force to overwrite)",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Run the wizard and write the config to `path`, returning whether it was
+/// written
+///
+/// An existing file is refused before any question is asked, unless `force`
+/// is set.
+pub fn write_config<R: BufRead, W: Write>(
+    path: &Path,
+    force: bool,
+    input: &mut R,
+    output: &mut W,
+) -> Result<bool> {
+    check_overwrite(path, force)?;
+
+    let Some(contents) = run(input, output)? else {
+        return Ok(false);
+    };
+    std::fs::write(path, contents)
+        .map_err(|e| OllmError::Config(format!("Failed to write config: {}", e)))?;
+    Ok(true)
+}
+
+/// Prompt until `validate` accepts the trimmed answer; an empty answer
+/// takes `default` when there is one
+fn ask<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    prompt: &str,
+    default: &str,
+    validate: impl Fn(&str) -> std::result::Result<(), String>,
+) -> Result<String> {
+    loop {
+        if default.is_empty() {
+            write!(output, "{}: ", prompt)?;
+        } else {
+            write!(output, "{} [{}]: ", prompt, default)?;
+        }
+        output.flush()?;
+
+        let answer = read_answer(input)?;
+        let answer = if answer.is_empty() {
+            default
+        } else {
+            answer.as_str()
+        };
+        match validate(answer) {
+            Ok(()) => return Ok(answer.to_string()),
+            Err(problem) => writeln!(output, "  {}", problem)?,
+        }
+    }
+}
+
+/// Prompt for yes or no; an empty answer takes `default`
+fn confirm<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    prompt: &str,
+    default: bool,
+) -> Result<bool> {
+    loop {
+        write!(
+            output,
+            "{} [{}]: ",
+            prompt,
+            if default { "Y/n" } else { "y/N" }
+        )?;
+        output.flush()?;
+
+        match read_answer(input)?.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => writeln!(output, "  please answer y or n")?,
+        }
+    }
+}
+
+/// One trimmed line of input; running out of input is an error rather than
+/// an endless re-prompt
+fn read_answer<R: BufRead>(input: &mut R) -> Result<String> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Err(OllmError::Config(
+            "Input ended before the setup finished".to_string(),
+        ));
+    }
+    Ok(line.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("ollm-wizard-{}.toml", uuid::Uuid::new_v4()))
+    }
+
+    fn answer(script: &str) -> (Result<Option<String>>, String) {
+        let mut output = Vec::new();
+        let result = run(&mut script.as_bytes(), &mut output);
+        (result, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn scripted_answers_produce_a_config_that_parses() {
+        // Provider, model, one MCP server, no more servers, then write
+        let script = "ollama
+qwen2.5-coder
+y
+files
+npx
+-y server-files /tmp
+n
+
+";
+        let (result, output) = answer(script);
+
+        let config: Config = toml::from_str(&result.unwrap().unwrap()).unwrap();
+        assert_eq!(config.llm.provider, "ollama");
+        assert_eq!(config.llm.model, "qwen2.5-coder");
+        assert_eq!(config.llm.api_key_env, None);
+        assert_eq!(config.mcp_servers.len(), 1);
+        assert_eq!(config.mcp_servers[0].name, "files");
+        assert_eq!(config.mcp_servers[0].args, ["-y", "server-files", "/tmp"]);
+        assert!(config.validate().is_ok());
+        assert!(output.contains("MCP servers: 1"));
+    }
+
+    #[test]
+    fn invalid_answers_are_asked_again() {
+        let script = "openai
+
+
+1BAD
+MY_KEY
+n
+y
+";
+        let (result, output) = answer(script);
+
+        let config: Config = toml::from_str(&result.unwrap().unwrap()).unwrap();
+        assert_eq!(config.llm.provider, "anthropic");
+        assert_eq!(config.llm.api_key_env.as_deref(), Some("MY_KEY"));
+        assert!(output.contains("unknown provider"));
+        assert!(output.contains("expected a variable name"));
+    }
+
+    #[test]
+    fn declining_or_running_out_of_input_writes_nothing() {
+        let (result, _) = answer("
+
+
+n
+n
+");
+        assert!(result.unwrap().is_none());
+
+        let (result, _) = answer("ollama
+");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn existing_config_is_only_replaced_with_force() {
+        let path = temp_path();
+        std::fs::write(&path, "# mine").unwrap();
+        let script = "ollama
+
+n
+
+";
+
+        let error = write_config(&path, false, &mut script.as_bytes(), &mut Vec::new())
+            .unwrap_err();
+        assert!(error.to_string().contains("use --force to overwrite"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# mine");
+
+        assert!(write_config(&path, true, &mut script.as_bytes(), &mut Vec::new()).unwrap());
+        let config: Config = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(config.llm.provider, "ollama");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+use crate::types::{ContentBlock, Message, Role};
+
+/// Message history for a multi-turn conversation that tracks which prefix
+/// has already been sent, so providers can cache it
+///
+/// Each `request()` marks the system prompt, the end of the previously sent
+/// prefix (a cache read) and the end of the new request (a cache write for
+/// the next turn) with `Message::cache_breakpoint`. Providers without prompt
+/// caching ignore the markers.
+#[derive(Debug, Clone, Default)]
+pub struct ConversationBuffer {
+    system: Option<Message>,
+    messages: Vec<Message>,
+    /// Number of messages sent by the previous `request()`
+    sent: usize,
+}
+
+impl ConversationBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a conversation with a system prompt
+    pub fn with_system(system: impl Into<String>) -> Self {
+        Self {
+            system: Some(Message::new_system(system.into())),
+            ..Self::default()
+        }
+    }
+
+    /// Append a message (user turn, assistant reply, or tool results)
+    pub fn push(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    /// Conversation messages, excluding the system prompt
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    pub fn system(&self) -> Option<&Message> {
+        self.system.as_ref()
+    }
+
+    /// Number of leading messages already sent to the provider, which the
+    /// next request can read from the cache
+    pub fn cached_prefix_len(&self) -> usize {
+        self.sent
+    }
+
+    /// Messages for the next provider request, with cache breakpoints set
+    ///
+    /// Records everything returned as sent, so after the caller pushes the
+    /// reply and the next turn, this prefix stays marked as cacheable.
+    pub fn request(&mut self) -> Vec<Message> {
+        let mut request = Vec::with_capacity(self.messages.len() + 1);
+
+        if let Some(system) = &self.system {
+            let mut system = system.clone();
+            system.cache_breakpoint = true;
+            request.push(system);
+        }
+
+        let prior_end = self.sent.checked_sub(1);
+        let end = self.messages.len().checked_sub(1);
+
+        request.extend(self.messages.iter().enumerate().map(|(i, message)| {
+            let mut message = message.clone();
+            message.cache_breakpoint = Some(i) == prior_end || Some(i) == end;
+            message
+        }));
+
+        self.sent = self.messages.len();
+        request
+    }
+
+    /// The whole conversation, system prompt first, without cache
+    /// breakpoints (e.g. for saving)
+    pub fn to_messages(&self) -> Vec<Message> {
+        self.system
+            .iter()
+            .chain(&self.messages)
+            .cloned()
+            .collect()
+    }
+
+    /// Keep only the first `len` messages, e.g. to drop a turn that failed
+    pub fn truncate(&mut self, len: usize) {
+        self.messages.truncate(len);
+        self.sent = self.sent.min(len);
+    }
+
+    /// Drop all messages (keeping the system prompt) and the cache state
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.sent = 0;
+    }
+}
+
+impl From<Vec<Message>> for ConversationBuffer {
+    /// Build a buffer from existing history, splitting out the system prompt
+    fn from(messages: Vec<Message>) -> Self {
+        let mut buffer = Self::new();
+        for message in messages {
+            if message.role == Role::System && buffer.system.is_none() {
+                buffer.system = Some(message);
+            } else {
+                buffer.push(message);
+            }
+        }
+        buffer
+    }
+}
+
+/// Keep the system messages plus at most the `max` most recent others
+///
+/// The kept history starts at a plain user turn, so it never opens with an
+/// assistant reply or with tool results whose tool calls were dropped. If
+/// the window has no such turn (a long tool-calling run), it extends back to
+/// the latest one instead.
+pub fn limit_history(messages: Vec<Message>, max: usize) -> Vec<Message> {
+    let (system, rest): (Vec<Message>, Vec<Message>) =
+        messages.into_iter().partition(|m| m.role == Role::System);
+
+    let is_turn_start = |m: &Message| {
+        m.role == Role::User
+            && !m
+                .content
+                .iter()
+                .any(|c| matches!(c, ContentBlock::ToolResult { .. }))
+    };
+
+    let window = rest.len().saturating_sub(max);
+    let start = (window..rest.len())
+        .find(|&i| is_turn_start(&rest[i]))
+        .or_else(|| (0..window).rev().find(|&i| is_turn_start(&rest[i])))
+        .unwrap_or(window);
+
+    system.into_iter().chain(rest.into_iter().skip(start)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breakpoints(request: &[Message]) -> Vec<bool> {
+        request.iter().map(|m| m.cache_breakpoint).collect()
+    }
+
+    #[test]
+    fn new_turn_keeps_the_prior_prefix_cacheable() {
+        let mut buffer = ConversationBuffer::with_system("Be brief");
+        buffer.push(Message::new_user("hi".to_string()));
+
+        let first = buffer.request();
+        assert_eq!(breakpoints(&first), [true, true]);
+        assert_eq!(buffer.cached_prefix_len(), 1);
+
+        buffer.push(Message::new_assistant(vec![ContentBlock::Text {
+            text: "Hello".to_string(),
+        }]));
+        buffer.push(Message::new_user("how are you?".to_string()));
+
+        // The end of the first request is read from the cache, and the end of
+        // this one written for the next
+        let second = buffer.request();
+        assert_eq!(breakpoints(&second), [true, true, false, true]);
+        assert_eq!(buffer.cached_prefix_len(), 3);
+
+        // The stored history itself carries no breakpoints
+        assert!(buffer.to_messages().iter().all(|m| !m.cache_breakpoint));
+    }
+
+    #[test]
+    fn truncate_forgets_a_failed_turn() {
+        let mut buffer = ConversationBuffer::new();
+        buffer.push(Message::new_user("hi".to_string()));
+        buffer.request();
+
+        buffer.truncate(0);
+        assert!(buffer.messages().is_empty());
+        assert_eq!(buffer.cached_prefix_len(), 0);
+    }
+
+    #[test]
+    fn history_limit_keeps_the_system_prompt() {
+        let assistant = |text: &str| {
+            Message::new_assistant(vec![ContentBlock::Text {
+                text: text.to_string(),
+            }])
+        };
+        let messages = vec![
+            Message::new_system("Be brief".to_string()),
+            Message::new_user("one".to_string()),
+            assistant("1"),
+            Message::new_user("two".to_string()),
+            assistant("2"),
+            Message::new_user("three".to_string()),
+        ];
+
+        let limited = limit_history(messages, 3);
+        let texts: Vec<String> = limited
+            .iter()
+            .map(crate::llm::templates::message_text)
+            .collect();
+        assert_eq!(texts, ["Be brief", "two", "2", "three"]);
+    }
+
+    #[test]
+    fn history_limit_never_starts_with_tool_results() {
+        let messages = vec![
+            Message::new_user("read it".to_string()),
+            Message::new_assistant(vec![ContentBlock::ToolUse {
+                id: "call_1".to_string(),
+                name: "fs::read_file".to_string(),
+                input: serde_json::json!({ "path": "a" }),
+            }]),
+            Message::new_tool_results(vec![ContentBlock::ToolResult {
+                tool_use_id: "call_1".to_string(),
+                content: Vec::new(),
+                is_error: None,
+            }]),
+            Message::new_assistant(vec![ContentBlock::Text {
+                text: "done".to_string(),
+            }]),
+        ];
+
+        // The two most recent messages open with tool results, so the window
+        // extends back to the user turn that led to them
+        let limited = limit_history(messages, 2);
+        assert_eq!(limited.len(), 4);
+        assert_eq!(limited[0].role, Role::User);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::config::Config;
+use crate::error::{OllmError, Result};
+use crate::llm;
+use crate::mcp::McpClient;
+use std::path::PathBuf;
+
+/// Outcome of a single diagnostic check
+struct Check {
+    name: String,
+    passed: bool,
+    critical: bool,
+    detail: String,
+}
+
+impl Check {
+    fn print(&self) {
+        let icon = match (self.passed, self.critical) {
+            (true, _) => "✅",
+            (false, true) => "❌",
+            (false, false) => "⚠️ ",
+        };
+        println!("{} {}: {}", icon, self.name, self.detail);
+    }
+}
+
+/// Run all connectivity checks, printing one line per check
+///
+/// Returns an error if any critical check (config, LLM credentials, LLM
+/// reachability) fails; MCP and OpenSearch failures are reported only.
+pub async fn run(config_path: Option<PathBuf>) -> Result<()> {
+    let mut checks = Vec::new();
+
+    let config = match Config::load(config_path) {
+        Ok(config) => {
+            checks.push(Check {
+                name: "Config".to_string(),
+                passed: true,
+                critical: true,
+                detail: format!("{} ({})", config.llm.provider, config.effective_model()),
+            });
+            config
+        }
+        Err(e) => {
+            let check = Check {
+                name: "Config".to_string(),
+                passed: false,
+                critical: true,
+                detail: e.to_string(),
+            };
+            check.print();
+            return Err(e);
+        }
+    };
+
+    checks.extend(config.warnings().into_iter().map(|warning| Check {
+        name: "Config".to_string(),
+        passed: false,
+        critical: false,
+        detail: warning,
+    }));
+    checks.extend(check_secrets(&config));
+    checks.push(check_llm_endpoint(&config).await);
+    checks.extend(check_mcp_servers(&config).await);
+    checks.push(check_opensearch(&config).await);
+
+    for check in &checks {
+        check.print();
+    }
+
+    let failed = checks.iter().filter(|c| c.critical && !c.passed).count();
+    if failed > 0 {
+        return Err(OllmError::Config(format!(
+            "{} critical check(s) failed",
+            failed
+        )));
+    }
+
+    Ok(())
+}
+
+fn check_secrets(config: &Config) -> Vec<Check> {
+    let mut secrets = Vec::new();
+
+    if matches!(config.llm.provider.as_str(), "anthropic" | "huggingface") {
+        match &config.llm.api_key_env {
+            Some(env_var) => secrets.push((env_var.clone(), true)),
+            None => {
+                return vec![Check {
+                    name: "LLM API key".to_string(),
+                    passed: false,
+                    critical: true,
+                    detail: format!("api_key_env not configured for {}", config.llm.provider),
+                }]
+            }
+        }
+    }
+
+    secrets.push((config.opensearch.password_env.clone(), false));
+
+    secrets
+        .into_iter()
+        .map(|(env_var, critical)| {
+            let passed = std::env::var(&env_var).is_ok();
+            Check {
+                name: format!("Secret {}", env_var),
+                passed,
+                critical,
+                detail: if passed { "set" } else { "not set" }.to_string(),
+            }
+        })
+        .collect()
+}
+
+async fn check_llm_endpoint(config: &Config) -> Check {
+    let url = match config.llm.provider.as_str() {
+        "anthropic" => llm::anthropic::ANTHROPIC_API_BASE.to_string(),
+        "ollama" => format!("{}/api/tags", config.ollama.endpoint),
+        "huggingface" => format!("{}/models", config.huggingface.endpoint),
+        other => {
+            return Check {
+                name: "LLM endpoint".to_string(),
+                passed: false,
+                critical: true,
+                detail: format!("Unknown LLM provider: {}", other),
+            }
+        }
+    };
+
+    ping("LLM endpoint", &url, true, config).await
+}
+
+async fn check_mcp_servers(config: &Config) -> Vec<Check> {
+    let mut checks = Vec::new();
+    if !config.mcp_enabled {
+        return checks;
+    }
+
+    for server in &config.mcp_servers {
+        let name = format!("MCP server {}", server.name);
+        let result = match McpClient::start(
+            server.name.clone(),
+            server.command.clone(),
+            server.args.clone(),
+            server.env.clone(),
+            server.inherit_env,
+            std::time::Duration::from_secs(config.mcp_request_timeout_secs),
+        ) {
+            Ok(mut client) => client.initialize().await.map(|_| client.get_tools().len()),
+            Err(e) => Err(e),
+        };
+
+        checks.push(match result {
+            Ok(tool_count) => Check {
+                name,
+                passed: true,
+                critical: false,
+                detail: format!("initialized, {} tools", tool_count),
+            },
+            Err(e) => Check {
+                name,
+                passed: false,
+                critical: false,
+                detail: e.to_string(),
+            },
+        });
+    }
+
+    checks
+}
+
+async fn check_opensearch(config: &Config) -> Check {
+    ping("OpenSearch", &config.opensearch.endpoint, false, config).await
+}
+
+/// Any HTTP response (even 401/404) counts as reachable
+async fn ping(name: &str, url: &str, critical: bool, config: &Config) -> Check {
+    let result = match llm::http::build_client(config, &[]) {
+        Ok(client) => client
+            .get(url)
+            .timeout(std::time::Duration::from_secs(10))
+            .send()
+            .await
+            .map_err(OllmError::from),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(response) => Check {
+            name: name.to_string(),
+            passed: true,
+            critical,
+            detail: format!("{} reachable (HTTP {})", url, response.status().as_u16()),
+        },
+        Err(e) => Check {
+            name: name.to_string(),
+            passed: false,
+            critical,
+            detail: format!("{} unreachable: {}", url, e),
+        },
+    }
+}
@@ -8,12 +8,33 @@ pub enum OllmError {
     #[error("LLM provider error: {0}")]
     LlmProvider(String),
 
+    #[error("LLM API error {status}: {message}")]
+    LlmApi {
+        status: u16,
+        code: Option<String>,
+        message: String,
+        retryable: bool,
+    },
+
+    /// The provider is over capacity (HTTP 529) or rate limiting (429)
+    #[error("{provider} is overloaded (HTTP {status}): {message}")]
+    Overloaded {
+        provider: String,
+        status: u16,
+        message: String,
+    },
+
     #[error("MCP error: {0}")]
     Mcp(String),
 
     #[error("MCP protocol error: {0}")]
     McpProtocol(String),
 
+    /// A stream failed after producing output; `partial` holds the text
+    /// received before the failure
+    #[error("Stream interrupted: {message}")]
+    StreamInterrupted { message: String, partial: String },
+
     #[error("Session error: {0}")]
     Session(String),
 
@@ -26,6 +47,11 @@ pub enum OllmError {
     #[error("HTTP error: {0}")]
     Http(#[from] reqwest::Error),
 
+    /// No response, or no more of a streamed response, within
+    /// `llm.timeout_secs`
+    #[error("Timed out after {0}s waiting for the provider")]
+    Timeout(u64),
+
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
@@ -40,3 +66,106 @@ pub enum OllmError {
 }
 
 pub type Result<T> = std::result::Result<T, OllmError>;
+
+impl OllmError {
+    /// Build an `LlmApi` error from a non-success HTTP response
+    ///
+    /// Understands the Anthropic (`{"error": {"type", "message"}}`),
+    /// OpenAI-compatible (`{"error": {"code", "message"}}`) and Ollama
+    /// (`{"error": "..."}`) error body shapes, falling back to the raw body.
+    pub fn from_api_response(status: u16, body: &str) -> Self {
+        let parsed: Option<serde_json::Value> = serde_json::from_str(body).ok();
+        let error = parsed.as_ref().and_then(|v| v.get("error"));
+
+        let (code, message) = match error {
+            Some(serde_json::Value::Object(obj)) => {
+                let code = obj
+                    .get("type")
+                    .or_else(|| obj.get("code"))
+                    .and_then(|c| match c {
+                        serde_json::Value::String(s) => Some(s.clone()),
+                        serde_json::Value::Number(n) => Some(n.to_string()),
+                        _ => None,
+                    });
+                let message = obj
+                    .get("message")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or(body)
+                    .to_string();
+                (code, message)
+            }
+            Some(serde_json::Value::String(message)) => (None, message.clone()),
+            _ => (None, body.to_string()),
+        };
+
+        OllmError::LlmApi {
+            status,
+            code,
+            message,
+            retryable: matches!(status, 408 | 429 | 500..=599),
+        }
+    }
+
+    /// Turn a 429 or 529 `LlmApi` error into `Overloaded` for `provider`;
+    /// other errors are returned unchanged
+    pub fn overloaded_for(self, provider: &str) -> Self {
+        match self {
+            OllmError::LlmApi {
+                status: status @ (429 | 529),
+                message,
+                ..
+            } => OllmError::Overloaded {
+                provider: provider.to_string(),
+                status,
+                message,
+            },
+            e => e,
+        }
+    }
+
+    /// Whether retrying the failed operation may succeed
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            OllmError::LlmApi { retryable, .. } => *retryable,
+            OllmError::Overloaded { .. } | OllmError::Timeout(_) => true,
+            OllmError::Http(e) => e.is_timeout() || e.is_connect(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limit_body_becomes_a_retryable_api_error() {
+        let error = OllmError::from_api_response(
+            429,
+            r#"{"type":"error","error":{"type":"rate_limit_error","message":"Number of requests has exceeded your rate limit"}}"#,
+        );
+
+        assert!(error.is_retryable());
+        assert_eq!(
+            error.to_string(),
+            "LLM API error 429: Number of requests has exceeded your rate limit"
+        );
+        match error {
+            OllmError::LlmApi { status, code, .. } => {
+                assert_eq!(status, 429);
+                assert_eq!(code.as_deref(), Some("rate_limit_error"));
+            }
+            e => panic!("expected LlmApi, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn unparseable_body_is_kept_as_the_message() {
+        let error = OllmError::from_api_response(400, "bad request");
+        assert!(!error.is_retryable());
+        assert!(matches!(
+            error,
+            OllmError::LlmApi { status: 400, code: None, ref message, .. } if message == "bad request"
+        ));
+    }
+}
@@ -0,0 +1,85 @@
+//! Number formatting for token counts and rates in CLI output
+//!
+//! Always uses `,` for thousands and `.` for decimals, regardless of locale,
+//! so output is stable for scripts and bug reports.
+
+/// `n` with thousands separators, e.g. `1,234,567`
+pub fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// `value` rounded to `places` decimals, with thousands separators, e.g.
+/// `12,345.6`
+pub fn decimal(value: f64, places: usize) -> String {
+    let rounded = format!("{:.*}", places, value.abs());
+    let (whole, fraction) = match rounded.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (rounded.as_str(), None),
+    };
+
+    let mut out = String::new();
+    if value < 0.0 && rounded.chars().any(|c| c.is_ascii_digit() && c != '0') {
+        out.push('-');
+    }
+    out.push_str(&thousands(whole.parse().unwrap_or(0)));
+    if let Some(fraction) = fraction {
+        out.push('.');
+        out.push_str(fraction);
+    }
+    out
+}
+
+/// Token usage as `1,234 in, 56 out (1,290)`
+pub fn usage(usage: &crate::llm::TokenUsage) -> String {
+    format!(
+        "{} in, {} out ({})",
+        thousands(usage.input_tokens),
+        thousands(usage.output_tokens),
+        thousands(usage.total())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thousands_groups_digits_in_threes() {
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1_000), "1,000");
+        assert_eq!(thousands(123_456), "123,456");
+        assert_eq!(thousands(1_234_567), "1,234,567");
+    }
+
+    #[test]
+    fn decimal_rounds_and_groups_the_whole_part() {
+        assert_eq!(decimal(0.04, 1), "0.0");
+        assert_eq!(decimal(12.345, 0), "12");
+        assert_eq!(decimal(12_345.64, 1), "12,345.6");
+        assert_eq!(decimal(1_234_567.891, 2), "1,234,567.89");
+    }
+
+    #[test]
+    fn decimal_keeps_the_sign_unless_it_rounds_to_zero() {
+        assert_eq!(decimal(-1_500.25, 1), "-1,500.2");
+        assert_eq!(decimal(-0.01, 1), "0.0");
+    }
+
+    #[test]
+    fn usage_shows_both_directions_and_the_total() {
+        let usage = crate::llm::TokenUsage {
+            input_tokens: 1_234,
+            output_tokens: 56,
+        };
+        assert_eq!(super::usage(&usage), "1,234 in, 56 out (1,290)");
+    }
+}
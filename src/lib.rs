@@ -0,0 +1,66 @@
+//! Open LLM Code - pluggable LLM backends with MCP tool support
+//!
+//! The `ollm` binary is a thin CLI over this library. The stable surface is
+//! re-exported at the crate root: `Config`, the `LlmProvider` trait and
+//! `create_provider`, `OllmClient` (with custom provider registration), the
+//! tool-calling `Agent`, conversation types (`Message`, `ContentBlock`,
+//! `Session`), `McpManager`, session stores, and `OllmError`/`Result`.
+//! Modules are public for finer grained access; `diagnose`, `timing` and
+//! `ui` exist to serve the CLI and may change without notice.
+//!
+//! ```
+//! use open_llm_code::llm::mock::MockProvider;
+//! use open_llm_code::{Config, Message, OllmClient};
+//!
+//! # tokio_test::block_on(async {
+//! let config: Config = toml::from_str(
+//!     r#"
+//!     [llm]
+//!     provider = "mock"
+//!     model = "mock"
+//!
+//!     [opensearch]
+//!     endpoint = "http://localhost:9200"
+//!     username = "admin"
+//!     password_env = "OPENSEARCH_PASSWORD"
+//!     "#,
+//! )?;
+//!
+//! let client = OllmClient::builder()
+//!     .config(config)
+//!     .register_provider("mock", |_| Ok(Box::new(MockProvider::echo())))
+//!     .build()?;
+//!
+//! let response = client
+//!     .chat(vec![Message::new_user("hello".to_string())], Vec::new())
+//!     .await?;
+//! assert_eq!(response.usage.output_tokens, 2);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! # }).unwrap();
+//! ```
+
+pub mod agent;
+pub mod client;
+pub mod config;
+pub mod conversation;
+pub mod diagnose;
+pub mod error;
+pub mod format;
+pub mod llm;
+pub mod mcp;
+pub mod retry;
+pub mod session;
+pub mod timing;
+pub mod tools;
+pub mod types;
+pub mod ui;
+
+pub use agent::{Agent, AgentEvent, AgentOutcome};
+pub use client::{OllmClient, OllmClientBuilder};
+pub use config::Config;
+pub use conversation::ConversationBuffer;
+pub use error::{OllmError, Result};
+pub use llm::{create_provider, ChatResponse, LlmProvider, ProviderRegistry};
+pub use mcp::McpManager;
+pub use session::{FileSessionStore, SessionStore};
+pub use types::{ContentBlock, Message, Role, Session, Tool, ToolResultContent};
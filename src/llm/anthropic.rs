@@ -1,24 +1,41 @@
+use super::tools::{to_provider_tool_choice, to_provider_tools, ToolFormat};
 use super::types::*;
 use super::LlmProvider;
+use crate::config::{ResponseFormat, ServiceTier, ToolChoice};
 use crate::error::{OllmError, Result};
-use crate::types::{ContentBlock, Message, Role, Tool};
+use crate::types::{ContentBlock, Message, Role, Tool, ToolResultContent};
 use async_trait::async_trait;
 use eventsource_stream::Eventsource;
 use futures::{Stream, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::pin::Pin;
-use tracing::{debug, info};
+use std::time::Duration;
+use tracing::{debug, info, warn};
 
-const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com/v1";
+pub const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com/v1";
 const DEFAULT_MODEL: &str = "claude-sonnet-4-20250514";
 
+/// Tool forced on the model to emulate `response_format`, since the
+/// Messages API has no JSON mode
+const JSON_RESPONSE_TOOL: &str = "json_response";
+
 pub struct AnthropicProvider {
     client: Client,
+    /// `ANTHROPIC_API_BASE`, or a mock server in tests
+    base_url: String,
     api_key: String,
     model: String,
     max_tokens: usize,
+    thinking_budget_tokens: Option<usize>,
+    service_tier: Option<ServiceTier>,
+    tool_choice: ToolChoice,
+    user_id: Option<String>,
+    max_tool_description_chars: Option<usize>,
+    response_format: ResponseFormat,
+    max_response_bytes: usize,
+    timeout: Option<Duration>,
+    temperature: Option<f32>,
 }
 
 impl AnthropicProvider {
@@ -36,20 +53,161 @@ impl AnthropicProvider {
             ));
         };
 
-        let model = if config.llm.model.is_empty() {
-            DEFAULT_MODEL.to_string()
-        } else {
-            config.llm.model.clone()
+        if config.llm.seed.is_some() {
+            warn!("Anthropic does not support seed - ignoring");
+        }
+
+        // Extended thinking only accepts the default temperature of 1
+        let thinking = config.anthropic.thinking_budget_tokens.is_some();
+        let temperature = match config.llm.temperature {
+            Some(temperature) if thinking => {
+                if temperature != 1.0 {
+                    warn!("temperature can't be changed with extended thinking - ignoring");
+                }
+                None
+            }
+            temperature => temperature,
+        };
+
+        let model = match config.effective_model() {
+            model if model.is_empty() => DEFAULT_MODEL.to_string(),
+            model => model,
         };
 
         Ok(Self {
-            client: Client::new(),
+            client: super::http::build_client(config, &["x-api-key"])?,
+            base_url: ANTHROPIC_API_BASE.to_string(),
             api_key,
+            max_tokens: super::models::clamp_max_tokens(config, &model),
             model,
-            max_tokens: config.llm.max_tokens,
+            thinking_budget_tokens: config.anthropic.thinking_budget_tokens,
+            service_tier: config.anthropic.service_tier,
+            tool_choice: config.llm.tool_choice.clone(),
+            user_id: config.anthropic.user_id.clone(),
+            max_tool_description_chars: config.llm.max_tool_description_chars,
+            response_format: config.llm.response_format.clone(),
+            max_response_bytes: config.llm.max_response_bytes,
+            timeout: super::http::request_timeout(config),
+            temperature,
         })
     }
 
+    /// Build the `/messages` request body
+    fn build_request(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+        stream: bool,
+    ) -> Result<serde_json::Value> {
+        let mut tools = tools;
+        let mut tool_choice = self.tool_choice.clone();
+
+        if let Some(schema) = self.response_format.schema() {
+            if !tools.is_empty() {
+                warn!("response_format replaces tools for Anthropic - ignoring {} tools", tools.len());
+            }
+            tools = vec![Tool {
+                name: JSON_RESPONSE_TOOL.to_string(),
+                description: "Respond with the requested JSON".to_string(),
+                input_schema: schema,
+            }];
+            tool_choice = ToolChoice::Tool(JSON_RESPONSE_TOOL.to_string());
+        } else if !tools.is_empty() {
+            tool_choice.validate(&tools)?;
+        }
+
+        // Extract system message
+        let system_message = messages
+            .iter()
+            .find(|m| m.role == Role::System)
+            .and_then(|m| {
+                m.content.first().and_then(|c| {
+                    if let ContentBlock::Text { text } = c {
+                        Some((text.clone(), m.cache_breakpoint))
+                    } else {
+                        None
+                    }
+                })
+            });
+
+        let breakpoints: Vec<bool> = messages
+            .iter()
+            .filter(|m| m.role != Role::System)
+            .map(|m| m.cache_breakpoint)
+            .collect();
+
+        let mut api_messages = json!(self.convert_messages(messages));
+        let api_tools = to_provider_tools(
+            ToolFormat::Anthropic,
+            &tools,
+            self.max_tool_description_chars,
+        );
+
+        // Cache the prompt prefix ending at each breakpoint message
+        if let Some(api_messages) = api_messages.as_array_mut() {
+            for (message, breakpoint) in api_messages.iter_mut().zip(breakpoints) {
+                if !breakpoint {
+                    continue;
+                }
+                if let Some(last) = message["content"].as_array_mut().and_then(|c| c.last_mut()) {
+                    last["cache_control"] = json!({ "type": "ephemeral" });
+                }
+            }
+        }
+
+        let mut request_body = json!({
+            "model": self.model,
+            "max_tokens": self.max_tokens,
+            "messages": api_messages,
+        });
+
+        if stream {
+            request_body["stream"] = json!(true);
+        }
+
+        if let Some(temperature) = self.temperature {
+            request_body["temperature"] = json!(temperature);
+        }
+
+        if let Some((system, breakpoint)) = system_message {
+            request_body["system"] = if breakpoint {
+                json!([{
+                    "type": "text",
+                    "text": system,
+                    "cache_control": { "type": "ephemeral" },
+                }])
+            } else {
+                json!(system)
+            };
+        }
+
+        if !api_tools.is_empty() {
+            request_body["tools"] = json!(api_tools);
+            if tool_choice != ToolChoice::Auto {
+                if let Some(choice) = to_provider_tool_choice(ToolFormat::Anthropic, &tool_choice) {
+                    request_body["tool_choice"] = choice;
+                }
+            }
+        }
+
+        if let Some(budget) = self.thinking_budget_tokens {
+            request_body["thinking"] = json!({
+                "type": "enabled",
+                "budget_tokens": budget,
+            });
+        }
+
+        if let Some(tier) = self.service_tier {
+            request_body["service_tier"] = json!(tier);
+        }
+
+        if let Some(user_id) = &self.user_id {
+            request_body["metadata"] = json!({ "user_id": user_id });
+        }
+
+        Ok(request_body)
+    }
+
     fn convert_messages(&self, messages: Vec<Message>) -> Vec<ApiMessage> {
         messages
             .into_iter()
@@ -84,29 +242,35 @@ impl AnthropicProvider {
             } => ApiContent::ToolResult {
                 r#type: "tool_result".to_string(),
                 tool_use_id: tool_use_id.clone(),
-                content: content.clone(),
+                content: content.iter().map(ApiToolResultContent::from).collect(),
                 is_error: *is_error,
             },
+            ContentBlock::Thinking {
+                thinking,
+                signature,
+            } => ApiContent::Thinking {
+                r#type: "thinking".to_string(),
+                thinking: thinking.clone(),
+                signature: signature.clone(),
+            },
+            ContentBlock::RedactedThinking { data } => ApiContent::RedactedThinking {
+                r#type: "redacted_thinking".to_string(),
+                data: data.clone(),
+            },
         }
     }
 
-    fn convert_tools(&self, tools: Vec<Tool>) -> Vec<ApiTool> {
-        tools
-            .into_iter()
-            .map(|t| ApiTool {
-                name: t.name,
-                description: t.description,
-                input_schema: t.input_schema,
-            })
-            .collect()
-    }
-
     fn parse_response(&self, response: ApiResponse) -> Result<ChatResponse> {
+        let json_mode = !self.response_format.is_text();
+
         let content = response
             .content
             .into_iter()
             .map(|c| match c {
                 ApiContent::Text { text, .. } => ContentBlock::Text { text },
+                ApiContent::ToolUse { input, .. } if json_mode => ContentBlock::Text {
+                    text: input.to_string(),
+                },
                 ApiContent::ToolUse { id, name, input, .. } => {
                     ContentBlock::ToolUse { id, name, input }
                 }
@@ -117,26 +281,35 @@ impl AnthropicProvider {
                     ..
                 } => ContentBlock::ToolResult {
                     tool_use_id,
-                    content,
+                    content: content.into_iter().map(ToolResultContent::from).collect(),
                     is_error,
                 },
+                ApiContent::Thinking {
+                    thinking,
+                    signature,
+                    ..
+                } => ContentBlock::Thinking {
+                    thinking,
+                    signature,
+                },
+                ApiContent::RedactedThinking { data, .. } => ContentBlock::RedactedThinking { data },
             })
             .collect();
 
+        let mut stop_reason = response.stop_reason.as_deref().and_then(parse_stop_reason);
+        if json_mode && stop_reason == Some(StopReason::ToolUse) {
+            stop_reason = Some(StopReason::EndTurn);
+        }
+
         Ok(ChatResponse {
             content,
             model: response.model,
-            stop_reason: response.stop_reason.and_then(|s| match s.as_str() {
-                "end_turn" => Some(StopReason::EndTurn),
-                "max_tokens" => Some(StopReason::MaxTokens),
-                "stop_sequence" => Some(StopReason::StopSequence),
-                "tool_use" => Some(StopReason::ToolUse),
-                _ => None,
-            }),
+            stop_reason,
             usage: TokenUsage {
                 input_tokens: response.usage.input_tokens,
                 output_tokens: response.usage.output_tokens,
             },
+            service_tier: response.usage.service_tier,
         })
     }
 }
@@ -150,47 +323,16 @@ impl LlmProvider for AnthropicProvider {
             self.model
         );
 
-        // Extract system messages
-        let system_message = messages
-            .iter()
-            .find(|m| m.role == Role::System)
-            .and_then(|m| {
-                m.content.first().and_then(|c| {
-                    if let ContentBlock::Text { text } = c {
-                        Some(text.clone())
-                    } else {
-                        None
-                    }
-                })
-            });
-
-        let api_messages = self.convert_messages(messages);
-        let api_tools = self.convert_tools(tools);
-
-        let mut request_body = json!({
-            "model": self.model,
-            "max_tokens": self.max_tokens,
-            "messages": api_messages,
-        });
+        let request_body = self.build_request(messages, tools, false)?;
 
-        if let Some(system) = system_message {
-            request_body["system"] = json!(system);
-        }
-
-        if !api_tools.is_empty() {
-            request_body["tools"] = json!(api_tools);
-        }
-
-        let response = self
+        let request = self
             .client
-            .post(format!("{}/messages", ANTHROPIC_API_BASE))
+            .post(format!("{}/messages", self.base_url))
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| OllmError::LlmProvider(format!("HTTP request failed: {}", e)))?;
+            .json(&request_body);
+        let response = super::http::send(request, self.timeout).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -198,13 +340,13 @@ impl LlmProvider for AnthropicProvider {
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(OllmError::LlmProvider(format!(
-                "API error {}: {}",
-                status, error_text
-            )));
+            return Err(
+                OllmError::from_api_response(status.as_u16(), &error_text).overloaded_for("Anthropic"),
+            );
         }
 
-        let api_response: ApiResponse = response.json().await.map_err(|e| {
+        let body = super::http::read_body(response, self.max_response_bytes, self.timeout).await?;
+        let api_response: ApiResponse = serde_json::from_slice(&body).map_err(|e| {
             OllmError::LlmProvider(format!("Failed to parse response: {}", e))
         })?;
 
@@ -227,48 +369,16 @@ impl LlmProvider for AnthropicProvider {
             self.model
         );
 
-        // Extract system message
-        let system_message = messages
-            .iter()
-            .find(|m| m.role == Role::System)
-            .and_then(|m| {
-                m.content.first().and_then(|c| {
-                    if let ContentBlock::Text { text } = c {
-                        Some(text.clone())
-                    } else {
-                        None
-                    }
-                })
-            });
-
-        let api_messages = self.convert_messages(messages);
-        let api_tools = self.convert_tools(tools);
-
-        let mut request_body = json!({
-            "model": self.model,
-            "max_tokens": self.max_tokens,
-            "messages": api_messages,
-            "stream": true,
-        });
-
-        if let Some(system) = system_message {
-            request_body["system"] = json!(system);
-        }
+        let request_body = self.build_request(messages, tools, true)?;
 
-        if !api_tools.is_empty() {
-            request_body["tools"] = json!(api_tools);
-        }
-
-        let response = self
+        let request = self
             .client
-            .post(format!("{}/messages", ANTHROPIC_API_BASE))
+            .post(format!("{}/messages", self.base_url))
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| OllmError::LlmProvider(format!("HTTP request failed: {}", e)))?;
+            .json(&request_body);
+        let response = super::http::send(request, self.timeout).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -276,48 +386,62 @@ impl LlmProvider for AnthropicProvider {
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(OllmError::LlmProvider(format!(
-                "API error {}: {}",
-                status, error_text
-            )));
+            return Err(
+                OllmError::from_api_response(status.as_u16(), &error_text).overloaded_for("Anthropic"),
+            );
         }
 
+        let json_mode = !self.response_format.is_text();
+
         // Create event source stream
-        let stream = response
-            .bytes_stream()
+        let stream = super::http::idle_timeout(response.bytes_stream(), self.timeout)
             .eventsource()
-            .map(|event| match event {
-                Ok(event) => {
-                    if event.event == "message_start"
-                        || event.event == "content_block_start"
-                        || event.event == "content_block_delta"
-                        || event.event == "content_block_stop"
-                        || event.event == "message_delta"
-                        || event.event == "message_stop"
-                    {
-                        // Parse the event data as ChatChunk
-                        serde_json::from_str::<ChatChunk>(&event.data)
-                            .map_err(|e| OllmError::LlmProvider(format!("Parse error: {}", e)))
-                    } else if event.event == "ping" {
-                        Ok(ChatChunk::Ping)
-                    } else {
-                        Ok(ChatChunk::Ping) // Ignore unknown events
-                    }
-                }
-                Err(e) => Err(OllmError::LlmProvider(format!("Stream error: {}", e))),
+            .map(move |event| match event {
+                Ok(event) => serde_json::from_str::<StreamEvent>(&event.data)
+                    .map(ChatChunk::from)
+                    .map(|chunk| if json_mode { json_mode_chunk(chunk) } else { chunk })
+                    .map_err(|e| OllmError::LlmProvider(format!("Parse error: {}", e))),
+                // Surface transport failures as a chunk so callers keep the
+                // partial output
+                Err(e) => Ok(ChatChunk::Error {
+                    error: e.to_string(),
+                }),
             });
 
-        Ok(Box::new(Box::pin(stream)))
+        Ok(super::stream::limit_response_bytes(
+            Box::new(Box::pin(stream)),
+            self.max_response_bytes,
+        ))
+    }
+
+    fn request_body(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+        stream: bool,
+    ) -> Result<Option<serde_json::Value>> {
+        self.build_request(messages, tools, stream).map(Some)
     }
 
     fn supports_tools(&self) -> bool {
         true
     }
 
+    fn supports_prefill(&self) -> bool {
+        true
+    }
+
     fn max_tokens(&self) -> usize {
         self.max_tokens
     }
 
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            vision: true,
+            ..ProviderCapabilities::of(self)
+        }
+    }
+
     fn name(&self) -> &str {
         "anthropic"
     }
@@ -327,6 +451,51 @@ impl LlmProvider for AnthropicProvider {
     }
 }
 
+/// Present the forced `json_response` tool call as streamed text
+fn json_mode_chunk(chunk: ChatChunk) -> ChatChunk {
+    match chunk {
+        ChatChunk::ContentBlockStart {
+            index,
+            content_block: ContentBlock::ToolUse { .. },
+        } => ChatChunk::ContentBlockStart {
+            index,
+            content_block: ContentBlock::Text {
+                text: String::new(),
+            },
+        },
+        ChatChunk::ContentBlockDelta {
+            index,
+            delta: ContentDelta::InputJsonDelta { partial_json },
+        } => ChatChunk::ContentBlockDelta {
+            index,
+            delta: ContentDelta::TextDelta { text: partial_json },
+        },
+        ChatChunk::MessageDelta {
+            delta:
+                MessageDelta {
+                    stop_reason: Some(StopReason::ToolUse),
+                    usage,
+                },
+        } => ChatChunk::MessageDelta {
+            delta: MessageDelta {
+                stop_reason: Some(StopReason::EndTurn),
+                usage,
+            },
+        },
+        chunk => chunk,
+    }
+}
+
+fn parse_stop_reason(reason: &str) -> Option<StopReason> {
+    match reason {
+        "end_turn" => Some(StopReason::EndTurn),
+        "max_tokens" => Some(StopReason::MaxTokens),
+        "stop_sequence" => Some(StopReason::StopSequence),
+        "tool_use" => Some(StopReason::ToolUse),
+        _ => None,
+    }
+}
+
 // API types for Anthropic
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -351,17 +520,61 @@ enum ApiContent {
     ToolResult {
         r#type: String,
         tool_use_id: String,
-        content: String,
+        content: Vec<ApiToolResultContent>,
         #[serde(skip_serializing_if = "Option::is_none")]
         is_error: Option<bool>,
     },
+    Thinking {
+        r#type: String,
+        thinking: String,
+        signature: String,
+    },
+    RedactedThinking {
+        r#type: String,
+        data: String,
+    },
+}
+
+/// Tool result content - Anthropic accepts text and base64 image parts
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ApiToolResultContent {
+    Text { text: String },
+    Image { source: ApiImageSource },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct ApiTool {
-    name: String,
-    description: String,
-    input_schema: serde_json::Value,
+struct ApiImageSource {
+    r#type: String,
+    media_type: String,
+    data: String,
+}
+
+impl From<&ToolResultContent> for ApiToolResultContent {
+    fn from(content: &ToolResultContent) -> Self {
+        match content {
+            ToolResultContent::Text { text } => ApiToolResultContent::Text { text: text.clone() },
+            ToolResultContent::Image { data, media_type } => ApiToolResultContent::Image {
+                source: ApiImageSource {
+                    r#type: "base64".to_string(),
+                    media_type: media_type.clone(),
+                    data: data.clone(),
+                },
+            },
+        }
+    }
+}
+
+impl From<ApiToolResultContent> for ToolResultContent {
+    fn from(content: ApiToolResultContent) -> Self {
+        match content {
+            ApiToolResultContent::Text { text } => ToolResultContent::Text { text },
+            ApiToolResultContent::Image { source } => ToolResultContent::Image {
+                data: source.data,
+                media_type: source.media_type,
+            },
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -376,4 +589,612 @@ struct ApiResponse {
 struct ApiUsage {
     input_tokens: usize,
     output_tokens: usize,
+    #[serde(default)]
+    service_tier: Option<String>,
+}
+
+// Streaming event types - the event data nests payloads under `message`,
+// `content_block`, `delta` and `usage`, so it is decoded here and mapped
+// into the provider-agnostic `ChatChunk`
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    MessageStart {
+        message: StreamMessage,
+    },
+    ContentBlockStart {
+        index: usize,
+        content_block: ContentBlock,
+    },
+    ContentBlockDelta {
+        index: usize,
+        delta: ContentDelta,
+    },
+    ContentBlockStop {
+        index: usize,
+    },
+    MessageDelta {
+        delta: StreamMessageDelta,
+        #[serde(default)]
+        usage: Option<StreamUsage>,
+    },
+    MessageStop,
+    Ping,
+    Error {
+        error: StreamError,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamMessage {
+    id: String,
+    role: Role,
+    model: String,
+    #[serde(default)]
+    usage: Option<StreamUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamMessageDelta {
+    #[serde(default)]
+    stop_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamUsage {
+    #[serde(default)]
+    input_tokens: usize,
+    #[serde(default)]
+    output_tokens: usize,
+    #[serde(default)]
+    service_tier: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamError {
+    r#type: String,
+    message: String,
+}
+
+impl From<StreamUsage> for TokenUsage {
+    fn from(usage: StreamUsage) -> Self {
+        TokenUsage {
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+        }
+    }
+}
+
+impl From<StreamEvent> for ChatChunk {
+    fn from(event: StreamEvent) -> Self {
+        match event {
+            StreamEvent::MessageStart { message } => ChatChunk::MessageStart {
+                message: MessageMetadata {
+                    id: message.id,
+                    role: message.role,
+                    model: message.model,
+                    service_tier: message
+                        .usage
+                        .as_ref()
+                        .and_then(|usage| usage.service_tier.clone()),
+                    usage: message.usage.map(TokenUsage::from),
+                },
+            },
+            StreamEvent::ContentBlockStart {
+                index,
+                content_block,
+            } => ChatChunk::ContentBlockStart {
+                index,
+                content_block,
+            },
+            StreamEvent::ContentBlockDelta { index, delta } => {
+                ChatChunk::ContentBlockDelta { index, delta }
+            }
+            StreamEvent::ContentBlockStop { index } => ChatChunk::ContentBlockStop { index },
+            StreamEvent::MessageDelta { delta, usage } => ChatChunk::MessageDelta {
+                delta: MessageDelta {
+                    stop_reason: delta.stop_reason.as_deref().and_then(parse_stop_reason),
+                    usage: usage.map(TokenUsage::from),
+                },
+            },
+            StreamEvent::MessageStop => ChatChunk::MessageStop,
+            StreamEvent::Ping | StreamEvent::Unknown => ChatChunk::Ping,
+            StreamEvent::Error { error } => ChatChunk::Error {
+                error: format!("{}: {}", error.r#type, error.message),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn provider(configure: impl FnOnce(&mut Config)) -> AnthropicProvider {
+        std::env::set_var("OLLM_TEST_ANTHROPIC_KEY", "test-key");
+        let mut config: Config = toml::from_str(&Config::example()).unwrap();
+        config.llm.api_key_env = Some("OLLM_TEST_ANTHROPIC_KEY".to_string());
+        configure(&mut config);
+        AnthropicProvider::new(&config).unwrap()
+    }
+
+    /// A tool call and its result, failed or not
+    fn tool_exchange(failed: bool) -> Vec<Message> {
+        vec![
+            Message::new_user("Read it".to_string()),
+            Message::new_assistant(vec![ContentBlock::ToolUse {
+                id: "toolu_1".to_string(),
+                name: "files::read".to_string(),
+                input: json!({ "path": "missing.txt" }),
+            }]),
+            Message::new_tool_results(vec![ContentBlock::ToolResult {
+                tool_use_id: "toolu_1".to_string(),
+                content: vec![ToolResultContent::text("No such file")],
+                is_error: failed.then_some(true),
+            }]),
+        ]
+    }
+
+    #[test]
+    fn failed_tool_result_is_marked_as_an_error() {
+        let provider = provider(|_| {});
+
+        let body = provider.build_request(tool_exchange(true), vec![], false).unwrap();
+        let result = &body["messages"][2]["content"][0];
+        assert_eq!(result["type"], "tool_result");
+        assert_eq!(result["tool_use_id"], "toolu_1");
+        assert_eq!(result["is_error"], json!(true));
+
+        let body = provider.build_request(tool_exchange(false), vec![], false).unwrap();
+        assert!(body["messages"][2]["content"][0].get("is_error").is_none());
+    }
+
+    #[test]
+    fn temperature_is_omitted_with_thinking() {
+        let body = provider(|config| config.llm.temperature = Some(0.2))
+            .build_request(vec![Message::new_user("hi".to_string())], vec![], false)
+            .unwrap();
+        assert_eq!(body["temperature"], json!(0.2f32));
+
+        let body = provider(|config| {
+            config.llm.temperature = Some(0.2);
+            config.anthropic.thinking_budget_tokens = Some(1024);
+        })
+        .build_request(vec![Message::new_user("hi".to_string())], vec![], false)
+        .unwrap();
+        assert!(body.get("temperature").is_none());
+        assert_eq!(body["thinking"]["budget_tokens"], 1024);
+    }
+
+    #[test]
+    fn redacted_thinking_round_trips() {
+        let provider = provider(|_| {});
+        let response: ApiResponse = serde_json::from_value(json!({
+            "content": [
+                { "type": "redacted_thinking", "data": "opaque" },
+                { "type": "text", "text": "Done" },
+            ],
+            "model": "claude-sonnet-4",
+            "stop_reason": "end_turn",
+            "usage": { "input_tokens": 1, "output_tokens": 2 },
+        }))
+        .unwrap();
+        let response = provider.parse_response(response).unwrap();
+        assert!(matches!(
+            &response.content[0],
+            ContentBlock::RedactedThinking { data } if data == "opaque"
+        ));
+
+        let body = provider
+            .build_request(
+                vec![
+                    Message::new_user("hi".to_string()),
+                    Message::new_assistant(response.content),
+                ],
+                vec![],
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            body["messages"][1]["content"][0],
+            json!({ "type": "redacted_thinking", "data": "opaque" })
+        );
+    }
+
+    #[test]
+    fn redacted_thinking_stream_block_parses() {
+        let event: StreamEvent = serde_json::from_value(json!({
+            "type": "content_block_start",
+            "index": 0,
+            "content_block": { "type": "redacted_thinking", "data": "opaque" },
+        }))
+        .unwrap();
+        assert!(matches!(
+            ChatChunk::from(event),
+            ChatChunk::ContentBlockStart {
+                content_block: ContentBlock::RedactedThinking { .. },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn mixed_tool_result_keeps_text_and_image_parts() {
+        let result = ContentBlock::ToolResult {
+            tool_use_id: "toolu_1".to_string(),
+            content: vec![
+                ToolResultContent::text("A chart"),
+                ToolResultContent::Image {
+                    data: "iVBORw0=".to_string(),
+                    media_type: "image/png".to_string(),
+                },
+            ],
+            is_error: None,
+        };
+
+        let body = provider(|_| {})
+            .build_request(
+                vec![
+                    Message::new_user("plot it".to_string()),
+                    Message::new_assistant(vec![ContentBlock::ToolUse {
+                        id: "toolu_1".to_string(),
+                        name: "plot::chart".to_string(),
+                        input: json!({}),
+                    }]),
+                    Message::new_tool_results(vec![result]),
+                ],
+                vec![],
+                false,
+            )
+            .unwrap();
+
+        assert_eq!(
+            body["messages"][2]["content"][0],
+            json!({
+                "type": "tool_result",
+                "tool_use_id": "toolu_1",
+                "content": [
+                    { "type": "text", "text": "A chart" },
+                    {
+                        "type": "image",
+                        "source": { "type": "base64", "media_type": "image/png", "data": "iVBORw0=" },
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn service_tier_is_sent_when_configured() {
+        let body = provider(|_| {})
+            .build_request(vec![Message::new_user("hi".to_string())], vec![], false)
+            .unwrap();
+        assert!(body.get("service_tier").is_none());
+
+        let body = provider(|config| {
+            config.anthropic.service_tier = Some(ServiceTier::StandardOnly);
+        })
+        .build_request(vec![Message::new_user("hi".to_string())], vec![], false)
+        .unwrap();
+        assert_eq!(body["service_tier"], "standard_only");
+
+        let invalid = toml::from_str::<crate::config::AnthropicConfig>(r#"service_tier = "priority""#);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn user_id_is_sent_only_when_configured() {
+        let body = provider(|_| {})
+            .build_request(vec![Message::new_user("hi".to_string())], vec![], false)
+            .unwrap();
+        assert!(body.get("metadata").is_none());
+
+        let body = provider(|config| {
+            config.anthropic.user_id = Some("user-1234".to_string());
+        })
+        .build_request(vec![Message::new_user("hi".to_string())], vec![], false)
+        .unwrap();
+        assert_eq!(body["metadata"], json!({ "user_id": "user-1234" }));
+    }
+
+    #[test]
+    fn streamed_service_tier_reaches_the_response() {
+        let event: StreamEvent = serde_json::from_value(json!({
+            "type": "message_start",
+            "message": {
+                "id": "msg_1",
+                "role": "assistant",
+                "model": "claude-sonnet-4",
+                "usage": { "input_tokens": 5, "output_tokens": 1, "service_tier": "standard" },
+            },
+        }))
+        .unwrap();
+
+        let mut accumulator = super::super::StreamAccumulator::new("claude-sonnet-4".to_string());
+        accumulator.push(ChatChunk::from(event)).unwrap();
+        let response = accumulator.finish().unwrap();
+        assert_eq!(response.service_tier.as_deref(), Some("standard"));
+        assert_eq!(response.usage.input_tokens, 5);
+    }
+
+    #[test]
+    fn tool_choice_variants_serialize() {
+        let tools = vec![Tool {
+            name: "fs::read_file".to_string(),
+            description: "Read a file".to_string(),
+            input_schema: json!({ "type": "object" }),
+        }];
+        let body = |choice: ToolChoice| {
+            provider(|config| config.llm.tool_choice = choice)
+                .build_request(vec![Message::new_user("hi".to_string())], tools.clone(), false)
+        };
+
+        // Auto is the API default, so it's left out
+        assert!(body(ToolChoice::Auto).unwrap().get("tool_choice").is_none());
+        assert_eq!(body(ToolChoice::Any).unwrap()["tool_choice"], json!({ "type": "any" }));
+        assert_eq!(body(ToolChoice::None).unwrap()["tool_choice"], json!({ "type": "none" }));
+        assert_eq!(
+            body(ToolChoice::Tool("fs::read_file".to_string())).unwrap()["tool_choice"],
+            json!({ "type": "tool", "name": "fs::read_file" })
+        );
+
+        let error = body(ToolChoice::Tool("fs::write_file".to_string())).unwrap_err();
+        assert!(error.to_string().contains("'fs::write_file', which is not among"));
+    }
+
+    #[test]
+    fn prefill_is_sent_as_the_last_message() {
+        let provider = crate::llm::RetryingProvider::new(Box::new(provider(|_| {})), 0);
+        assert!(provider.supports_prefill());
+
+        let mut messages = vec![Message::new_user("List three colors as JSON".to_string())];
+        crate::llm::push_prefill(&provider, &mut messages, "{\n");
+
+        let body = provider.request_body(messages, vec![], false).unwrap().unwrap();
+        let last = body["messages"].as_array().unwrap().last().unwrap().clone();
+        assert_eq!(last["role"], "assistant");
+        assert_eq!(last["content"][0]["text"], "{");
+    }
+
+    /// A provider sending to `server` instead of the real API
+    fn mock_provider(server: &mockito::Server) -> AnthropicProvider {
+        let mut provider = provider(|_| {});
+        provider.base_url = server.url();
+        provider
+    }
+
+    #[tokio::test]
+    async fn overloaded_api_is_retried_then_reported() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/messages")
+            .with_status(529)
+            .with_body(r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#)
+            // One call without retries, then the first try and two retries
+            .expect(4)
+            .create_async()
+            .await;
+
+        let error = mock_provider(&server)
+            .chat(vec![Message::new_user("hi".to_string())], vec![])
+            .await
+            .unwrap_err();
+        assert!(matches!(error, OllmError::Overloaded { status: 529, .. }));
+        assert!(error.is_retryable());
+
+        let policy = crate::retry::BackoffPolicy {
+            initial_delay: Duration::from_millis(1),
+            jitter: false,
+            ..crate::retry::BackoffPolicy::new(2)
+        };
+        let retrying =
+            crate::llm::RetryingProvider::with_policy(Box::new(mock_provider(&server)), policy);
+        let error = retrying
+            .chat(vec![Message::new_user("hi".to_string())], vec![])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Anthropic is overloaded (HTTP 529): Overloaded (still overloaded after 2 retries; try again later)"
+        );
+
+        mock.assert_async().await;
+    }
+
+    /// A Messages API response with extended thinking, as recorded
+    const THINKING_RESPONSE: &str = r#"{
+        "id": "msg_01AgDnqEYhJX3LR8kCwRTBBL",
+        "type": "message",
+        "role": "assistant",
+        "model": "claude-sonnet-4-20250514",
+        "content": [
+            {
+                "type": "thinking",
+                "thinking": "The user wants the sum. 2 + 2 = 4.",
+                "signature": "EqQBCgIYAhIM1gbcDa9GJwZA2b3hGgxBdjrkzLoky3dl1pkiMOYds"
+            },
+            { "type": "text", "text": "2 + 2 = 4" }
+        ],
+        "stop_reason": "end_turn",
+        "stop_sequence": null,
+        "usage": {
+            "input_tokens": 42,
+            "cache_creation_input_tokens": 0,
+            "cache_read_input_tokens": 0,
+            "output_tokens": 57,
+            "service_tier": "standard"
+        }
+    }"#;
+
+    #[tokio::test]
+    async fn thinking_block_with_signature_is_parsed_and_sent_back() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/messages")
+            .with_body(THINKING_RESPONSE)
+            .create_async()
+            .await;
+
+        let mut provider = provider(|config| {
+            config.llm.temperature = Some(0.2);
+            config.anthropic.thinking_budget_tokens = Some(1024);
+        });
+        provider.base_url = server.url();
+
+        let response = provider
+            .chat(vec![Message::new_user("What is 2 + 2?".to_string())], vec![])
+            .await
+            .unwrap();
+        assert!(matches!(
+            &response.content[0],
+            ContentBlock::Thinking { thinking, signature }
+                if thinking.starts_with("The user wants") && signature.starts_with("EqQB")
+        ));
+        assert_eq!(response.text(), "2 + 2 = 4");
+        assert_eq!(response.usage.output_tokens, 57);
+
+        // The signed block goes back unchanged, and temperature stays out
+        // while thinking is on
+        let body = provider
+            .build_request(
+                vec![
+                    Message::new_user("What is 2 + 2?".to_string()),
+                    Message::new_assistant(response.content),
+                    Message::new_user("And 3 + 3?".to_string()),
+                ],
+                vec![],
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            body["messages"][1]["content"][0]["signature"],
+            "EqQBCgIYAhIM1gbcDa9GJwZA2b3hGgxBdjrkzLoky3dl1pkiMOYds"
+        );
+        assert!(body.get("temperature").is_none());
+    }
+
+    /// A streamed Messages API response with text and a tool call, as
+    /// recorded (including the fields the decoder ignores)
+    const TOOL_USE_STREAM: &str = concat!(
+        "event: message_start\n",
+        r#"data: {"type":"message_start","message":{"id":"msg_014p7gG3wDgGV9EUtLvnow3U","type":"message","role":"assistant","model":"claude-sonnet-4-20250514","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":472,"cache_creation_input_tokens":0,"cache_read_input_tokens":0,"output_tokens":2,"service_tier":"standard"}}}"#,
+        "\n\n",
+        "event: content_block_start\n",
+        r#"data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+        "\n\n",
+        "event: ping\n",
+        r#"data: {"type": "ping"}"#,
+        "\n\n",
+        "event: content_block_delta\n",
+        r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Let me check "}}"#,
+        "\n\n",
+        "event: content_block_delta\n",
+        r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"the weather."}}"#,
+        "\n\n",
+        "event: content_block_stop\n",
+        r#"data: {"type":"content_block_stop","index":0}"#,
+        "\n\n",
+        "event: content_block_start\n",
+        r#"data: {"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_01T1x1fJ34qAmk2tNTrN7Up6","name":"weather::get","input":{}}}"#,
+        "\n\n",
+        "event: content_block_delta\n",
+        r#"data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":""}}"#,
+        "\n\n",
+        "event: content_block_delta\n",
+        r#"data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"location\": \"San Fra"}}"#,
+        "\n\n",
+        "event: content_block_delta\n",
+        r#"data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"ncisco, CA\"}"}}"#,
+        "\n\n",
+        "event: content_block_stop\n",
+        r#"data: {"type":"content_block_stop","index":1}"#,
+        "\n\n",
+        "event: message_delta\n",
+        r#"data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":89}}"#,
+        "\n\n",
+        "event: message_stop\n",
+        r#"data: {"type":"message_stop"}"#,
+        "\n\n",
+    );
+
+    #[tokio::test]
+    async fn recorded_stream_is_decoded() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/messages")
+            .with_header("content-type", "text/event-stream")
+            .with_body(TOOL_USE_STREAM)
+            .create_async()
+            .await;
+
+        let stream = mock_provider(&server)
+            .stream_chat(vec![Message::new_user("Weather in SF?".to_string())], vec![])
+            .await
+            .unwrap();
+        let chunks: Vec<ChatChunk> = stream.map(|chunk| chunk.unwrap()).collect().await;
+        assert!(matches!(
+            &chunks[0],
+            ChatChunk::MessageStart { message }
+                if message.id == "msg_014p7gG3wDgGV9EUtLvnow3U"
+                    && message.service_tier.as_deref() == Some("standard")
+        ));
+        assert!(matches!(chunks.last(), Some(ChatChunk::MessageStop)));
+
+        let mut accumulator = crate::llm::StreamAccumulator::new(String::new());
+        for chunk in chunks {
+            accumulator.push(chunk).unwrap();
+        }
+        let response = accumulator.finish().unwrap();
+        assert_eq!(response.model, "claude-sonnet-4-20250514");
+        assert_eq!(response.text(), "Let me check the weather.");
+        assert!(matches!(
+            response.tool_uses()[..],
+            [ContentBlock::ToolUse { id, name, input }]
+                if id == "toolu_01T1x1fJ34qAmk2tNTrN7Up6"
+                    && name == "weather::get"
+                    && input == &json!({ "location": "San Francisco, CA" })
+        ));
+        assert_eq!(response.stop_reason, Some(StopReason::ToolUse));
+        assert_eq!(response.usage.input_tokens, 472);
+        assert_eq!(response.usage.output_tokens, 89);
+    }
+
+    #[tokio::test]
+    async fn max_tokens_stop_reason_reaches_the_renderer() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/messages")
+            .with_header("content-type", "text/event-stream")
+            .with_body(concat!(
+                "event: message_start\n",
+                r#"data: {"type":"message_start","message":{"id":"msg_1","type":"message","role":"assistant","model":"claude-sonnet-4-20250514","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":8,"output_tokens":1}}}"#,
+                "\n\n",
+                "event: content_block_start\n",
+                r#"data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+                "\n\n",
+                "event: content_block_delta\n",
+                r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Once upon"}}"#,
+                "\n\n",
+                "event: content_block_stop\n",
+                r#"data: {"type":"content_block_stop","index":0}"#,
+                "\n\n",
+                "event: message_delta\n",
+                r#"data: {"type":"message_delta","delta":{"stop_reason":"max_tokens","stop_sequence":null},"usage":{"output_tokens":2}}"#,
+                "\n\n",
+                "event: message_stop\n",
+                r#"data: {"type":"message_stop"}"#,
+                "\n\n",
+            ))
+            .create_async()
+            .await;
+
+        let (stop_reason, stops) =
+            crate::ui::renderer::tests::streamed_stop_reasons(&mock_provider(&server)).await;
+        assert_eq!(stop_reason, Some(StopReason::MaxTokens));
+        assert_eq!(stops, [StopReason::MaxTokens]);
+    }
 }
@@ -0,0 +1,395 @@
+use super::stream::{response_chunks, with_usage_updates, StreamAccumulator};
+use super::types::*;
+use super::LlmProvider;
+use crate::error::Result;
+use crate::types::{Message, Tool};
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+/// On-disk store of responses, one JSON file per request hash
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Default location: `<cache dir>/open-llm-code/responses`
+    pub fn default_dir() -> PathBuf {
+        let mut p = dirs::cache_dir().expect("Cannot determine cache directory");
+        p.push("open-llm-code");
+        p.push("responses");
+        p
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Stored response for `key`; unreadable entries count as misses
+    pub async fn get(&self, key: &str) -> Option<ChatResponse> {
+        let data = tokio::fs::read(self.path_for(key)).await.ok()?;
+        match serde_json::from_slice(&data) {
+            Ok(response) => Some(response),
+            Err(e) => {
+                warn!("Ignoring unreadable cache entry {}: {}", key, e);
+                None
+            }
+        }
+    }
+
+    pub async fn put(&self, key: &str, response: &ChatResponse) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(self.path_for(key), serde_json::to_vec(response)?).await?;
+        Ok(())
+    }
+
+    /// Delete every cached response, returning how many were removed
+    pub async fn clear(&self) -> Result<usize> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut removed = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("json") {
+                tokio::fs::remove_file(path).await?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Wraps a provider and answers repeated requests from a `ResponseCache`
+///
+/// The key hashes the provider, model, messages, tools, and `params` (the
+/// settings that affect output). Streamed responses are cached before their
+/// `MessageStop` is passed on, and hits are replayed as a stream.
+pub struct CachingProvider {
+    inner: Box<dyn LlmProvider>,
+    cache: ResponseCache,
+    params: Value,
+}
+
+impl CachingProvider {
+    pub fn new(inner: Box<dyn LlmProvider>, cache: ResponseCache, params: Value) -> Self {
+        Self {
+            inner,
+            cache,
+            params,
+        }
+    }
+
+    fn key(&self, messages: &[Message], tools: &[Tool]) -> String {
+        // Only what the provider sees: ids, timestamps, and metadata differ
+        // between otherwise identical requests
+        let messages: Vec<Value> = messages
+            .iter()
+            .map(|m| json!({ "role": m.role, "content": m.content }))
+            .collect();
+        // Tools are gathered from several servers; their order says nothing
+        // about the request
+        let mut tools: Vec<&Tool> = tools.iter().collect();
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+        let request = json!({
+            "provider": self.inner.name(),
+            "model": self.inner.model(),
+            "messages": messages,
+            "tools": tools,
+            "params": self.params,
+        });
+
+        let mut hasher = Sha256::new();
+        hasher.update(request.to_string().as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+async fn store(cache: &ResponseCache, key: &str, response: &ChatResponse) {
+    if let Err(e) = cache.put(key, response).await {
+        warn!("Failed to cache response: {}", e);
+    }
+}
+
+/// Pass `stream` through, accumulating the chunks `chunk_of` finds in its
+/// items and storing the response under `key` before the item carrying
+/// `MessageStop` is yielded
+///
+/// A stream that fails or ends early isn't cached.
+fn cache_on_stop<T: Send + 'static>(
+    stream: Box<dyn Stream<Item = Result<T>> + Send + Unpin>,
+    cache: ResponseCache,
+    key: String,
+    model: String,
+    chunk_of: fn(&T) -> Option<&ChatChunk>,
+) -> Box<dyn Stream<Item = Result<T>> + Send + Unpin> {
+    let accumulator = Some(StreamAccumulator::new(model));
+
+    let stream = stream.scan(accumulator, move |accumulator, item| {
+        let mut response = None;
+        if let (Some(chunk), Some(acc)) =
+            (item.as_ref().ok().and_then(chunk_of), accumulator.as_mut())
+        {
+            if acc.push(chunk.clone()).is_err() {
+                *accumulator = None;
+            } else if matches!(chunk, ChatChunk::MessageStop) {
+                response = accumulator.take().and_then(|acc| acc.finish().ok());
+            }
+        }
+
+        let cache = cache.clone();
+        let key = key.clone();
+        async move {
+            if let Some(response) = response {
+                store(&cache, &key, &response).await;
+            }
+            Some(item)
+        }
+    });
+
+    Box::new(Box::pin(stream))
+}
+
+#[async_trait]
+impl LlmProvider for CachingProvider {
+    async fn chat(&self, messages: Vec<Message>, tools: Vec<Tool>) -> Result<ChatResponse> {
+        let key = self.key(&messages, &tools);
+        if let Some(response) = self.cache.get(&key).await {
+            debug!("Response cache hit: {}", key);
+            return Ok(response);
+        }
+
+        let response = self.inner.chat(messages, tools).await?;
+        store(&self.cache, &key, &response).await;
+        Ok(response)
+    }
+
+    async fn stream_chat(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>> {
+        let key = self.key(&messages, &tools);
+        if let Some(response) = self.cache.get(&key).await {
+            debug!("Response cache hit: {}", key);
+            return Ok(Box::new(futures::stream::iter(response_chunks(response))));
+        }
+
+        let stream = self.inner.stream_chat(messages, tools).await?;
+        Ok(cache_on_stop(
+            stream,
+            self.cache.clone(),
+            key,
+            self.inner.model().to_string(),
+            |chunk| Some(chunk),
+        ))
+    }
+
+    async fn stream_chat_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Box<dyn Stream<Item = Result<StreamEvent>> + Send + Unpin>> {
+        let key = self.key(&messages, &tools);
+        if let Some(response) = self.cache.get(&key).await {
+            debug!("Response cache hit: {}", key);
+            let chunks = futures::stream::iter(response_chunks(response));
+            return Ok(with_usage_updates(Box::new(chunks), false));
+        }
+
+        let stream = self.inner.stream_chat_events(messages, tools).await?;
+        Ok(cache_on_stop(
+            stream,
+            self.cache.clone(),
+            key,
+            self.inner.model().to_string(),
+            |event| match event {
+                StreamEvent::Chunk(chunk) => Some(chunk),
+                StreamEvent::UsageUpdate { .. } => None,
+            },
+        ))
+    }
+
+    fn request_body(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+        stream: bool,
+    ) -> Result<Option<serde_json::Value>> {
+        self.inner.request_body(messages, tools, stream)
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.inner.supports_tools()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    fn supports_prefill(&self) -> bool {
+        self.inner.supports_prefill()
+    }
+
+    fn estimate_tokens(&self, text: &str) -> usize {
+        self.inner.estimate_tokens(text)
+    }
+
+    fn max_tokens(&self) -> usize {
+        self.inner.max_tokens()
+    }
+
+    fn model_max_output_tokens(&self) -> Option<usize> {
+        self.inner.model_max_output_tokens()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock::MockProvider;
+
+    /// A caching provider over a mock that answers "first", then "second",
+    /// storing into a fresh directory
+    fn provider(params: Value) -> (CachingProvider, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("ollm-cache-test-{}", uuid::Uuid::new_v4()));
+        let inner = MockProvider::with_responses(["first", "second"]);
+        let provider =
+            CachingProvider::new(Box::new(inner), ResponseCache::new(dir.clone()), params);
+        (provider, dir)
+    }
+
+    fn messages() -> Vec<Message> {
+        vec![Message::new_user("hello".to_string())]
+    }
+
+    #[tokio::test]
+    async fn repeated_request_is_a_hit() {
+        let (provider, dir) = provider(json!({ "temperature": 0.0 }));
+
+        assert_eq!(
+            provider.chat(messages(), vec![]).await.unwrap().text(),
+            "first"
+        );
+        assert_eq!(
+            provider.chat(messages(), vec![]).await.unwrap().text(),
+            "first"
+        );
+
+        let other = vec![Message::new_user("goodbye".to_string())];
+        assert_eq!(provider.chat(other, vec![]).await.unwrap().text(), "second");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn streamed_response_is_cached_by_the_time_it_ends() {
+        let (provider, dir) = provider(json!({ "temperature": 0.0 }));
+
+        let stream = provider.stream_chat(messages(), vec![]).await.unwrap();
+        let chunks: Vec<ChatChunk> = stream.map(|chunk| chunk.unwrap()).collect().await;
+        assert!(matches!(chunks.last(), Some(ChatChunk::MessageStop)));
+
+        // No waiting: the entry was written before MessageStop was yielded
+        assert_eq!(
+            provider.chat(messages(), vec![]).await.unwrap().text(),
+            "first"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn streamed_events_are_cached_and_replayed() {
+        let (provider, dir) = provider(json!({ "temperature": 0.0 }));
+
+        for _ in 0..2 {
+            let events = provider
+                .stream_chat_events(messages(), vec![])
+                .await
+                .unwrap();
+            let mut accumulator = StreamAccumulator::new("mock".to_string());
+            let events: Vec<StreamEvent> = events.map(|event| event.unwrap()).collect().await;
+            for event in events {
+                if let StreamEvent::Chunk(chunk) = event {
+                    accumulator.push(chunk).unwrap();
+                }
+            }
+            assert_eq!(accumulator.finish().unwrap().text(), "first");
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn params_are_part_of_the_key() {
+        let (provider, dir) = provider(json!({ "ollama_options": { "num_ctx": 4096 } }));
+        assert_eq!(
+            provider.chat(messages(), vec![]).await.unwrap().text(),
+            "first"
+        );
+
+        let other = CachingProvider::new(
+            Box::new(MockProvider::with_responses(["other"])),
+            ResponseCache::new(dir.clone()),
+            json!({ "ollama_options": { "num_ctx": 8192 } }),
+        );
+        assert_eq!(
+            other.chat(messages(), vec![]).await.unwrap().text(),
+            "other"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn message_metadata_is_not_part_of_the_key() {
+        let (provider, dir) = provider(json!({ "temperature": 0.0 }));
+        assert_eq!(provider.chat(messages(), vec![]).await.unwrap().text(), "first");
+
+        let tagged = vec![Message::new_user("hello".to_string()).with_metadata("source", "test")];
+        assert_eq!(provider.chat(tagged, vec![]).await.unwrap().text(), "first");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tool_order_is_not_part_of_the_key() {
+        let tool = |name: &str| Tool {
+            name: name.to_string(),
+            description: String::new(),
+            input_schema: json!({ "type": "object" }),
+        };
+        let (provider, dir) = provider(json!({ "temperature": 0.0 }));
+
+        let tools = vec![tool("a::read"), tool("b::write")];
+        assert_eq!(provider.chat(messages(), tools).await.unwrap().text(), "first");
+        let reversed = vec![tool("b::write"), tool("a::read")];
+        assert_eq!(provider.chat(messages(), reversed).await.unwrap().text(), "first");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+use super::types::*;
+use super::LlmProvider;
+use crate::error::Result;
+use crate::types::{Message, Tool};
+use async_trait::async_trait;
+use futures::Stream;
+use serde_json::Value;
+use tracing::warn;
+
+/// Body keys whose values are replaced by `redact`
+const SECRET_KEYS: &[&str] = &[
+    "api_key",
+    "apikey",
+    "authorization",
+    "x-api-key",
+    "token",
+    "access_token",
+    "secret",
+    "password",
+];
+
+/// Wraps a provider and prints each request body (from
+/// `LlmProvider::request_body`) to stderr before sending it, with secrets
+/// redacted
+pub struct RequestDumpingProvider {
+    inner: Box<dyn LlmProvider>,
+}
+
+impl RequestDumpingProvider {
+    pub fn new(inner: Box<dyn LlmProvider>) -> Self {
+        Self { inner }
+    }
+
+    /// The inner provider's request body, redacted
+    fn redacted_body(
+        &self,
+        messages: &[Message],
+        tools: &[Tool],
+        stream: bool,
+    ) -> Result<Option<Value>> {
+        let mut body = self.inner.request_body(messages.to_vec(), tools.to_vec(), stream)?;
+        if let Some(body) = &mut body {
+            redact(body);
+        }
+        Ok(body)
+    }
+
+    fn dump(&self, messages: &[Message], tools: &[Tool], stream: bool) {
+        match self.redacted_body(messages, tools, stream) {
+            Ok(Some(body)) => {
+                eprintln!(
+                    "--- {} request ---\n{}",
+                    self.inner.name(),
+                    serde_json::to_string_pretty(&body).unwrap_or_default()
+                );
+            }
+            Ok(None) => warn!("{} can't show its request body", self.inner.name()),
+            Err(e) => warn!("Failed to build the request body to dump: {}", e),
+        }
+    }
+}
+
+/// Replace the values of secret-looking keys (see `SECRET_KEYS`) anywhere
+/// in `value`
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SECRET_KEYS.contains(&key.to_lowercase().as_str()) {
+                    *value = Value::String("[redacted]".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[async_trait]
+impl LlmProvider for RequestDumpingProvider {
+    async fn chat(&self, messages: Vec<Message>, tools: Vec<Tool>) -> Result<ChatResponse> {
+        self.dump(&messages, &tools, false);
+        self.inner.chat(messages, tools).await
+    }
+
+    async fn stream_chat(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>> {
+        self.dump(&messages, &tools, true);
+        self.inner.stream_chat(messages, tools).await
+    }
+
+    async fn stream_chat_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Box<dyn Stream<Item = Result<StreamEvent>> + Send + Unpin>> {
+        self.dump(&messages, &tools, true);
+        self.inner.stream_chat_events(messages, tools).await
+    }
+
+    fn request_body(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+        stream: bool,
+    ) -> Result<Option<Value>> {
+        self.inner.request_body(messages, tools, stream)
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.inner.supports_tools()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    fn supports_prefill(&self) -> bool {
+        self.inner.supports_prefill()
+    }
+
+    fn estimate_tokens(&self, text: &str) -> usize {
+        self.inner.estimate_tokens(text)
+    }
+
+    fn max_tokens(&self) -> usize {
+        self.inner.max_tokens()
+    }
+
+    fn model_max_output_tokens(&self) -> Option<usize> {
+        self.inner.model_max_output_tokens()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn dumped_body_has_the_messages_but_no_key() {
+        std::env::set_var("OLLM_TEST_DUMP_KEY", "sk-ant-secret");
+        let mut config: Config = toml::from_str(&Config::example()).unwrap();
+        config.llm.provider = "anthropic".to_string();
+        config.llm.api_key_env = Some("OLLM_TEST_DUMP_KEY".to_string());
+        let provider = RequestDumpingProvider::new(Box::new(
+            crate::llm::anthropic::AnthropicProvider::new(&config).unwrap(),
+        ));
+
+        let messages = vec![Message::new_user("What is in src/?".to_string())];
+        let body = provider.redacted_body(&messages, &[], true).unwrap().unwrap();
+
+        assert_eq!(body["messages"][0]["content"][0]["text"], "What is in src/?");
+        assert_eq!(body["stream"], true);
+        assert!(!body.to_string().contains("sk-ant-secret"));
+
+        let body = provider.redacted_body(&messages, &[], false).unwrap().unwrap();
+        assert_ne!(body["stream"], true);
+    }
+
+    #[test]
+    fn secret_keys_are_redacted_at_any_depth() {
+        let mut body = serde_json::json!({
+            "api_key": "sk-1",
+            "parameters": { "Authorization": "Bearer sk-2", "temperature": 0.2 },
+            "extra": [{ "token": "sk-3" }],
+        });
+        redact(&mut body);
+
+        assert_eq!(body["api_key"], "[redacted]");
+        assert_eq!(body["parameters"]["Authorization"], "[redacted]");
+        assert_eq!(body["parameters"]["temperature"], 0.2);
+        assert_eq!(body["extra"][0]["token"], "[redacted]");
+    }
+}
@@ -0,0 +1,262 @@
+use crate::config::{Config, EmbeddingApi};
+use crate::error::{OllmError, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use tracing::debug;
+
+/// Turns text into embedding vectors
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// Embed each input, returning one vector per input in the same order
+    async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>>;
+
+    /// Size of the vectors produced
+    fn dimensions(&self) -> usize;
+}
+
+/// Embedder backed by Ollama's `/api/embed` or an OpenAI-style
+/// `/v1/embeddings` endpoint, sending up to `batch_size` inputs per request
+pub struct HttpEmbedder {
+    client: Client,
+    api: EmbeddingApi,
+    endpoint: String,
+    model: String,
+    api_key: Option<String>,
+    dimensions: usize,
+    batch_size: usize,
+    timeout: Option<Duration>,
+}
+
+impl HttpEmbedder {
+    pub fn new(config: &Config) -> Result<Self> {
+        let embeddings = &config.embeddings;
+
+        let endpoint = embeddings.endpoint.clone().unwrap_or_else(|| match embeddings.api {
+            EmbeddingApi::Ollama => config.ollama.endpoint.clone(),
+            EmbeddingApi::OpenAi => "https://api.openai.com".to_string(),
+        });
+
+        let api_key = match &embeddings.api_key_env {
+            Some(env_var) => Some(std::env::var(env_var).map_err(|_| {
+                OllmError::Config(format!("Environment variable {} not set", env_var))
+            })?),
+            None => None,
+        };
+
+        // The key goes in `authorization`, so only protect it when set
+        let credential_headers: &[&str] = if api_key.is_some() {
+            &["authorization"]
+        } else {
+            &[]
+        };
+
+        Ok(Self {
+            client: super::http::build_client(config, credential_headers)?,
+            api: embeddings.api,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            model: embeddings.model.clone(),
+            api_key,
+            dimensions: embeddings.dimensions,
+            batch_size: embeddings.batch_size.max(1),
+            timeout: super::http::request_timeout(config),
+        })
+    }
+
+    async fn embed_batch(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+        let (url, body) = match self.api {
+            EmbeddingApi::Ollama => (
+                format!("{}/api/embed", self.endpoint),
+                json!({ "model": self.model, "input": inputs }),
+            ),
+            EmbeddingApi::OpenAi => (
+                format!("{}/v1/embeddings", self.endpoint),
+                json!({ "model": self.model, "input": inputs }),
+            ),
+        };
+
+        debug!("Embedding {} inputs with {}", inputs.len(), self.model);
+
+        let mut request = self.client.post(&url).json(&body);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = super::http::send(request, self.timeout).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(OllmError::from_api_response(status.as_u16(), &error_text));
+        }
+
+        let vectors = match self.api {
+            EmbeddingApi::Ollama => {
+                let response: OllamaEmbedResponse = response.json().await.map_err(|e| {
+                    OllmError::LlmProvider(format!("Failed to parse embeddings response: {}", e))
+                })?;
+                response.embeddings
+            }
+            EmbeddingApi::OpenAi => {
+                let mut response: OpenAiEmbeddingResponse = response.json().await.map_err(|e| {
+                    OllmError::LlmProvider(format!("Failed to parse embeddings response: {}", e))
+                })?;
+                response.data.sort_by_key(|d| d.index);
+                response.data.into_iter().map(|d| d.embedding).collect()
+            }
+        };
+
+        if vectors.len() != inputs.len() {
+            return Err(OllmError::LlmProvider(format!(
+                "Expected {} embeddings, got {}",
+                inputs.len(),
+                vectors.len()
+            )));
+        }
+
+        Ok(vectors)
+    }
+}
+
+#[async_trait]
+impl Embedder for HttpEmbedder {
+    async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let mut vectors = Vec::with_capacity(inputs.len());
+        for batch in inputs.chunks(self.batch_size) {
+            vectors.extend(self.embed_batch(batch).await?);
+        }
+        Ok(vectors)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+}
+
+/// Element-wise mean of `vectors` (empty when there are none)
+pub fn mean_vector(vectors: &[Vec<f32>]) -> Vec<f32> {
+    let Some(first) = vectors.first() else {
+        return Vec::new();
+    };
+
+    let mut mean = vec![0.0; first.len()];
+    for vector in vectors {
+        for (sum, value) in mean.iter_mut().zip(vector) {
+            *sum += value;
+        }
+    }
+
+    let count = vectors.len() as f32;
+    mean.iter_mut().for_each(|v| *v /= count);
+    mean
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaEmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiEmbeddingResponse {
+    data: Vec<OpenAiEmbedding>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedder(server: &mockito::Server, api: EmbeddingApi, batch_size: usize) -> HttpEmbedder {
+        std::env::set_var("OLLM_TEST_EMBEDDINGS_KEY", "test-key");
+        let mut config: Config = toml::from_str(&Config::example()).unwrap();
+        config.embeddings.api = api;
+        config.embeddings.endpoint = Some(server.url());
+        config.embeddings.model = "embed".to_string();
+        config.embeddings.batch_size = batch_size;
+        config.embeddings.api_key_env =
+            (api == EmbeddingApi::OpenAi).then(|| "OLLM_TEST_EMBEDDINGS_KEY".to_string());
+        HttpEmbedder::new(&config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn ollama_embeddings_come_back_in_batches() {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("POST", "/api/embed")
+            .match_body(mockito::Matcher::Json(json!({ "model": "embed", "input": ["a", "b"] })))
+            .with_body(json!({ "embeddings": [[1.0, 0.0], [0.0, 1.0]] }).to_string())
+            .create_async()
+            .await;
+        let second = server
+            .mock("POST", "/api/embed")
+            .match_body(mockito::Matcher::Json(json!({ "model": "embed", "input": ["c"] })))
+            .with_body(json!({ "embeddings": [[0.5, 0.5]] }).to_string())
+            .create_async()
+            .await;
+
+        let vectors = embedder(&server, EmbeddingApi::Ollama, 2)
+            .embed(vec!["a".to_string(), "b".to_string(), "c".to_string()])
+            .await
+            .unwrap();
+
+        assert_eq!(vectors, [vec![1.0, 0.0], vec![0.0, 1.0], vec![0.5, 0.5]]);
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn openai_embeddings_are_ordered_by_index() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/embeddings")
+            .match_header("authorization", "Bearer test-key")
+            .match_body(mockito::Matcher::Json(json!({ "model": "embed", "input": ["a", "b"] })))
+            .with_body(
+                json!({
+                    "object": "list",
+                    "data": [
+                        { "object": "embedding", "index": 1, "embedding": [0.0, 1.0] },
+                        { "object": "embedding", "index": 0, "embedding": [1.0, 0.0] },
+                    ],
+                    "model": "embed",
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let vectors = embedder(&server, EmbeddingApi::OpenAi, 16)
+            .embed(vec!["a".to_string(), "b".to_string()])
+            .await
+            .unwrap();
+
+        assert_eq!(vectors, [vec![1.0, 0.0], vec![0.0, 1.0]]);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn missing_embeddings_are_an_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/embed")
+            .with_body(json!({ "embeddings": [[1.0, 0.0]] }).to_string())
+            .create_async()
+            .await;
+
+        let error = embedder(&server, EmbeddingApi::Ollama, 16)
+            .embed(vec!["a".to_string(), "b".to_string()])
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Expected 2 embeddings, got 1"), "{}", error);
+    }
+}
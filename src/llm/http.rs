@@ -0,0 +1,215 @@
+use crate::config::Config;
+use crate::error::{OllmError, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
+use futures::{Stream, StreamExt};
+use reqwest::{Client, RequestBuilder, Response};
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+/// Build the HTTP client shared by all providers
+///
+/// Applies the `[http]` pool settings and headers and `llm.timeout_secs` as
+/// the connect timeout, so cross-cutting client options live in one place.
+/// The rest of the timeout is applied per wait by `send`, `read_body` and
+/// `idle_timeout`; a whole-request timeout would cut off long streams.
+///
+/// `credential_headers` are the lowercase names of the headers the caller
+/// sets itself, which `[http] headers` must not replace.
+pub fn build_client(config: &Config, credential_headers: &[&str]) -> Result<Client> {
+    let http = &config.http;
+    let mut builder =
+        Client::builder().default_headers(default_headers(config, credential_headers)?);
+
+    if let Some(timeout) = request_timeout(config) {
+        builder = builder.connect_timeout(timeout);
+    }
+
+    if let Some(max_idle) = http.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+
+    if let Some(secs) = http.pool_idle_timeout_secs {
+        builder = builder.pool_idle_timeout(Duration::from_secs(secs));
+    }
+
+    if let Some(secs) = http.tcp_keepalive_secs {
+        builder = builder.tcp_keepalive(Duration::from_secs(secs));
+    }
+
+    builder
+        .build()
+        .map_err(|e| OllmError::Config(format!("Failed to build HTTP client: {}", e)))
+}
+
+/// Headers for every outgoing request: `User-Agent: open-llm-code/<version>`
+/// and the `[http] headers`, skipping any in `credential_headers`
+///
+/// A `user-agent` in `[http] headers` replaces the default one.
+pub fn default_headers(config: &Config, credential_headers: &[&str]) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        USER_AGENT,
+        HeaderValue::from_static(concat!("open-llm-code/", env!("CARGO_PKG_VERSION"))),
+    );
+
+    for (name, value) in &config.http.headers {
+        if credential_headers.contains(&name.to_lowercase().as_str()) {
+            warn!(
+                "Ignoring http.headers.{}: the credential header is set from the provider's key",
+                name
+            );
+            continue;
+        }
+
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| OllmError::Config(format!("Invalid header name '{}': {}", name, e)))?;
+        let value = HeaderValue::from_str(value).map_err(|e| {
+            OllmError::Config(format!("Invalid value for header '{}': {}", name, e))
+        })?;
+        headers.insert(name, value);
+    }
+
+    Ok(headers)
+}
+
+/// `llm.timeout_secs` as a duration
+pub fn request_timeout(config: &Config) -> Option<Duration> {
+    config.llm.timeout_secs.map(Duration::from_secs)
+}
+
+/// Send a request, failing if the response headers take longer than
+/// `timeout`
+pub async fn send(request: RequestBuilder, timeout: Option<Duration>) -> Result<Response> {
+    within(timeout, request.send())
+        .await?
+        .map_err(OllmError::Http)
+}
+
+/// Read a response body, failing once it grows past `max_bytes` or when a
+/// chunk takes longer than `timeout` to arrive
+pub async fn read_body(
+    mut response: Response,
+    max_bytes: usize,
+    timeout: Option<Duration>,
+) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    while let Some(chunk) = within(timeout, response.chunk())
+        .await?
+        .map_err(OllmError::Http)?
+    {
+        body.extend_from_slice(&chunk);
+        if body.len() > max_bytes {
+            return Err(response_too_large(max_bytes));
+        }
+    }
+    Ok(body)
+}
+
+/// Error for a response over `llm.max_response_bytes`
+pub fn response_too_large(max_bytes: usize) -> OllmError {
+    OllmError::LlmProvider(format!(
+        "response exceeded max size ({} bytes, see llm.max_response_bytes)",
+        max_bytes
+    ))
+}
+
+/// Fail a byte stream with `OllmError::Timeout` when no chunk arrives within
+/// `timeout`, ending it after the error
+///
+/// Bounds the gap between chunks rather than the whole response, so a slow
+/// but steady stream runs as long as it needs to.
+pub fn idle_timeout<S, B, E>(
+    stream: S,
+    timeout: Option<Duration>,
+) -> impl Stream<Item = Result<B>> + Send
+where
+    S: Stream<Item = std::result::Result<B, E>> + Send + 'static,
+    B: Send,
+    E: Into<OllmError>,
+{
+    futures::stream::unfold(Some(Box::pin(stream)), move |stream| async move {
+        let mut stream = stream?;
+        match within(timeout, stream.next()).await {
+            Ok(Some(item)) => Some((item.map_err(Into::into), Some(stream))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+/// Await `future`, giving up after `timeout` when there is one
+async fn within<F: Future>(timeout: Option<Duration>, future: F) -> Result<F::Output> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future)
+            .await
+            .map_err(|_| OllmError::Timeout(timeout.as_secs())),
+        None => Ok(future.await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_headers(headers: &[(&str, &str)]) -> Config {
+        let mut config: Config = toml::from_str(&Config::example()).unwrap();
+        config.http.headers = headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        config
+    }
+
+    #[test]
+    fn default_headers_only_skip_the_callers_credential_headers() {
+        let config = config_with_headers(&[
+            ("Authorization", "Bearer gateway"),
+            ("x-api-key", "gateway"),
+            ("x-org-id", "org"),
+        ]);
+
+        // Ollama sets no credentials, so a gateway can use either header
+        let ollama = default_headers(&config, &[]).unwrap();
+        assert_eq!(ollama["authorization"], "Bearer gateway");
+        assert_eq!(ollama["x-api-key"], "gateway");
+
+        let anthropic = default_headers(&config, &["x-api-key"]).unwrap();
+        assert_eq!(anthropic["authorization"], "Bearer gateway");
+        assert!(!anthropic.contains_key("x-api-key"));
+        assert_eq!(anthropic["x-org-id"], "org");
+        assert!(anthropic[USER_AGENT]
+            .to_str()
+            .unwrap()
+            .starts_with("open-llm-code/"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_timeout_fails_a_stalled_stream() {
+        let stream = futures::stream::iter(vec![Ok::<_, OllmError>(b"a".to_vec())])
+            .chain(futures::stream::pending());
+        let items: Vec<_> = idle_timeout(stream, Some(Duration::from_secs(5)))
+            .collect()
+            .await;
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].as_ref().unwrap(), &b"a".to_vec());
+        assert!(matches!(items[1], Err(OllmError::Timeout(5))));
+        assert!(items[1].as_ref().unwrap_err().is_retryable());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_timeout_allows_slow_steady_streams() {
+        // Ten chunks 4s apart take 40s overall, well past the 5s timeout
+        let stream = futures::stream::iter(0..10).then(|i| async move {
+            tokio::time::sleep(Duration::from_secs(4)).await;
+            Ok::<_, OllmError>(i.to_string().into_bytes())
+        });
+        let items: Vec<_> = idle_timeout(stream, Some(Duration::from_secs(5)))
+            .collect()
+            .await;
+
+        assert_eq!(items.len(), 10);
+        assert!(items.iter().all(|item| item.is_ok()));
+    }
+}
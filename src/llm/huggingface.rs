@@ -1,20 +1,34 @@
 use super::types::*;
 use super::LlmProvider;
+use crate::config::{HuggingFaceApiStyle, PromptTemplate, ResponseFormat};
 use crate::error::{OllmError, Result};
 use crate::types::{ContentBlock, Message, Role, Tool};
 use async_trait::async_trait;
+use eventsource_stream::Eventsource;
 use futures::{Stream, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
+/// Sampling temperature when `llm.temperature` is unset
+const DEFAULT_TEMPERATURE: f32 = 0.7;
+
 pub struct HuggingFaceProvider {
     client: Client,
     api_key: String,
     endpoint: String,
     model: String,
     max_tokens: usize,
+    api_style: HuggingFaceApiStyle,
+    prompt_template: PromptTemplate,
+    retry_on_empty: bool,
+    response_format: ResponseFormat,
+    max_response_bytes: usize,
+    timeout: Option<Duration>,
+    temperature: Option<f32>,
+    seed: Option<u64>,
 }
 
 impl HuggingFaceProvider {
@@ -32,12 +46,23 @@ impl HuggingFaceProvider {
             ));
         };
 
+        let model = config.effective_model();
+        super::models::check_response_format(&model, &config.llm.response_format)?;
+
         Ok(Self {
-            client: Client::new(),
+            client: super::http::build_client(config, &["authorization"])?,
             api_key,
             endpoint: config.huggingface.endpoint.clone(),
-            model: config.huggingface.model.clone(),
-            max_tokens: config.llm.max_tokens,
+            max_tokens: super::models::clamp_max_tokens(config, &model),
+            model,
+            api_style: config.huggingface.api_style,
+            prompt_template: config.huggingface.prompt_template,
+            retry_on_empty: config.llm.retry_on_empty,
+            response_format: config.llm.response_format.clone(),
+            max_response_bytes: config.llm.max_response_bytes,
+            timeout: super::http::request_timeout(config),
+            temperature: config.llm.temperature,
+            seed: config.llm.seed,
         })
     }
 
@@ -69,43 +94,76 @@ impl HuggingFaceProvider {
             })
             .collect()
     }
-}
-
-#[async_trait]
-impl LlmProvider for HuggingFaceProvider {
-    async fn chat(&self, messages: Vec<Message>, tools: Vec<Tool>) -> Result<ChatResponse> {
-        debug!(
-            "Sending {} messages to HuggingFace ({})",
-            messages.len(),
-            self.model
-        );
-
-        if !tools.is_empty() {
-            warn!("HuggingFace Inference API does not support tool use - tools will be ignored");
-        }
 
-        let hf_messages = self.convert_messages(messages);
-
-        // Use OpenAI-compatible chat completions API
-        let request_body = json!({
+    /// Build the OpenAI-compatible `/chat/completions` request body
+    fn chat_request_body(&self, messages: Vec<Message>, stream: bool) -> serde_json::Value {
+        let mut request_body = json!({
             "model": self.model,
-            "messages": hf_messages,
+            "messages": self.convert_messages(messages),
             "max_tokens": self.max_tokens,
-            "temperature": 0.7,
+            "temperature": self.temperature.unwrap_or(DEFAULT_TEMPERATURE),
             "top_p": 0.95
         });
+        if stream {
+            request_body["stream"] = json!(true);
+        }
+        if let Some(format) = self.chat_response_format() {
+            request_body["response_format"] = format;
+        }
+        if let Some(seed) = self.seed {
+            request_body["seed"] = json!(seed);
+        }
+        request_body
+    }
 
-        let url = format!("{}/chat/completions", self.endpoint);
+    fn tgi_request_body(&self, messages: &[Message]) -> serde_json::Value {
+        let mut body = json!({
+            "inputs": super::templates::render(self.prompt_template, messages),
+            "parameters": {
+                "max_new_tokens": self.max_tokens,
+                "temperature": self.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+                "top_p": 0.95,
+                "return_full_text": false,
+                "details": true
+            }
+        });
 
-        let response = self
+        if let Some(seed) = self.seed {
+            body["parameters"]["seed"] = json!(seed);
+        }
+
+        // TGI constrains output with a JSON grammar instead of response_format
+        if let Some(schema) = self.response_format.schema() {
+            body["parameters"]["grammar"] = json!({ "type": "json", "value": schema });
+        }
+
+        body
+    }
+
+    /// The OpenAI-style `response_format` field, unless plain text
+    fn chat_response_format(&self) -> Option<serde_json::Value> {
+        match &self.response_format {
+            ResponseFormat::Text => None,
+            ResponseFormat::JsonObject => Some(json!({ "type": "json_object" })),
+            ResponseFormat::JsonSchema { name, schema } => Some(json!({
+                "type": "json_schema",
+                "json_schema": { "name": name, "schema": schema, "strict": true }
+            })),
+        }
+    }
+
+    /// POST `request_body` to `path` under the endpoint, turning a
+    /// non-success status into an error
+    async fn post(&self, path: &str, request_body: &serde_json::Value) -> Result<reqwest::Response> {
+        let url = format!("{}/{}", self.endpoint, path);
+
+        let request = self
             .client
             .post(&url)
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| OllmError::LlmProvider(format!("HTTP request failed: {}", e)))?;
+            .json(request_body);
+        let response = super::http::send(request, self.timeout).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -113,13 +171,89 @@ impl LlmProvider for HuggingFaceProvider {
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(OllmError::LlmProvider(format!(
-                "HuggingFace API error {}: {}",
-                status, error_text
-            )));
+            return Err(OllmError::from_api_response(status.as_u16(), &error_text));
         }
 
-        let hf_response: HFChatCompletionResponse = response.json().await.map_err(|e| {
+        Ok(response)
+    }
+
+    /// Non-streaming request against TGI's `/generate`
+    async fn tgi_chat(&self, messages: Vec<Message>) -> Result<ChatResponse> {
+        let response = self
+            .post("generate", &self.tgi_request_body(&messages))
+            .await?;
+
+        let body = super::http::read_body(response, self.max_response_bytes, self.timeout).await?;
+        let tgi_response: TgiResponse = serde_json::from_slice(&body).map_err(|e| {
+            OllmError::LlmProvider(format!("Failed to parse TGI response: {}", e))
+        })?;
+
+        info!("Received response from HuggingFace TGI");
+
+        let details = tgi_response.details;
+        Ok(ChatResponse {
+            content: vec![ContentBlock::Text {
+                text: tgi_response.generated_text,
+            }],
+            model: self.model.clone(),
+            stop_reason: Some(tgi_stop_reason(
+                details.as_ref().map(|d| d.finish_reason.as_str()),
+            )),
+            usage: TokenUsage {
+                input_tokens: 0,
+                output_tokens: details.map(|d| d.generated_tokens).unwrap_or(0),
+            },
+            service_tier: None,
+        })
+    }
+
+    /// Streaming request against TGI's `/generate_stream`
+    async fn tgi_stream_chat(
+        &self,
+        messages: Vec<Message>,
+    ) -> Result<Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>> {
+        let response = self
+            .post("generate_stream", &self.tgi_request_body(&messages))
+            .await?;
+
+        let stream = super::http::idle_timeout(response.bytes_stream(), self.timeout)
+            .eventsource()
+            .flat_map(|event| {
+                let chunks = match event {
+                    Ok(event) => match serde_json::from_str::<TgiStreamChunk>(&event.data) {
+                        Ok(tgi_chunk) => tgi_chunks(tgi_chunk),
+                        Err(e) => vec![Err(OllmError::LlmProvider(format!("Parse error: {}", e)))],
+                    },
+                    Err(e) => vec![Ok(ChatChunk::Error {
+                        error: e.to_string(),
+                    })],
+                };
+                futures::stream::iter(chunks)
+            });
+
+        Ok(super::stream::limit_response_bytes(
+            Box::new(Box::pin(stream)),
+            self.max_response_bytes,
+        ))
+    }
+
+    /// Send a single non-streaming request in the configured API style
+    async fn chat_once(&self, messages: Vec<Message>) -> Result<ChatResponse> {
+        if self.api_style == HuggingFaceApiStyle::Tgi {
+            return self.tgi_chat(messages).await;
+        }
+
+        if self.prompt_template != PromptTemplate::Raw {
+            warn!("prompt_template only applies to the tgi api_style - ignoring");
+        }
+
+        // Use OpenAI-compatible chat completions API
+        let request_body = self.chat_request_body(messages, false);
+
+        let response = self.post("chat/completions", &request_body).await?;
+
+        let body = super::http::read_body(response, self.max_response_bytes, self.timeout).await?;
+        let hf_response: HFChatCompletionResponse = serde_json::from_slice(&body).map_err(|e| {
             OllmError::LlmProvider(format!("Failed to parse HuggingFace response: {}", e))
         })?;
 
@@ -138,16 +272,130 @@ impl LlmProvider for HuggingFaceProvider {
                 text: generated_text,
             }],
             model: hf_response.model,
-            stop_reason: Some(match hf_response.choices[0].finish_reason.as_str() {
-                "stop" => StopReason::EndTurn,
-                "length" => StopReason::MaxTokens,
-                _ => StopReason::EndTurn,
-            }),
+            stop_reason: Some(chat_stop_reason(&hf_response.choices[0].finish_reason)),
             usage: TokenUsage {
                 input_tokens: hf_response.usage.prompt_tokens,
                 output_tokens: hf_response.usage.completion_tokens,
             },
+            service_tier: None,
+        })
+    }
+}
+
+/// Parse a chat-completions SSE byte stream (OpenAI format) into chat
+/// chunks; events may span or share byte chunks
+fn parse_chat_sse<S, B, E>(bytes: S) -> impl Stream<Item = Result<ChatChunk>> + Send
+where
+    S: Stream<Item = std::result::Result<B, E>> + Send,
+    B: AsRef<[u8]>,
+    E: std::fmt::Display,
+{
+    bytes.eventsource().flat_map(|event| {
+        let chunks = match event {
+            Ok(event) if event.data.trim() == "[DONE]" => vec![Ok(ChatChunk::MessageStop)],
+            Ok(event) => match serde_json::from_str::<HFChatCompletionChunk>(&event.data) {
+                Ok(hf_chunk) => chat_chunks(hf_chunk),
+                Err(e) => vec![Err(OllmError::LlmProvider(format!("Parse error: {}", e)))],
+            },
+            Err(e) => vec![Ok(ChatChunk::Error {
+                error: e.to_string(),
+            })],
+        };
+        futures::stream::iter(chunks)
+    })
+}
+
+/// Map a chat-completions stream event to chat chunks - the last content
+/// event (or one after it) carries the `finish_reason`
+fn chat_chunks(hf_chunk: HFChatCompletionChunk) -> Vec<Result<ChatChunk>> {
+    let Some(choice) = hf_chunk.choices.into_iter().next() else {
+        return vec![Ok(ChatChunk::Ping)];
+    };
+
+    let mut chunks = Vec::new();
+    if let Some(content) = choice.delta.content {
+        chunks.push(Ok(ChatChunk::ContentBlockDelta {
+            index: 0,
+            delta: ContentDelta::TextDelta { text: content },
+        }));
+    }
+    if let Some(finish_reason) = choice.finish_reason {
+        chunks.push(Ok(ChatChunk::MessageDelta {
+            delta: MessageDelta {
+                stop_reason: Some(chat_stop_reason(&finish_reason)),
+                usage: None,
+            },
+        }));
+    }
+    if chunks.is_empty() {
+        chunks.push(Ok(ChatChunk::Ping));
+    }
+    chunks
+}
+
+fn chat_stop_reason(finish_reason: &str) -> StopReason {
+    match finish_reason {
+        "length" => StopReason::MaxTokens,
+        "stop_sequence" => StopReason::StopSequence,
+        _ => StopReason::EndTurn,
+    }
+}
+
+/// Map a TGI stream event to chat chunks - the final event carries both the
+/// last token and the generation details
+fn tgi_chunks(tgi_chunk: TgiStreamChunk) -> Vec<Result<ChatChunk>> {
+    let mut chunks = Vec::new();
+
+    if !tgi_chunk.token.special {
+        chunks.push(Ok(ChatChunk::ContentBlockDelta {
+            index: 0,
+            delta: ContentDelta::TextDelta {
+                text: tgi_chunk.token.text,
+            },
+        }));
+    }
+
+    if let Some(details) = tgi_chunk.details {
+        chunks.push(Ok(ChatChunk::MessageDelta {
+            delta: MessageDelta {
+                stop_reason: Some(tgi_stop_reason(Some(&details.finish_reason))),
+                usage: Some(TokenUsage {
+                    input_tokens: 0,
+                    output_tokens: details.generated_tokens,
+                }),
+            },
+        }));
+        chunks.push(Ok(ChatChunk::MessageStop));
+    }
+
+    chunks
+}
+
+fn tgi_stop_reason(finish_reason: Option<&str>) -> StopReason {
+    match finish_reason {
+        Some("length") => StopReason::MaxTokens,
+        Some("stop_sequence") => StopReason::StopSequence,
+        _ => StopReason::EndTurn,
+    }
+}
+
+#[async_trait]
+impl LlmProvider for HuggingFaceProvider {
+    async fn chat(&self, messages: Vec<Message>, tools: Vec<Tool>) -> Result<ChatResponse> {
+        debug!(
+            "Sending {} messages to HuggingFace ({})",
+            messages.len(),
+            self.model
+        );
+
+        if !tools.is_empty() {
+            warn!("HuggingFace Inference API does not support tool use - tools will be ignored (set llm.tool_prompting to describe them in the prompt)");
+        }
+
+        super::retry_on_empty("HuggingFace", self.retry_on_empty, || {
+            self.chat_once(messages.clone())
         })
+        .await
     }
 
     async fn stream_chat(
@@ -162,84 +410,42 @@ impl LlmProvider for HuggingFaceProvider {
         );
 
         if !tools.is_empty() {
-            warn!("HuggingFace Inference API does not support tool use - tools will be ignored");
+            warn!("HuggingFace Inference API does not support tool use - tools will be ignored (set llm.tool_prompting to describe them in the prompt)");
         }
 
-        let hf_messages = self.convert_messages(messages);
-
-        let request_body = json!({
-            "model": self.model,
-            "messages": hf_messages,
-            "max_tokens": self.max_tokens,
-            "temperature": 0.7,
-            "top_p": 0.95,
-            "stream": true
-        });
-
-        let url = format!("{}/chat/completions", self.endpoint);
-
-        let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| OllmError::LlmProvider(format!("HTTP request failed: {}", e)))?;
+        if self.api_style == HuggingFaceApiStyle::Tgi {
+            return self.tgi_stream_chat(messages).await;
+        }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(OllmError::LlmProvider(format!(
-                "HuggingFace API error {}: {}",
-                status, error_text
-            )));
+        if self.prompt_template != PromptTemplate::Raw {
+            warn!("prompt_template only applies to the tgi api_style - ignoring");
         }
 
-        // Parse SSE stream (OpenAI format)
-        let stream = response.bytes_stream().map(|chunk_result| {
-            chunk_result
-                .map_err(|e| OllmError::LlmProvider(format!("Stream error: {}", e)))
-                .and_then(|chunk| {
-                    let text = String::from_utf8_lossy(&chunk);
+        let request_body = self.chat_request_body(messages, true);
 
-                    // HuggingFace sends Server-Sent Events like OpenAI
-                    if text.starts_with("data: ") {
-                        let json_str = text.strip_prefix("data: ").unwrap_or(&text);
+        let response = self.post("chat/completions", &request_body).await?;
 
-                        if json_str.trim() == "[DONE]" {
-                            return Ok(ChatChunk::MessageStop);
-                        }
+        let stream = parse_chat_sse(super::http::idle_timeout(
+            response.bytes_stream(),
+            self.timeout,
+        ));
 
-                        serde_json::from_str::<HFChatCompletionChunk>(json_str)
-                            .map_err(|e| OllmError::LlmProvider(format!("Parse error: {}", e)))
-                            .and_then(|hf_chunk| {
-                                if let Some(choice) = hf_chunk.choices.first() {
-                                    if let Some(content) = &choice.delta.content {
-                                        Ok(ChatChunk::ContentBlockDelta {
-                                            index: 0,
-                                            delta: ContentDelta::TextDelta {
-                                                text: content.clone(),
-                                            },
-                                        })
-                                    } else {
-                                        Ok(ChatChunk::Ping)
-                                    }
-                                } else {
-                                    Ok(ChatChunk::Ping)
-                                }
-                            })
-                    } else {
-                        Ok(ChatChunk::Ping)
-                    }
-                })
-        });
+        Ok(super::stream::limit_response_bytes(
+            Box::new(Box::pin(stream)),
+            self.max_response_bytes,
+        ))
+    }
 
-        Ok(Box::new(Box::pin(stream)))
+    fn request_body(
+        &self,
+        messages: Vec<Message>,
+        _tools: Vec<Tool>,
+        stream: bool,
+    ) -> Result<Option<Value>> {
+        Ok(Some(match self.api_style {
+            HuggingFaceApiStyle::Tgi => self.tgi_request_body(&messages),
+            HuggingFaceApiStyle::Chat => self.chat_request_body(messages, stream),
+        }))
     }
 
     fn supports_tools(&self) -> bool {
@@ -294,9 +500,364 @@ struct HFChatCompletionChunk {
 #[derive(Debug, Deserialize)]
 struct HFChunkChoice {
     delta: HFDelta,
+    #[serde(default)]
+    finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct HFDelta {
     content: Option<String>,
 }
+
+// TGI (text-generation-inference) API types
+
+#[derive(Debug, Deserialize)]
+struct TgiResponse {
+    generated_text: String,
+    #[serde(default)]
+    details: Option<TgiDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TgiDetails {
+    finish_reason: String,
+    #[serde(default)]
+    generated_tokens: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct TgiStreamChunk {
+    token: TgiToken,
+    #[serde(default)]
+    details: Option<TgiDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TgiToken {
+    text: String,
+    #[serde(default)]
+    special: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sse_frame_split_across_chunks_is_reassembled() {
+        let parts = [
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\ndata: {\"choi",
+            "ces\":[{\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\n\ndata: [DONE]\n\n",
+        ];
+        let bytes = futures::stream::iter(
+            parts
+                .iter()
+                .map(|part| Ok::<_, std::io::Error>(part.as_bytes().to_vec()))
+                .collect::<Vec<_>>(),
+        );
+
+        let mut accumulator = super::super::StreamAccumulator::new("m".to_string());
+        let mut chunks = Box::pin(parse_chat_sse(bytes));
+        while let Some(chunk) = chunks.next().await {
+            accumulator.push(chunk.unwrap()).unwrap();
+        }
+        let response = accumulator.finish().unwrap();
+
+        assert_eq!(response.text(), "Hello");
+        assert_eq!(response.stop_reason, Some(StopReason::EndTurn));
+    }
+
+    async fn provider_for(
+        server: &mockito::Server,
+        configure: impl FnOnce(&mut crate::config::Config),
+    ) -> HuggingFaceProvider {
+        std::env::set_var("OLLM_TEST_HF_KEY", "test-key");
+        let mut config: crate::config::Config =
+            toml::from_str(&crate::config::Config::example()).unwrap();
+        config.llm.provider = "huggingface".to_string();
+        config.llm.api_key_env = Some("OLLM_TEST_HF_KEY".to_string());
+        config.llm.max_retries = 0;
+        config.huggingface.endpoint = server.url();
+        configure(&mut config);
+        HuggingFaceProvider::new(&config).unwrap()
+    }
+
+    fn completion(content: &str) -> String {
+        json!({
+            "model": "m",
+            "choices": [{
+                "message": { "role": "assistant", "content": content },
+                "finish_reason": "stop",
+            }],
+            "usage": { "prompt_tokens": 3, "completion_tokens": 0 },
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn empty_content_is_an_error() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .with_body(completion("  \n"))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let error = provider_for(&server, |_| {})
+            .await
+            .chat(vec![Message::new_user("hi".to_string())], vec![])
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.to_string(), "LLM provider error: Empty response from HuggingFace");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn empty_content_is_retried_once_when_configured() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .with_body(completion(""))
+            .expect(2)
+            .create_async()
+            .await;
+
+        let error = provider_for(&server, |config| config.llm.retry_on_empty = true)
+            .await
+            .chat(vec![Message::new_user("hi".to_string())], vec![])
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("Empty response"));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn empty_choices_is_an_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/chat/completions")
+            .with_body(
+                json!({
+                    "model": "m",
+                    "choices": [],
+                    "usage": { "prompt_tokens": 3, "completion_tokens": 0 },
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let error = provider_for(&server, |_| {})
+            .await
+            .chat(vec![Message::new_user("hi".to_string())], vec![])
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("Empty response from HuggingFace"));
+    }
+
+    fn hi() -> Vec<Message> {
+        vec![Message::new_user("hi".to_string())]
+    }
+
+    async fn collect(
+        stream: Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>,
+    ) -> ChatResponse {
+        let mut accumulator = super::super::StreamAccumulator::new("m".to_string());
+        let chunks: Vec<Result<ChatChunk>> = stream.collect().await;
+        for chunk in chunks {
+            accumulator.push(chunk.unwrap()).unwrap();
+        }
+        accumulator.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn chat_style_uses_chat_completions() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "messages": [{ "role": "user", "content": "hi" }],
+            })))
+            .with_body(completion("Hello"))
+            .create_async()
+            .await;
+
+        let provider = provider_for(&server, |_| {}).await;
+        let response = provider.chat(hi(), vec![]).await.unwrap();
+        assert_eq!(response.text(), "Hello");
+        assert_eq!(response.stop_reason, Some(StopReason::EndTurn));
+        assert_eq!(response.usage.input_tokens, 3);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn chat_style_streams_chat_completions() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "messages": [{ "role": "user", "content": "hi" }],
+                "stream": true,
+            })))
+            .with_header("content-type", "text/event-stream")
+            .with_body(concat!(
+                "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"finish_reason\":null}]}\n\n",
+                "data: {\"choices\":[{\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"length\"}]}\n\n",
+                "data: [DONE]\n\n",
+            ))
+            .create_async()
+            .await;
+
+        let provider = provider_for(&server, |_| {}).await;
+        let response = collect(provider.stream_chat(hi(), vec![]).await.unwrap()).await;
+        assert_eq!(response.text(), "Hello");
+        assert_eq!(response.stop_reason, Some(StopReason::MaxTokens));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn tgi_style_uses_generate_with_a_rendered_prompt() {
+        let mut server = mockito::Server::new_async().await;
+        let request = json!({
+            "inputs": "<|im_start|>user\nhi<|im_end|>\n<|im_start|>assistant\n",
+            "parameters": { "return_full_text": false, "details": true },
+        });
+        let generate = server
+            .mock("POST", "/generate")
+            .match_body(mockito::Matcher::PartialJson(request.clone()))
+            .with_body(
+                json!({
+                    "generated_text": "Hello",
+                    "details": { "finish_reason": "length", "generated_tokens": 5, "seed": null },
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let generate_stream = server
+            .mock("POST", "/generate_stream")
+            .match_body(mockito::Matcher::PartialJson(request))
+            .with_header("content-type", "text/event-stream")
+            .with_body(concat!(
+                "data: {\"index\":1,\"token\":{\"id\":9707,\"text\":\"Hel\",\"logprob\":-0.2,\"special\":false},\"generated_text\":null,\"details\":null}\n\n",
+                "data: {\"index\":2,\"token\":{\"id\":385,\"text\":\"lo\",\"logprob\":-0.1,\"special\":false},\"generated_text\":null,\"details\":null}\n\n",
+                "data: {\"index\":3,\"token\":{\"id\":151645,\"text\":\"<|im_end|>\",\"logprob\":0.0,\"special\":true},\"generated_text\":\"Hello\",\"details\":{\"finish_reason\":\"eos_token\",\"generated_tokens\":3,\"seed\":null}}\n\n",
+            ))
+            .create_async()
+            .await;
+
+        let provider = provider_for(&server, |config| {
+            config.huggingface.api_style = HuggingFaceApiStyle::Tgi;
+            config.huggingface.prompt_template = PromptTemplate::ChatMl;
+        })
+        .await;
+
+        let response = provider.chat(hi(), vec![]).await.unwrap();
+        assert_eq!(response.text(), "Hello");
+        assert_eq!(response.stop_reason, Some(StopReason::MaxTokens));
+        assert_eq!(response.usage.output_tokens, 5);
+
+        // The special end token isn't part of the text
+        let response = collect(provider.stream_chat(hi(), vec![]).await.unwrap()).await;
+        assert_eq!(response.text(), "Hello");
+        assert_eq!(response.stop_reason, Some(StopReason::EndTurn));
+        assert_eq!(response.usage.output_tokens, 3);
+
+        generate.assert_async().await;
+        generate_stream.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn length_stop_reason_reaches_the_renderer() {
+        use crate::ui::renderer::tests::streamed_stop_reasons;
+
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/chat/completions")
+            .with_header("content-type", "text/event-stream")
+            .with_body(concat!(
+                "data: {\"choices\":[{\"delta\":{\"content\":\"Once upon\"},\"finish_reason\":\"length\"}]}\n\n",
+                "data: [DONE]\n\n",
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/generate_stream")
+            .with_header("content-type", "text/event-stream")
+            .with_body(
+                "data: {\"token\":{\"id\":1,\"text\":\"Once upon\",\"special\":false},\"details\":{\"finish_reason\":\"length\",\"generated_tokens\":2}}\n\n",
+            )
+            .create_async()
+            .await;
+
+        let chat = provider_for(&server, |_| {}).await;
+        let tgi = provider_for(&server, |config| {
+            config.huggingface.api_style = HuggingFaceApiStyle::Tgi;
+        })
+        .await;
+        for provider in [chat, tgi] {
+            let (stop_reason, stops) = streamed_stop_reasons(&provider).await;
+            assert_eq!(stop_reason, Some(StopReason::MaxTokens));
+            assert_eq!(stops, [StopReason::MaxTokens]);
+        }
+    }
+
+    #[tokio::test]
+    async fn response_format_is_sent_for_each_api_style() {
+        let server = mockito::Server::new_async().await;
+        let schema = json!({ "type": "object", "properties": { "answer": { "type": "string" } } });
+        let body = |api_style, response_format| {
+            let server = &server;
+            async move {
+                provider_for(server, |config| {
+                    config.huggingface.api_style = api_style;
+                    config.llm.response_format = response_format;
+                })
+                .await
+                .request_body(hi(), vec![], false)
+                .unwrap()
+                .unwrap()
+            }
+        };
+
+        let chat = body(HuggingFaceApiStyle::Chat, ResponseFormat::JsonObject).await;
+        assert_eq!(chat["response_format"], json!({ "type": "json_object" }));
+        let chat = body(
+            HuggingFaceApiStyle::Chat,
+            ResponseFormat::JsonSchema {
+                name: "answer".to_string(),
+                schema: schema.clone(),
+            },
+        )
+        .await;
+        assert_eq!(
+            chat["response_format"],
+            json!({
+                "type": "json_schema",
+                "json_schema": { "name": "answer", "schema": schema, "strict": true },
+            })
+        );
+        let chat = body(HuggingFaceApiStyle::Chat, ResponseFormat::Text).await;
+        assert!(chat.get("response_format").is_none());
+
+        // TGI takes a grammar instead
+        let tgi = body(
+            HuggingFaceApiStyle::Tgi,
+            ResponseFormat::JsonSchema {
+                name: "answer".to_string(),
+                schema: schema.clone(),
+            },
+        )
+        .await;
+        assert_eq!(
+            tgi["parameters"]["grammar"],
+            json!({ "type": "json", "value": schema })
+        );
+        assert!(tgi.get("response_format").is_none());
+    }
+}
+
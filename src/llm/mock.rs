@@ -0,0 +1,121 @@
+use super::types::*;
+use super::LlmProvider;
+use crate::error::Result;
+use crate::types::{ContentBlock, Message, Role, Tool};
+use async_trait::async_trait;
+use futures::Stream;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Offline provider for examples and tests
+///
+/// Replies with the queued canned responses in order, then echoes the last
+/// user message once the queue is empty. Token counts use the chars/4
+/// heuristic.
+pub struct MockProvider {
+    model: String,
+    responses: Mutex<VecDeque<String>>,
+}
+
+impl MockProvider {
+    /// A provider that echoes the last user message
+    pub fn echo() -> Self {
+        Self::with_responses(Vec::<String>::new())
+    }
+
+    /// A provider that returns `responses` in order before echoing
+    pub fn with_responses<I, S>(responses: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            model: "mock".to_string(),
+            responses: Mutex::new(responses.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    fn reply(&self, messages: &[Message]) -> (String, TokenUsage) {
+        let text = self.responses.lock().unwrap().pop_front().unwrap_or_else(|| {
+            messages
+                .iter()
+                .rev()
+                .find(|m| m.role == Role::User)
+                .map(super::templates::message_text)
+                .unwrap_or_default()
+        });
+
+        let input_tokens = messages
+            .iter()
+            .map(|m| super::tokens::heuristic_tokens(&super::templates::message_text(m)))
+            .sum();
+        let usage = TokenUsage {
+            input_tokens,
+            output_tokens: super::tokens::heuristic_tokens(&text),
+        };
+
+        (text, usage)
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MockProvider {
+    async fn chat(&self, messages: Vec<Message>, _tools: Vec<Tool>) -> Result<ChatResponse> {
+        let (text, usage) = self.reply(&messages);
+
+        Ok(ChatResponse {
+            content: vec![ContentBlock::Text { text }],
+            model: self.model.clone(),
+            stop_reason: Some(StopReason::EndTurn),
+            usage,
+            service_tier: None,
+        })
+    }
+
+    async fn stream_chat(
+        &self,
+        messages: Vec<Message>,
+        _tools: Vec<Tool>,
+    ) -> Result<Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>> {
+        let (text, usage) = self.reply(&messages);
+
+        let chunks = vec![
+            Ok(ChatChunk::ContentBlockStart {
+                index: 0,
+                content_block: ContentBlock::Text {
+                    text: String::new(),
+                },
+            }),
+            Ok(ChatChunk::ContentBlockDelta {
+                index: 0,
+                delta: ContentDelta::TextDelta { text },
+            }),
+            Ok(ChatChunk::ContentBlockStop { index: 0 }),
+            Ok(ChatChunk::MessageDelta {
+                delta: MessageDelta {
+                    stop_reason: Some(StopReason::EndTurn),
+                    usage: Some(usage),
+                },
+            }),
+            Ok(ChatChunk::MessageStop),
+        ];
+
+        Ok(Box::new(futures::stream::iter(chunks)))
+    }
+
+    fn supports_tools(&self) -> bool {
+        false
+    }
+
+    fn max_tokens(&self) -> usize {
+        4096
+    }
+
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
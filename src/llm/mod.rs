@@ -1,12 +1,34 @@
 pub mod anthropic;
+pub mod cache;
+pub mod dump;
+pub mod embeddings;
+pub mod http;
 pub mod huggingface;
+pub mod mock;
+pub mod models;
 pub mod ollama;
+pub mod registry;
+pub mod retry;
+pub mod router;
+pub mod stream;
+pub mod templates;
+pub mod tokens;
+pub mod tool_prompting;
+pub mod tools;
 pub mod types;
 
 use crate::error::Result;
-use crate::types::{Message, Tool};
+use crate::types::{Message, Session, Tool};
 use async_trait::async_trait;
-use futures::Stream;
+use futures::{Stream, StreamExt};
+pub use cache::{CachingProvider, ResponseCache};
+pub use dump::RequestDumpingProvider;
+pub use registry::{ProviderFactory, ProviderRegistry, BUILTIN_PROVIDERS};
+pub use retry::RetryingProvider;
+pub use router::RouterProvider;
+pub use stream::StreamAccumulator;
+pub use tool_prompting::ToolPromptingProvider;
+pub use tools::ToolFormat;
 pub use types::*;
 
 /// LLM Provider trait - abstraction over different LLM backends
@@ -16,18 +38,81 @@ pub trait LlmProvider: Send + Sync {
     async fn chat(&self, messages: Vec<Message>, tools: Vec<Tool>) -> Result<ChatResponse>;
 
     /// Send messages and stream the response
+    ///
+    /// May return an error for models that cannot stream; callers should
+    /// check `supports_streaming()` first (or use `chat_collect`).
     async fn stream_chat(
         &self,
         messages: Vec<Message>,
         tools: Vec<Tool>,
     ) -> Result<Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>>;
 
+    /// Stream the response as `StreamEvent`s, interleaving running usage
+    /// updates with the chunks (e.g. for a live token counter)
+    async fn stream_chat_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Box<dyn Stream<Item = Result<StreamEvent>> + Send + Unpin>> {
+        let stream = self.stream_chat(messages, tools).await?;
+        Ok(stream::with_usage_updates(stream, false))
+    }
+
+    /// Stream the response through a bounded buffer of `capacity` chunks,
+    /// so reading from the provider pauses while the consumer lags behind
+    async fn buffered_stream_chat(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+        capacity: usize,
+    ) -> Result<Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>> {
+        let stream = self.stream_chat(messages, tools).await?;
+        Ok(stream::buffered(stream, capacity))
+    }
+
+    /// The JSON body `chat` (or with `stream`, `stream_chat`) would send
+    /// for these messages (for `--dump-request`), or `None` if the provider
+    /// can't show it
+    fn request_body(
+        &self,
+        _messages: Vec<Message>,
+        _tools: Vec<Tool>,
+        _stream: bool,
+    ) -> Result<Option<serde_json::Value>> {
+        Ok(None)
+    }
+
     /// Check if this provider supports tool use
     fn supports_tools(&self) -> bool;
 
+    /// Check if this provider supports real streaming via `stream_chat`
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    /// Check if this provider continues a trailing assistant message (prefill)
+    fn supports_prefill(&self) -> bool {
+        false
+    }
+
+    /// Estimate the token count of `text` for this provider's model
+    fn estimate_tokens(&self, text: &str) -> usize {
+        tokens::count_tokens(self.model(), text)
+    }
+
     /// Get maximum tokens supported
     fn max_tokens(&self) -> usize;
 
+    /// Most tokens the model can produce in one response, if known
+    fn model_max_output_tokens(&self) -> Option<usize> {
+        models::max_output_tokens(self.model())
+    }
+
+    /// Everything the provider supports, in one place
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities::of(self)
+    }
+
     /// Get provider name
     fn name(&self) -> &str;
 
@@ -35,26 +120,144 @@ pub trait LlmProvider: Send + Sync {
     fn model(&self) -> &str;
 }
 
-/// Create a provider based on configuration
+/// Get a complete response, streaming when the provider supports it and
+/// falling back to `chat` otherwise
+pub async fn chat_collect(
+    provider: &dyn LlmProvider,
+    messages: Vec<Message>,
+    tools: Vec<Tool>,
+) -> Result<ChatResponse> {
+    if !provider.supports_streaming() {
+        return provider.chat(messages, tools).await;
+    }
+
+    let mut stream = provider.stream_chat(messages, tools).await?;
+    let mut accumulator = StreamAccumulator::new(provider.model().to_string());
+
+    while let Some(chunk) = stream.next().await {
+        accumulator.push(chunk?)?;
+    }
+
+    accumulator.finish()
+}
+
+/// Send a session's history and return the response, leaving the session
+/// unchanged
+pub async fn chat_session(
+    provider: &dyn LlmProvider,
+    session: &Session,
+    tools: Vec<Tool>,
+) -> Result<ChatResponse> {
+    provider.chat(session.messages.clone(), tools).await
+}
+
+/// Send a session's history, then append the assistant's reply to the
+/// session and add its usage to the session total
+pub async fn continue_session(
+    provider: &dyn LlmProvider,
+    session: &mut Session,
+    tools: Vec<Tool>,
+) -> Result<ChatResponse> {
+    let response = chat_session(provider, session, tools).await?;
+    session.add_message(Message::new_assistant(response.content.clone()));
+    session.record_usage(&response.usage);
+    Ok(response)
+}
+
+/// Append an assistant turn for the model to continue from (prefill), e.g.
+/// `{` to force JSON output
+///
+/// Providers without `supports_prefill` log a warning. Trailing whitespace
+/// is trimmed because Anthropic rejects it.
+pub fn push_prefill(provider: &dyn LlmProvider, messages: &mut Vec<Message>, prefill: &str) {
+    if !provider.supports_prefill() {
+        tracing::warn!(
+            "Prefill is not supported by {}; the model may not continue it",
+            provider.name()
+        );
+    }
+
+    messages.push(Message::new_assistant(vec![crate::types::ContentBlock::Text {
+        text: prefill.trim_end().to_string(),
+    }]));
+}
+
+/// Run a non-streaming request, treating a blank response as an error and
+/// retrying it once first when `retry` is set
+pub(crate) async fn retry_on_empty<F, Fut>(
+    provider: &str,
+    retry: bool,
+    request: F,
+) -> Result<ChatResponse>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = Result<ChatResponse>>,
+{
+    let response = request().await?;
+    if !response.is_blank() {
+        return Ok(response);
+    }
+
+    if retry {
+        tracing::warn!("Empty response from {}, retrying once", provider);
+        let response = request().await?;
+        if !response.is_blank() {
+            return Ok(response);
+        }
+    }
+
+    Err(crate::error::OllmError::LlmProvider(format!(
+        "Empty response from {}",
+        provider
+    )))
+}
+
+/// Create a built-in provider based on configuration
+///
+/// Use a `ProviderRegistry` (or `OllmClient::builder`) to add custom providers.
 pub async fn create_provider(
     config: &crate::config::Config,
 ) -> Result<Box<dyn LlmProvider>> {
-    match config.llm.provider.as_str() {
-        "anthropic" => {
-            let provider = anthropic::AnthropicProvider::new(config)?;
-            Ok(Box::new(provider))
-        }
-        "ollama" => {
-            let provider = ollama::OllamaProvider::new(config)?;
-            Ok(Box::new(provider))
-        }
-        "huggingface" => {
-            let provider = huggingface::HuggingFaceProvider::new(config)?;
-            Ok(Box::new(provider))
-        }
-        _ => Err(crate::error::OllmError::Config(format!(
-            "Unknown LLM provider: {}",
-            config.llm.provider
-        ))),
+    ProviderRegistry::new().create(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Role;
+
+    #[tokio::test]
+    async fn continued_session_keeps_every_turn() {
+        let provider = mock::MockProvider::with_responses(["Hello!", "Goodbye!"]);
+        let mut session = Session::new("mock".to_string(), "mock".to_string());
+
+        session.add_message(Message::new_user("Hi".to_string()));
+        let first = continue_session(&provider, &mut session, vec![]).await.unwrap();
+        session.add_message(Message::new_user("Bye".to_string()));
+        let second = continue_session(&provider, &mut session, vec![]).await.unwrap();
+
+        let turns: Vec<(Role, String)> = session
+            .messages
+            .iter()
+            .map(|m| (m.role.clone(), templates::message_text(m)))
+            .collect();
+        assert_eq!(
+            turns,
+            [
+                (Role::User, "Hi".to_string()),
+                (Role::Assistant, "Hello!".to_string()),
+                (Role::User, "Bye".to_string()),
+                (Role::Assistant, "Goodbye!".to_string()),
+            ]
+        );
+        assert_eq!(
+            session.total_usage.total(),
+            first.usage.total() + second.usage.total()
+        );
+
+        // chat_session only reads the session
+        let reply = chat_session(&provider, &session, vec![]).await.unwrap();
+        assert_eq!(reply.text(), "Bye");
+        assert_eq!(session.messages.len(), 4);
     }
 }
@@ -0,0 +1,135 @@
+use crate::config::{Config, ResponseFormat};
+use crate::error::{OllmError, Result};
+use tracing::warn;
+
+/// Maximum output tokens for known models, matched by model-name prefix
+///
+/// More specific prefixes come first. Models not listed are not clamped.
+const MAX_OUTPUT_TOKENS: &[(&str, usize)] = &[
+    ("claude-opus-4", 32_000),
+    ("claude-sonnet-4", 64_000),
+    ("claude-3-7-sonnet", 64_000),
+    ("claude-3-5-sonnet", 8_192),
+    ("claude-3-5-haiku", 8_192),
+    ("claude-3-opus", 4_096),
+    ("claude-3-sonnet", 4_096),
+    ("claude-3-haiku", 4_096),
+    ("gpt-4o-mini", 16_384),
+    ("gpt-4o", 16_384),
+    ("gpt-4-turbo", 4_096),
+];
+
+/// Context window (input plus output tokens) for known models, matched by
+/// model-name prefix
+const CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("claude-", 200_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4-1106", 128_000),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+];
+
+/// Models without JSON mode (`response_format = json_object`), by prefix
+const NO_JSON_OBJECT: &[&str] = &[
+    "gpt-4-0314",
+    "gpt-4-0613",
+    "gpt-3.5-turbo-0301",
+    "gpt-3.5-turbo-0613",
+];
+
+/// Models without structured outputs (`response_format = json_schema`),
+/// by prefix, in addition to those without JSON mode
+const NO_JSON_SCHEMA: &[&str] = &["gpt-3.5-turbo", "gpt-4-turbo", "gpt-4-0", "gpt-4-1106"];
+
+/// Maximum output tokens for `model`, if known
+pub fn max_output_tokens(model: &str) -> Option<usize> {
+    MAX_OUTPUT_TOKENS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, limit)| *limit)
+}
+
+/// Context window for `model`, if known
+pub fn context_window(model: &str) -> Option<usize> {
+    CONTEXT_WINDOWS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, limit)| *limit)
+}
+
+/// `llm.max_tokens` limited to what `model` can produce, warning when it's
+/// reduced (unless `llm.clamp_max_tokens` is off)
+pub fn clamp_max_tokens(config: &Config, model: &str) -> usize {
+    let configured = config.llm.max_tokens;
+    if !config.llm.clamp_max_tokens {
+        return configured;
+    }
+
+    match max_output_tokens(model) {
+        Some(limit) if configured > limit => {
+            warn!(
+                "max_tokens {} exceeds the {} output limit of {}, using {}",
+                configured, model, limit, limit
+            );
+            limit
+        }
+        _ => configured,
+    }
+}
+
+/// Reject a `response_format` that `model` is known not to support
+pub fn check_response_format(model: &str, format: &ResponseFormat) -> Result<()> {
+    let unsupported: &[&str] = match format {
+        ResponseFormat::Text => return Ok(()),
+        ResponseFormat::JsonObject => NO_JSON_OBJECT,
+        ResponseFormat::JsonSchema { .. } => NO_JSON_SCHEMA,
+    };
+
+    let known_unsupported = model == "gpt-4"
+        || NO_JSON_OBJECT
+            .iter()
+            .chain(unsupported)
+            .any(|prefix| model.starts_with(prefix));
+
+    if known_unsupported {
+        return Err(OllmError::Config(format!(
+            "Model {} does not support response_format {}",
+            model,
+            format.kind()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_tokens: usize) -> Config {
+        let mut config: Config = toml::from_str(&Config::example()).unwrap();
+        config.llm.max_tokens = max_tokens;
+        config
+    }
+
+    #[test]
+    fn max_tokens_below_the_limit_is_kept() {
+        assert_eq!(clamp_max_tokens(&config(4_096), "claude-3-5-haiku-latest"), 4_096);
+        assert_eq!(clamp_max_tokens(&config(8_192), "claude-3-5-haiku-latest"), 8_192);
+    }
+
+    #[test]
+    fn max_tokens_above_the_limit_is_clamped() {
+        assert_eq!(clamp_max_tokens(&config(100_000), "claude-3-5-haiku-latest"), 8_192);
+        assert_eq!(clamp_max_tokens(&config(100_000), "claude-opus-4-1"), 32_000);
+    }
+
+    #[test]
+    fn unknown_models_and_disabled_clamping_keep_max_tokens() {
+        assert_eq!(clamp_max_tokens(&config(100_000), "llama3.2"), 100_000);
+
+        let mut config = config(100_000);
+        config.llm.clamp_max_tokens = false;
+        assert_eq!(clamp_max_tokens(&config, "claude-3-5-haiku-latest"), 100_000);
+    }
+}
@@ -1,95 +1,228 @@
+use super::tools::{from_provider_tool_calls, to_provider_tool_calls, to_provider_tools, ToolFormat};
 use super::types::*;
 use super::LlmProvider;
+use crate::config::{OllamaApi, PromptTemplate, ResponseFormat};
 use crate::error::{OllmError, Result};
-use crate::types::{ContentBlock, Message, Role, Tool};
+use crate::retry::BackoffPolicy;
+use crate::types::{ContentBlock, Message, Role, Tool, ToolResultContent};
 use async_trait::async_trait;
 use futures::{Stream, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
+/// Maximum reconnects for one streamed response with `resume_streaming`
+const MAX_STREAM_RESUMES: u32 = 3;
+
+type ChunkStream = Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>;
+
+#[derive(Clone)]
 pub struct OllamaProvider {
     client: Client,
     endpoint: String,
     model: String,
     max_tokens: usize,
+    prompt_template: PromptTemplate,
+    /// Send a flattened prompt to `/api/generate` instead of `/api/chat`
+    generate: bool,
+    retry_on_empty: bool,
+    resume_streaming: bool,
+    /// Reconnect attempts and delays for `resume_streaming`
+    resume_policy: BackoffPolicy,
+    response_format: ResponseFormat,
+    max_response_bytes: usize,
+    timeout: Option<Duration>,
+    temperature: Option<f32>,
+    seed: Option<u64>,
+    native_tools: bool,
+    max_tool_description_chars: Option<usize>,
+    options: serde_json::Map<String, serde_json::Value>,
+    keep_alive: Option<serde_json::Value>,
 }
 
 impl OllamaProvider {
     pub fn new(config: &crate::config::Config) -> Result<Self> {
+        let model = config.effective_model();
+        super::models::check_response_format(&model, &config.llm.response_format)?;
+
+        let generate = config.ollama.api == OllamaApi::Generate
+            || config.ollama.prompt_template != PromptTemplate::Raw;
+        if config.ollama.native_tools && generate {
+            warn!("ollama.native_tools needs the \"chat\" API and \"raw\" prompt template - tools will be ignored");
+        }
+
+        let typed = [
+            ("num_predict", true),
+            ("temperature", config.llm.temperature.is_some()),
+            ("seed", config.llm.seed.is_some()),
+        ];
+        for (key, set) in typed {
+            if set && config.ollama.options.contains_key(key) {
+                warn!("ollama.options.{} is overridden by the typed setting", key);
+            }
+        }
+
         Ok(Self {
-            client: Client::new(),
+            client: super::http::build_client(config, &[])?,
             endpoint: config.ollama.endpoint.clone(),
-            model: config.ollama.model.clone(),
-            max_tokens: config.llm.max_tokens,
+            max_tokens: super::models::clamp_max_tokens(config, &model),
+            model,
+            prompt_template: config.ollama.prompt_template,
+            generate,
+            retry_on_empty: config.llm.retry_on_empty,
+            resume_streaming: config.llm.resume_streaming,
+            resume_policy: BackoffPolicy::new(MAX_STREAM_RESUMES),
+            response_format: config.llm.response_format.clone(),
+            max_response_bytes: config.llm.max_response_bytes,
+            timeout: super::http::request_timeout(config),
+            temperature: config.llm.temperature,
+            seed: config.llm.seed,
+            native_tools: config.ollama.native_tools && !generate,
+            max_tool_description_chars: config.llm.max_tool_description_chars,
+            options: config.ollama.options.clone(),
+            // Ollama takes seconds as a number and durations as a string
+            keep_alive: config.ollama.keep_alive.as_deref().map(|value| {
+                let value = value.trim();
+                value.parse::<i64>().map_or_else(|_| json!(value), |secs| json!(secs))
+            }),
         })
     }
 
     fn convert_messages(&self, messages: Vec<Message>) -> Vec<OllamaMessage> {
-        messages
-            .into_iter()
-            .map(|m| {
-                let role = match m.role {
-                    Role::User => "user",
-                    Role::Assistant => "assistant",
-                    Role::System => "system",
-                };
+        let mut converted = Vec::with_capacity(messages.len());
 
-                // Combine all text content blocks
-                let content = m
-                    .content
-                    .iter()
-                    .filter_map(|c| {
-                        if let ContentBlock::Text { text } = c {
-                            Some(text.clone())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>()
-                    .join("\n");
+        for m in messages {
+            let role = match m.role {
+                Role::User => "user",
+                Role::Assistant => "assistant",
+                Role::System => "system",
+            };
 
-                OllamaMessage {
-                    role: role.to_string(),
-                    content,
+            // Tool results go back as "tool" messages, one per result
+            let mut has_results = false;
+            for block in &m.content {
+                if let ContentBlock::ToolResult {
+                    content, is_error, ..
+                } = block
+                {
+                    has_results = true;
+                    // The tool message has no error flag, so mark failures
+                    // in the text
+                    let mut text = tool_result_text(content);
+                    if *is_error == Some(true) {
+                        text = format!("Error: {}", text);
+                    }
+                    converted.push(OllamaMessage {
+                        role: "tool".to_string(),
+                        content: text,
+                        tool_calls: Vec::new(),
+                    });
                 }
-            })
-            .collect()
+            }
+
+            // Combine all text content blocks
+            let content = m
+                .content
+                .iter()
+                .filter_map(|c| {
+                    if let ContentBlock::Text { text } = c {
+                        Some(text.clone())
+                    } else {
+                        None
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let tool_calls = to_provider_tool_calls(ToolFormat::Ollama, &m.content);
+
+            if has_results && content.is_empty() && tool_calls.is_empty() {
+                continue;
+            }
+            converted.push(OllamaMessage {
+                role: role.to_string(),
+                content,
+                tool_calls,
+            });
+        }
+
+        converted
     }
-}
 
-#[async_trait]
-impl LlmProvider for OllamaProvider {
-    async fn chat(&self, messages: Vec<Message>, tools: Vec<Tool>) -> Result<ChatResponse> {
-        debug!(
-            "Sending {} messages to Ollama ({})",
-            messages.len(),
-            self.model
-        );
+    /// Build the endpoint path and request body
+    ///
+    /// With a prompt template or the "generate" API, the prompt is rendered
+    /// locally (role-labeled for the "raw" template) and sent to
+    /// `/api/generate` in raw mode so Ollama doesn't apply its own template.
+    fn build_request(
+        &self,
+        messages: Vec<Message>,
+        tools: &[Tool],
+        stream: bool,
+    ) -> (&'static str, serde_json::Value) {
+        // Free-form options first, so the typed settings take precedence
+        let mut options = serde_json::Value::Object(self.options.clone());
+        options["num_predict"] = json!(self.max_tokens);
+        if let Some(temperature) = self.temperature {
+            options["temperature"] = json!(temperature);
+        }
+        if let Some(seed) = self.seed {
+            options["seed"] = json!(seed);
+        }
 
-        if !tools.is_empty() {
-            warn!("Ollama provider does not support tool use - tools will be ignored");
+        let (path, mut body) = if !self.generate {
+            let ollama_messages = self.convert_messages(messages);
+            (
+                "api/chat",
+                json!({
+                    "model": self.model,
+                    "messages": ollama_messages,
+                    "stream": stream,
+                    "options": options,
+                }),
+            )
+        } else {
+            (
+                "api/generate",
+                json!({
+                    "model": self.model,
+                    "prompt": super::templates::render(self.prompt_template, &messages),
+                    "raw": true,
+                    "stream": stream,
+                    "options": options,
+                }),
+            )
+        };
+
+        if let Some(keep_alive) = &self.keep_alive {
+            body["keep_alive"] = keep_alive.clone();
         }
 
-        let ollama_messages = self.convert_messages(messages);
+        if self.native_tools && !tools.is_empty() {
+            body["tools"] = json!(to_provider_tools(
+                ToolFormat::Ollama,
+                tools,
+                self.max_tool_description_chars
+            ));
+        }
 
-        let request_body = json!({
-            "model": self.model,
-            "messages": ollama_messages,
-            "stream": false,
-            "options": {
-                "num_predict": self.max_tokens,
-            }
-        });
+        // Ollama takes "json" for JSON mode or the schema itself
+        match &self.response_format {
+            ResponseFormat::Text => {}
+            ResponseFormat::JsonObject => body["format"] = json!("json"),
+            ResponseFormat::JsonSchema { schema, .. } => body["format"] = schema.clone(),
+        }
+
+        (path, body)
+    }
 
-        let response = self
+    async fn post(&self, path: &str, request_body: &serde_json::Value) -> Result<reqwest::Response> {
+        let request = self
             .client
-            .post(format!("{}/api/chat", self.endpoint))
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| OllmError::LlmProvider(format!("HTTP request failed: {}", e)))?;
+            .post(format!("{}/{}", self.endpoint, path))
+            .json(request_body);
+        let response = super::http::send(request, self.timeout).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -97,34 +230,140 @@ impl LlmProvider for OllamaProvider {
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(OllmError::LlmProvider(format!(
-                "Ollama API error {}: {}",
-                status, error_text
-            )));
+            return Err(OllmError::from_api_response(status.as_u16(), &error_text));
         }
 
-        let ollama_response: OllamaResponse = response.json().await.map_err(|e| {
+        Ok(response)
+    }
+
+    /// Send a streaming request and parse the NDJSON response into chunks
+    async fn open_stream(&self, path: &str, request_body: &serde_json::Value) -> Result<ChunkStream> {
+        let response = self.post(path, request_body).await?;
+        Ok(Box::new(Box::pin(parse_ndjson(super::http::idle_timeout(
+            response.bytes_stream(),
+            self.timeout,
+        )))))
+    }
+
+    /// Wrap `stream` so a dropped connection re-requests with the text
+    /// received so far as a prefix and continues from there
+    fn resuming(
+        self,
+        path: &'static str,
+        request_body: serde_json::Value,
+        stream: ChunkStream,
+    ) -> ChunkStream {
+        let state = ResumeState {
+            provider: self,
+            path,
+            request_body,
+            stream,
+            text: String::new(),
+            resumes: 0,
+        };
+
+        let stream = futures::stream::unfold(state, |mut state| async move {
+            loop {
+                match state.stream.next().await? {
+                    Ok(ChatChunk::Error { error })
+                        if !state.text.is_empty()
+                            && state.resumes < state.provider.resume_policy.max_retries =>
+                    {
+                        state.resumes += 1;
+                        let delay = state.provider.resume_policy.delay(state.resumes);
+                        warn!(
+                            "Ollama stream dropped after {} chars ({}), resuming in {:?} ({}/{})",
+                            state.text.len(),
+                            error,
+                            delay,
+                            state.resumes,
+                            state.provider.resume_policy.max_retries
+                        );
+                        tokio::time::sleep(delay).await;
+
+                        let body = continuation(state.path, &state.request_body, &state.text);
+                        match state.provider.open_stream(state.path, &body).await {
+                            Ok(stream) => state.stream = stream,
+                            Err(e) => {
+                                state.stream = Box::new(futures::stream::empty());
+                                let chunk = ChatChunk::Error {
+                                    error: format!("{} (resume failed: {})", error, e),
+                                };
+                                return Some((Ok(chunk), state));
+                            }
+                        }
+                    }
+                    Ok(chunk) => {
+                        if let ChatChunk::ContentBlockDelta {
+                            delta: ContentDelta::TextDelta { text },
+                            ..
+                        } = &chunk
+                        {
+                            state.text.push_str(text);
+                        }
+                        return Some((Ok(chunk), state));
+                    }
+                    Err(e) => return Some((Err(e), state)),
+                }
+            }
+        });
+
+        Box::new(Box::pin(stream))
+    }
+
+    /// Send a single non-streaming request
+    async fn chat_once(&self, messages: Vec<Message>, tools: &[Tool]) -> Result<ChatResponse> {
+        let (path, request_body) = self.build_request(messages, tools, false);
+        let response = self.post(path, &request_body).await?;
+
+        let body = super::http::read_body(response, self.max_response_bytes, self.timeout).await?;
+        let ollama_response: OllamaResponse = serde_json::from_slice(&body).map_err(|e| {
             OllmError::LlmProvider(format!("Failed to parse Ollama response: {}", e))
         })?;
 
         info!("Received response from Ollama");
 
+        let tool_calls = ollama_response.tool_calls()?;
+        let text = ollama_response.text();
+
+        let mut content = Vec::with_capacity(tool_calls.len() + 1);
+        if !text.is_empty() || tool_calls.is_empty() {
+            content.push(ContentBlock::Text { text });
+        }
+        let stop_reason = ollama_response.stop_reason(!tool_calls.is_empty());
+        content.extend(tool_calls);
+
         Ok(ChatResponse {
-            content: vec![ContentBlock::Text {
-                text: ollama_response.message.content,
-            }],
+            stop_reason: Some(stop_reason),
+            content,
             model: ollama_response.model,
-            stop_reason: Some(if ollama_response.done {
-                StopReason::EndTurn
-            } else {
-                StopReason::MaxTokens
-            }),
             usage: TokenUsage {
                 input_tokens: ollama_response.prompt_eval_count.unwrap_or(0),
                 output_tokens: ollama_response.eval_count.unwrap_or(0),
             },
+            service_tier: None,
         })
     }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    async fn chat(&self, messages: Vec<Message>, tools: Vec<Tool>) -> Result<ChatResponse> {
+        debug!(
+            "Sending {} messages to Ollama ({})",
+            messages.len(),
+            self.model
+        );
+
+        if !tools.is_empty() && !self.native_tools {
+            warn!("Ollama provider does not support tool use - tools will be ignored (set ollama.native_tools, or llm.tool_prompting to describe them in the prompt)");
+        }
+
+        super::retry_on_empty("Ollama", self.retry_on_empty, || {
+            self.chat_once(messages.clone(), &tools)
+        })
+        .await
+    }
 
     async fn stream_chat(
         &self,
@@ -137,69 +376,42 @@ impl LlmProvider for OllamaProvider {
             self.model
         );
 
-        if !tools.is_empty() {
-            warn!("Ollama provider does not support tool use - tools will be ignored");
+        if !tools.is_empty() && !self.native_tools {
+            warn!("Ollama provider does not support tool use - tools will be ignored (set ollama.native_tools, or llm.tool_prompting to describe them in the prompt)");
         }
 
-        let ollama_messages = self.convert_messages(messages);
-
-        let request_body = json!({
-            "model": self.model,
-            "messages": ollama_messages,
-            "stream": true,
-            "options": {
-                "num_predict": self.max_tokens,
-            }
-        });
+        let (path, request_body) = self.build_request(messages, &tools, true);
+        let stream = self.open_stream(path, &request_body).await?;
 
-        let response = self
-            .client
-            .post(format!("{}/api/chat", self.endpoint))
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| OllmError::LlmProvider(format!("HTTP request failed: {}", e)))?;
+        let stream = if self.resume_streaming {
+            self.clone().resuming(path, request_body, stream)
+        } else {
+            stream
+        };
+        Ok(super::stream::limit_response_bytes(stream, self.max_response_bytes))
+    }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(OllmError::LlmProvider(format!(
-                "Ollama API error {}: {}",
-                status, error_text
-            )));
-        }
-
-        // Parse NDJSON stream
-        let stream = response.bytes_stream().map(|chunk_result| {
-            chunk_result
-                .map_err(|e| OllmError::LlmProvider(format!("Stream error: {}", e)))
-                .and_then(|chunk| {
-                    let text = String::from_utf8_lossy(&chunk);
-                    serde_json::from_str::<OllamaStreamChunk>(&text)
-                        .map_err(|e| OllmError::LlmProvider(format!("Parse error: {}", e)))
-                })
-                .map(|ollama_chunk| {
-                    if ollama_chunk.done {
-                        ChatChunk::MessageStop
-                    } else {
-                        ChatChunk::ContentBlockDelta {
-                            index: 0,
-                            delta: ContentDelta::TextDelta {
-                                text: ollama_chunk.message.content,
-                            },
-                        }
-                    }
-                })
-        });
+    async fn stream_chat_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Box<dyn Stream<Item = Result<StreamEvent>> + Send + Unpin>> {
+        // Ollama only reports usage on the final line, so count lines as tokens
+        let stream = self.stream_chat(messages, tools).await?;
+        Ok(super::stream::with_usage_updates(stream, true))
+    }
 
-        Ok(Box::new(Box::pin(stream)))
+    fn request_body(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+        stream: bool,
+    ) -> Result<Option<serde_json::Value>> {
+        Ok(Some(self.build_request(messages, &tools, stream).1))
     }
 
     fn supports_tools(&self) -> bool {
-        false // Ollama doesn't support structured tool use (yet)
+        self.native_tools
     }
 
     fn max_tokens(&self) -> usize {
@@ -215,28 +427,656 @@ impl LlmProvider for OllamaProvider {
     }
 }
 
+/// State for a stream that reconnects after dropped connections
+struct ResumeState {
+    provider: OllamaProvider,
+    path: &'static str,
+    request_body: serde_json::Value,
+    stream: ChunkStream,
+    /// Text streamed so far, across reconnects
+    text: String,
+    resumes: u32,
+}
+
+/// Request body that continues a response from its partial `text`
+///
+/// `/api/generate` prompts end where the assistant's reply begins, so the
+/// text is appended to the prompt; `/api/chat` continues a trailing
+/// assistant message.
+fn continuation(path: &str, request_body: &serde_json::Value, text: &str) -> serde_json::Value {
+    let mut body = request_body.clone();
+    if path == "api/generate" {
+        let prompt = body["prompt"].as_str().unwrap_or_default().to_string();
+        body["prompt"] = json!(prompt + text);
+    } else if let Some(messages) = body["messages"].as_array_mut() {
+        messages.push(json!({ "role": "assistant", "content": text }));
+    }
+    body
+}
+
+/// Line buffer and tool-call count for parsing an NDJSON stream
+#[derive(Default)]
+struct LineState {
+    buffer: Vec<u8>,
+    tool_calls: usize,
+}
+
+/// Parse an NDJSON byte stream into chat chunks
+///
+/// Objects may span or share byte chunks, so bytes are buffered and only
+/// complete lines parsed; whatever is left when the stream ends (a final
+/// `done` object without a trailing newline) is parsed as the last line.
+fn parse_ndjson<S, B, E>(bytes: S) -> impl Stream<Item = Result<ChatChunk>> + Send
+where
+    S: Stream<Item = std::result::Result<B, E>> + Send,
+    B: AsRef<[u8]>,
+    E: std::fmt::Display,
+{
+    bytes
+        .map(Some)
+        .chain(futures::stream::once(async { None }))
+        .scan(LineState::default(), |state, item| {
+            let chunks = match item {
+                Some(Ok(chunk)) => {
+                    state.buffer.extend_from_slice(chunk.as_ref());
+                    let mut chunks = Vec::new();
+                    while let Some(pos) = state.buffer.iter().position(|&b| b == b'\n') {
+                        let line: Vec<u8> = state.buffer.drain(..=pos).collect();
+                        chunks.extend(parse_stream_line(&line, &mut state.tool_calls));
+                    }
+                    chunks
+                }
+                // Surface transport failures as a chunk so callers keep the
+                // partial output
+                Some(Err(e)) => vec![Ok(ChatChunk::Error {
+                    error: e.to_string(),
+                })],
+                None => {
+                    let rest = std::mem::take(&mut state.buffer);
+                    parse_stream_line(&rest, &mut state.tool_calls)
+                }
+            };
+            futures::future::ready(Some(futures::stream::iter(chunks)))
+        })
+        .flatten()
+}
+
+/// Parse a single NDJSON line from the Ollama stream into chat chunks
+///
+/// Text streams as block 0; each tool call (Ollama sends them whole) becomes
+/// its own block after it, counted in `tool_calls`.
+fn parse_stream_line(line: &[u8], tool_calls: &mut usize) -> Vec<Result<ChatChunk>> {
+    let text = String::from_utf8_lossy(line);
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let ollama_chunk = match serde_json::from_str::<OllamaResponse>(&text) {
+        Ok(chunk) => chunk,
+        Err(e) => return vec![Err(OllmError::LlmProvider(format!("Parse error: {}", e)))],
+    };
+
+    let mut chunks = Vec::new();
+
+    let chunk_text = ollama_chunk.text();
+    if !chunk_text.is_empty() {
+        chunks.push(Ok(ChatChunk::ContentBlockDelta {
+            index: 0,
+            delta: ContentDelta::TextDelta { text: chunk_text },
+        }));
+    }
+
+    match ollama_chunk.tool_calls() {
+        Ok(calls) => {
+            for call in calls {
+                let ContentBlock::ToolUse { id, name, input } = call else {
+                    continue;
+                };
+                *tool_calls += 1;
+                let index = *tool_calls;
+                chunks.push(Ok(ChatChunk::ContentBlockStart {
+                    index,
+                    content_block: ContentBlock::ToolUse {
+                        id,
+                        name,
+                        input: json!({}),
+                    },
+                }));
+                chunks.push(Ok(ChatChunk::ContentBlockDelta {
+                    index,
+                    delta: ContentDelta::InputJsonDelta {
+                        partial_json: input.to_string(),
+                    },
+                }));
+                chunks.push(Ok(ChatChunk::ContentBlockStop { index }));
+            }
+        }
+        Err(e) => chunks.push(Err(e)),
+    }
+
+    if ollama_chunk.done {
+        chunks.push(Ok(ChatChunk::MessageDelta {
+            delta: MessageDelta {
+                stop_reason: Some(ollama_chunk.stop_reason(*tool_calls > 0)),
+                usage: Some(TokenUsage {
+                    input_tokens: ollama_chunk.prompt_eval_count.unwrap_or(0),
+                    output_tokens: ollama_chunk.eval_count.unwrap_or(0),
+                }),
+            },
+        }));
+        chunks.push(Ok(ChatChunk::MessageStop));
+    }
+
+    chunks
+}
+
+/// Text of a tool result, with a placeholder for each image
+fn tool_result_text(content: &[ToolResultContent]) -> String {
+    content
+        .iter()
+        .map(|c| match c {
+            ToolResultContent::Text { text } => text.clone(),
+            ToolResultContent::Image { media_type, .. } => format!("[{} image]", media_type),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 // Ollama API types
 
 #[derive(Debug, Serialize, Deserialize)]
 struct OllamaMessage {
     role: String,
     content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<serde_json::Value>,
 }
 
+/// Response (or stream line) from `/api/chat` or `/api/generate` - chat
+/// returns `message`, generate returns `response`
 #[derive(Debug, Deserialize)]
 struct OllamaResponse {
     model: String,
-    message: OllamaMessage,
+    #[serde(default)]
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    response: Option<String>,
     done: bool,
+    /// Why generation stopped ("stop", "length"), on the final object
+    #[serde(default)]
+    done_reason: Option<String>,
     #[serde(default)]
     prompt_eval_count: Option<usize>,
     #[serde(default)]
     eval_count: Option<usize>,
 }
 
-#[derive(Debug, Deserialize)]
-struct OllamaStreamChunk {
-    model: String,
-    message: OllamaMessage,
-    done: bool,
+impl OllamaResponse {
+    fn text(&self) -> String {
+        self.message
+            .as_ref()
+            .map(|m| m.content.clone())
+            .or_else(|| self.response.clone())
+            .unwrap_or_default()
+    }
+
+    fn tool_calls(&self) -> Result<Vec<ContentBlock>> {
+        match &self.message {
+            Some(message) if !message.tool_calls.is_empty() => {
+                from_provider_tool_calls(ToolFormat::Ollama, &json!(message.tool_calls))
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn stop_reason(&self, tool_calls: bool) -> StopReason {
+        if tool_calls {
+            StopReason::ToolUse
+        } else if !self.done || self.done_reason.as_deref() == Some("length") {
+            StopReason::MaxTokens
+        } else {
+            StopReason::EndTurn
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn parse(parts: &[&str]) -> ChatResponse {
+        let bytes = futures::stream::iter(
+            parts
+                .iter()
+                .map(|part| Ok::<_, std::io::Error>(part.as_bytes().to_vec()))
+                .collect::<Vec<_>>(),
+        );
+        let mut accumulator = super::super::StreamAccumulator::new("m".to_string());
+        let mut chunks = Box::pin(parse_ndjson(bytes));
+        while let Some(chunk) = chunks.next().await {
+            accumulator.push(chunk.unwrap()).unwrap();
+        }
+        accumulator.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn final_object_without_newline_is_parsed() {
+        let response = parse(&[
+            "{\"model\":\"m\",\"message\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"done\":false}\n",
+            "{\"model\":\"m\",\"message\":{\"role\":\"assistant\",\"content\":\"lo\"},\"done\":true,",
+            "\"done_reason\":\"stop\",\"prompt_eval_count\":3,\"eval_count\":2}",
+        ])
+        .await;
+
+        assert_eq!(response.text(), "Hello");
+        assert_eq!(response.stop_reason, Some(StopReason::EndTurn));
+        assert_eq!(response.usage.input_tokens, 3);
+        assert_eq!(response.usage.output_tokens, 2);
+    }
+
+    #[tokio::test]
+    async fn objects_split_across_chunks_are_joined() {
+        let response = parse(&[
+            "{\"model\":\"m\",\"message\":{\"role\":\"assi",
+            "stant\",\"content\":\"Hi\"},\"done\":true,\"done_reason\":\"length\"}\n",
+        ])
+        .await;
+
+        assert_eq!(response.text(), "Hi");
+        assert_eq!(response.stop_reason, Some(StopReason::MaxTokens));
+    }
+
+    #[tokio::test]
+    async fn recorded_tool_call_stream_ends_with_usage() {
+        let bytes = futures::stream::iter(vec![Ok::<_, std::io::Error>(
+            concat!(
+                "{\"model\":\"m\",\"message\":{\"role\":\"assistant\",\"content\":\"\",\"tool_calls\":",
+                "[{\"function\":{\"name\":\"weather::get\",\"arguments\":{\"city\":\"Paris\"}}}]},\"done\":false}\n",
+                "{\"model\":\"m\",\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,",
+                "\"done_reason\":\"stop\",\"prompt_eval_count\":31,\"eval_count\":12}\n",
+            )
+            .as_bytes()
+            .to_vec(),
+        )]);
+        let chunks: Vec<ChatChunk> = parse_ndjson(bytes)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        // No empty text deltas around the tool call
+        assert!(!chunks.iter().any(|chunk| matches!(
+            chunk,
+            ChatChunk::ContentBlockDelta {
+                delta: ContentDelta::TextDelta { .. },
+                ..
+            }
+        )));
+        assert!(matches!(
+            &chunks[0],
+            ChatChunk::ContentBlockStart {
+                index: 1,
+                content_block: ContentBlock::ToolUse { name, .. },
+            } if name == "weather::get"
+        ));
+        let ChatChunk::MessageDelta { delta } = &chunks[chunks.len() - 2] else {
+            panic!("expected a message delta, got {:?}", chunks[chunks.len() - 2]);
+        };
+        assert_eq!(delta.stop_reason, Some(StopReason::ToolUse));
+        let usage = delta.usage.as_ref().unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens), (31, 12));
+        assert!(matches!(chunks.last(), Some(ChatChunk::MessageStop)));
+    }
+
+    fn provider(configure: impl FnOnce(&mut crate::config::Config)) -> OllamaProvider {
+        let mut config: crate::config::Config =
+            toml::from_str(&crate::config::Config::example()).unwrap();
+        configure(&mut config);
+        OllamaProvider::new(&config).unwrap()
+    }
+
+    #[test]
+    fn extra_options_are_sent_under_typed_settings() {
+        let provider = provider(|config| {
+            config.llm.max_tokens = 512;
+            config.ollama.options.insert("num_ctx".to_string(), json!(8192));
+            config.ollama.options.insert("num_predict".to_string(), json!(1));
+        });
+
+        let (_, body) = provider.build_request(vec![Message::new_user("hi".to_string())], &[], false);
+        assert_eq!(body["options"]["num_ctx"], 8192);
+        // The typed max_tokens wins over the free-form option
+        assert_eq!(body["options"]["num_predict"], 512);
+    }
+
+    #[test]
+    fn failed_tool_result_is_marked_in_the_tool_message() {
+        let result = |is_error| {
+            Message::new_tool_results(vec![ContentBlock::ToolResult {
+                tool_use_id: "call_1".to_string(),
+                content: vec![ToolResultContent::text("No such file")],
+                is_error,
+            }])
+        };
+        let provider = provider(|_| {});
+
+        let (_, body) = provider.build_request(vec![result(Some(true))], &[], false);
+        assert_eq!(
+            body["messages"][0],
+            json!({ "role": "tool", "content": "Error: No such file" })
+        );
+        let (_, body) = provider.build_request(vec![result(None)], &[], false);
+        assert_eq!(body["messages"][0]["content"], "No such file");
+    }
+
+    #[test]
+    fn seed_and_temperature_are_sent_as_options() {
+        use crate::config::OllamaApi;
+
+        for api in [OllamaApi::Chat, OllamaApi::Generate] {
+            let provider = provider(|config| {
+                config.ollama.api = api;
+                config.llm.seed = Some(42);
+                config.llm.temperature = Some(0.0);
+                config.ollama.options.insert("seed".to_string(), json!(7));
+            });
+            let (_, body) =
+                provider.build_request(vec![Message::new_user("hi".to_string())], &[], false);
+            assert_eq!(body["options"]["seed"], 42);
+            assert_eq!(body["options"]["temperature"], 0.0);
+        }
+
+        let (_, body) =
+            provider(|_| {}).build_request(vec![Message::new_user("hi".to_string())], &[], false);
+        assert!(body["options"].get("seed").is_none());
+    }
+
+    fn keep_alive_sent(keep_alive: Option<&str>, api: crate::config::OllamaApi) -> serde_json::Value {
+        let provider = provider(|config| {
+            config.ollama.keep_alive = keep_alive.map(str::to_string);
+            config.ollama.api = api;
+        });
+        let (_, body) = provider.build_request(vec![Message::new_user("hi".to_string())], &[], false);
+        body.get("keep_alive").cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn keep_alive_is_sent_as_a_duration_or_seconds() {
+        use crate::config::OllamaApi;
+
+        for api in [OllamaApi::Chat, OllamaApi::Generate] {
+            assert_eq!(keep_alive_sent(Some("5m"), api), json!("5m"));
+            assert_eq!(keep_alive_sent(Some("-1"), api), json!(-1));
+            assert_eq!(keep_alive_sent(Some(" 300 "), api), json!(300));
+            assert!(keep_alive_sent(None, api).is_null());
+        }
+    }
+
+    fn generate_provider(server: &mockito::Server) -> OllamaProvider {
+        provider(|config| {
+            config.ollama.endpoint = server.url();
+            config.ollama.api = crate::config::OllamaApi::Generate;
+            config.ollama.prompt_template = PromptTemplate::ChatMl;
+        })
+    }
+
+    fn generate_request(stream: bool) -> mockito::Matcher {
+        mockito::Matcher::PartialJson(json!({
+            "prompt": "<|im_start|>user\nhi<|im_end|>\n<|im_start|>assistant\n",
+            "raw": true,
+            "stream": stream,
+        }))
+    }
+
+    #[tokio::test]
+    async fn generate_api_sends_a_prompt_and_reads_the_response() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/generate")
+            .match_body(generate_request(false))
+            .with_body(
+                json!({
+                    "model": "m",
+                    "response": "Hello",
+                    "done": true,
+                    "done_reason": "length",
+                    "prompt_eval_count": 7,
+                    "eval_count": 2,
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let provider = generate_provider(&server);
+        let response = provider
+            .chat(vec![Message::new_user("hi".to_string())], vec![])
+            .await
+            .unwrap();
+
+        assert_eq!(response.text(), "Hello");
+        assert_eq!(response.stop_reason, Some(StopReason::MaxTokens));
+        assert_eq!(response.usage.input_tokens, 7);
+        assert_eq!(response.usage.output_tokens, 2);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn generate_api_streams_response_lines() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/generate")
+            .match_body(generate_request(true))
+            .with_body(concat!(
+                "{\"model\":\"m\",\"response\":\"Hel\",\"done\":false}\n",
+                "{\"model\":\"m\",\"response\":\"lo\",\"done\":false}\n",
+                "{\"model\":\"m\",\"response\":\"\",\"done\":true,\"done_reason\":\"stop\",",
+                "\"prompt_eval_count\":7,\"eval_count\":2}\n",
+            ))
+            .create_async()
+            .await;
+
+        let provider = generate_provider(&server);
+        let stream = provider
+            .stream_chat(vec![Message::new_user("hi".to_string())], vec![])
+            .await
+            .unwrap();
+        let mut accumulator = super::super::StreamAccumulator::new("m".to_string());
+        let chunks: Vec<Result<ChatChunk>> = stream.collect().await;
+        for chunk in chunks {
+            accumulator.push(chunk.unwrap()).unwrap();
+        }
+        let response = accumulator.finish().unwrap();
+
+        assert_eq!(response.text(), "Hello");
+        assert_eq!(response.stop_reason, Some(StopReason::EndTurn));
+        assert_eq!(response.usage.input_tokens, 7);
+        assert_eq!(response.usage.output_tokens, 2);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn chat_api_sends_messages_and_reads_the_message() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/chat")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "messages": [{ "role": "user", "content": "hi" }],
+                "stream": false,
+            })))
+            .with_body(
+                json!({
+                    "model": "m",
+                    "message": { "role": "assistant", "content": "Hello" },
+                    "done": true,
+                    "done_reason": "stop",
+                    "prompt_eval_count": 7,
+                    "eval_count": 2,
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let provider = provider(|config| config.ollama.endpoint = server.url());
+        let response = provider
+            .chat(vec![Message::new_user("hi".to_string())], vec![])
+            .await
+            .unwrap();
+
+        assert_eq!(response.text(), "Hello");
+        assert_eq!(response.stop_reason, Some(StopReason::EndTurn));
+        assert_eq!(response.usage.output_tokens, 2);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn streamed_response_over_max_response_bytes_fails() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/chat")
+            .with_body(concat!(
+                "{\"model\":\"m\",\"message\":{\"role\":\"assistant\",\"content\":\"Hello\"},\"done\":false}\n",
+                "{\"model\":\"m\",\"message\":{\"role\":\"assistant\",\"content\":\" world\"},\"done\":false}\n",
+                "{\"model\":\"m\",\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true}\n",
+            ))
+            .create_async()
+            .await;
+
+        let provider = provider(|config| {
+            config.ollama.endpoint = server.url();
+            config.llm.max_response_bytes = 8;
+        });
+        let error = super::super::chat_collect(
+            &provider,
+            vec![Message::new_user("hi".to_string())],
+            vec![],
+        )
+        .await
+        .unwrap_err();
+
+        assert!(
+            error.to_string().contains("response exceeded max size (8 bytes"),
+            "{}",
+            error
+        );
+    }
+
+    /// A stream that produced `text` and then lost its connection
+    fn dropped_stream(text: &str) -> ChunkStream {
+        Box::new(futures::stream::iter(vec![
+            Ok(ChatChunk::ContentBlockDelta {
+                index: 0,
+                delta: ContentDelta::TextDelta {
+                    text: text.to_string(),
+                },
+            }),
+            Ok(ChatChunk::Error {
+                error: "connection reset".to_string(),
+            }),
+        ]))
+    }
+
+    async fn collect(stream: ChunkStream) -> (String, Vec<String>) {
+        let chunks: Vec<ChatChunk> = stream.map(|chunk| chunk.unwrap()).collect().await;
+        let mut text = String::new();
+        let mut errors = Vec::new();
+        for chunk in chunks {
+            match chunk {
+                ChatChunk::ContentBlockDelta {
+                    delta: ContentDelta::TextDelta { text: delta },
+                    ..
+                } => text.push_str(&delta),
+                ChatChunk::Error { error } => errors.push(error),
+                _ => {}
+            }
+        }
+        (text, errors)
+    }
+
+    #[tokio::test]
+    async fn dropped_stream_resumes_after_a_backoff() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/chat")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "messages": [{ "role": "user" }, { "role": "assistant", "content": "Hel" }],
+            })))
+            .with_body("{\"model\":\"m\",\"message\":{\"role\":\"assistant\",\"content\":\"lo\"},\"done\":true}\n")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut provider = provider(|config| config.ollama.endpoint = server.url());
+        provider.resume_policy = BackoffPolicy {
+            initial_delay: Duration::from_millis(50),
+            jitter: false,
+            ..BackoffPolicy::new(3)
+        };
+        let (_, body) = provider.build_request(vec![Message::new_user("hi".to_string())], &[], true);
+
+        let started = std::time::Instant::now();
+        let (text, errors) = collect(provider.resuming("api/chat", body, dropped_stream("Hel"))).await;
+
+        assert_eq!(text, "Hello");
+        assert!(errors.is_empty());
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn dropped_stream_is_not_resumed_without_retries() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("POST", "/api/chat").expect(0).create_async().await;
+
+        let mut provider = provider(|config| config.ollama.endpoint = server.url());
+        provider.resume_policy = BackoffPolicy::new(0);
+        let (_, body) = provider.build_request(vec![Message::new_user("hi".to_string())], &[], true);
+
+        let (text, errors) = collect(provider.resuming("api/chat", body, dropped_stream("Hel"))).await;
+
+        assert_eq!(text, "Hel");
+        assert_eq!(errors, ["connection reset"]);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn length_stop_reason_reaches_the_renderer() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/chat")
+            .with_body(concat!(
+                "{\"model\":\"m\",\"message\":{\"role\":\"assistant\",\"content\":\"Once upon\"},\"done\":false}\n",
+                "{\"model\":\"m\",\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"done_reason\":\"length\"}\n",
+            ))
+            .create_async()
+            .await;
+
+        let provider = provider(|config| config.ollama.endpoint = server.url());
+        let (stop_reason, stops) =
+            crate::ui::renderer::tests::streamed_stop_reasons(&provider).await;
+        assert_eq!(stop_reason, Some(StopReason::MaxTokens));
+        assert_eq!(stops, [StopReason::MaxTokens]);
+    }
+
+    #[test]
+    fn response_format_is_sent_as_format() {
+        let schema = json!({ "type": "object", "properties": { "answer": { "type": "string" } } });
+        let format_sent = |response_format: ResponseFormat| {
+            let provider = provider(|config| config.llm.response_format = response_format);
+            let (_, body) =
+                provider.build_request(vec![Message::new_user("hi".to_string())], &[], false);
+            body.get("format").cloned()
+        };
+
+        assert_eq!(format_sent(ResponseFormat::JsonObject), Some(json!("json")));
+        assert_eq!(
+            format_sent(ResponseFormat::JsonSchema {
+                name: "answer".to_string(),
+                schema: schema.clone(),
+            }),
+            Some(schema)
+        );
+        assert_eq!(format_sent(ResponseFormat::Text), None);
+    }
 }
+
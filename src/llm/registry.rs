@@ -0,0 +1,248 @@
+use super::{
+    CachingProvider, LlmProvider, RequestDumpingProvider, ResponseCache, RetryingProvider,
+    RouterProvider, ToolPromptingProvider,
+};
+use crate::config::Config;
+use crate::error::{OllmError, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// `llm.provider` names handled without registering a factory
+pub const BUILTIN_PROVIDERS: &[&str] = &["anthropic", "ollama", "huggingface", "router"];
+
+/// Constructs a provider from the loaded config
+pub type ProviderFactory = Arc<dyn Fn(&Config) -> Result<Box<dyn LlmProvider>> + Send + Sync>;
+
+/// Maps `llm.provider` names to provider constructors
+///
+/// Custom factories are consulted first, so registering a built-in name
+/// (e.g. "ollama") replaces the built-in provider.
+#[derive(Clone, Default)]
+pub struct ProviderRegistry {
+    factories: HashMap<String, ProviderFactory>,
+    dump_requests: bool,
+}
+
+impl ProviderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a factory for `name`, replacing any previous registration
+    pub fn register<F>(&mut self, name: impl Into<String>, factory: F) -> &mut Self
+    where
+        F: Fn(&Config) -> Result<Box<dyn LlmProvider>> + Send + Sync + 'static,
+    {
+        self.factories.insert(name.into(), Arc::new(factory));
+        self
+    }
+
+    /// Print each request body the created providers send to stderr (see
+    /// `RequestDumpingProvider`)
+    pub fn dump_requests(&mut self, enabled: bool) -> &mut Self {
+        self.dump_requests = enabled;
+        self
+    }
+
+    /// Whether a custom factory is registered for `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    /// Construct the provider named by `config.llm.provider`, wrapped to
+    /// prompt for tool calls when `llm.tool_prompting` is set and the
+    /// provider lacks native tool use, to retry transient failures when
+    /// `llm.max_retries` is set, and to answer from the response cache when
+    /// `[cache]` is enabled
+    ///
+    /// For "router" each backend is created (and wrapped) this way in turn.
+    pub fn create(&self, config: &Config) -> Result<Box<dyn LlmProvider>> {
+        if config.llm.provider == "router" && !self.contains("router") {
+            return Ok(Box::new(RouterProvider::new(config, |c| self.create(c))?));
+        }
+
+        let mut provider = self.create_unwrapped(config)?;
+
+        // Innermost, so the dump shows what is actually sent: after tool
+        // prompting, on every retry, and never for a cache hit
+        if self.dump_requests {
+            provider = Box::new(RequestDumpingProvider::new(provider));
+        }
+
+        if config.llm.tool_prompting && !provider.supports_tools() {
+            provider = Box::new(ToolPromptingProvider::new(
+                provider,
+                config.llm.max_tool_description_chars,
+            ));
+        }
+
+        if config.llm.max_retries > 0 {
+            provider = Box::new(RetryingProvider::new(provider, config.llm.max_retries));
+        }
+
+        // Outermost, so a hit skips retries and tool prompting entirely
+        let deterministic = config.llm.temperature == Some(0.0);
+        if config.cache.enabled && (deterministic || config.cache.nondeterministic) {
+            provider = Box::new(CachingProvider::new(
+                provider,
+                ResponseCache::new(config.cache_dir()),
+                cache_params(config),
+            ));
+        }
+
+        Ok(provider)
+    }
+
+    fn create_unwrapped(&self, config: &Config) -> Result<Box<dyn LlmProvider>> {
+        let name = config.llm.provider.as_str();
+
+        if let Some(factory) = self.factories.get(name) {
+            return factory(config);
+        }
+
+        match name {
+            "anthropic" => Ok(Box::new(super::anthropic::AnthropicProvider::new(config)?)),
+            "ollama" => Ok(Box::new(super::ollama::OllamaProvider::new(config)?)),
+            "huggingface" => Ok(Box::new(super::huggingface::HuggingFaceProvider::new(config)?)),
+            _ => Err(OllmError::Config(format!("Unknown LLM provider: {}", name))),
+        }
+    }
+}
+
+/// Settings besides the messages and tools that change the request body,
+/// and so belong in the response cache key
+fn cache_params(config: &Config) -> serde_json::Value {
+    serde_json::json!({
+        "max_tokens": config.llm.max_tokens,
+        "temperature": config.llm.temperature,
+        "seed": config.llm.seed,
+        "tool_choice": config.llm.tool_choice,
+        "response_format": config.llm.response_format,
+        "tool_prompting": config.llm.tool_prompting,
+        "max_tool_description_chars": config.llm.max_tool_description_chars,
+        "thinking_budget_tokens": config.anthropic.thinking_budget_tokens,
+        "ollama_api": config.ollama.api,
+        "ollama_prompt_template": config.ollama.prompt_template,
+        "ollama_native_tools": config.ollama.native_tools,
+        "ollama_options": config.ollama.options,
+        "keep_alive": config.ollama.keep_alive,
+        "huggingface_api_style": config.huggingface.api_style,
+        "huggingface_prompt_template": config.huggingface.prompt_template,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock::MockProvider;
+    use crate::llm::ProviderCapabilities;
+
+    fn config(provider: &str) -> Config {
+        let mut config: Config = toml::from_str(&Config::example()).unwrap();
+        config.llm.provider = provider.to_string();
+        config.llm.max_retries = 0;
+        config
+    }
+
+    #[test]
+    fn custom_provider_is_constructed_by_name() {
+        let mut registry = ProviderRegistry::new();
+        registry.register("internal", |_| Ok(Box::new(MockProvider::echo())));
+        assert!(registry.contains("internal"));
+
+        let provider = registry.create(&config("internal")).unwrap();
+        assert_eq!(provider.name(), "mock");
+    }
+
+    #[test]
+    fn unknown_provider_is_an_error() {
+        let error = ProviderRegistry::new()
+            .create(&config("internal"))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Configuration error: Unknown LLM provider: internal"
+        );
+    }
+
+    #[test]
+    fn settings_that_change_the_request_change_the_cache_params() {
+        use crate::config::{HuggingFaceApiStyle, OllamaApi, PromptTemplate};
+
+        let base = config("ollama");
+        let changes: Vec<fn(&mut Config)> = vec![
+            |c| c.ollama.api = OllamaApi::Generate,
+            |c| c.ollama.prompt_template = PromptTemplate::ChatMl,
+            |c| c.ollama.native_tools = !c.ollama.native_tools,
+            |c| c.huggingface.api_style = HuggingFaceApiStyle::Tgi,
+            |c| c.huggingface.prompt_template = PromptTemplate::Llama,
+            |c| c.llm.tool_prompting = !c.llm.tool_prompting,
+            |c| c.llm.max_tool_description_chars = Some(10),
+        ];
+        for (i, change) in changes.into_iter().enumerate() {
+            let mut changed = base.clone();
+            change(&mut changed);
+            assert_ne!(cache_params(&changed), cache_params(&base), "change {}", i);
+        }
+    }
+
+    #[test]
+    fn builtin_providers_report_their_capabilities() {
+        std::env::set_var("OLLM_TEST_CAPABILITIES_KEY", "test-key");
+        let capabilities = |provider: &str, configure: fn(&mut Config)| {
+            let mut config = config(provider);
+            config.llm.api_key_env = Some("OLLM_TEST_CAPABILITIES_KEY".to_string());
+            config.llm.tool_prompting = false;
+            config.llm.max_tokens = 4096;
+            configure(&mut config);
+            ProviderRegistry::new().create(&config).unwrap().capabilities()
+        };
+
+        assert_eq!(
+            capabilities("anthropic", |c| c.llm.model = "claude-sonnet-4-20250514".to_string()),
+            ProviderCapabilities {
+                tools: true,
+                streaming: true,
+                vision: true,
+                max_tokens: 4096,
+                max_output_tokens: Some(64_000),
+                context_window: Some(200_000),
+            }
+        );
+        assert_eq!(
+            capabilities("huggingface", |c| {
+                c.llm.model = "HuggingFaceH4/zephyr-7b-beta".to_string()
+            }),
+            ProviderCapabilities {
+                tools: false,
+                streaming: true,
+                vision: false,
+                max_tokens: 4096,
+                max_output_tokens: None,
+                context_window: None,
+            }
+        );
+
+        // Ollama's tools depend on native_tools and the chat API
+        let ollama = capabilities("ollama", |c| {
+            c.llm.model = "llama3.1".to_string();
+            c.ollama.native_tools = true;
+        });
+        assert!(ollama.tools && ollama.streaming && !ollama.vision);
+        assert_eq!(ollama.max_tokens, 4096);
+        let ollama = capabilities("ollama", |c| {
+            c.ollama.native_tools = true;
+            c.ollama.api = crate::config::OllamaApi::Generate;
+        });
+        assert!(!ollama.tools);
+
+        // Tool prompting gives providers without native tools tool use
+        let mut config = config("huggingface");
+        config.llm.api_key_env = Some("OLLM_TEST_CAPABILITIES_KEY".to_string());
+        config.llm.tool_prompting = true;
+        let provider = ProviderRegistry::new().create(&config).unwrap();
+        assert!(provider.capabilities().tools);
+    }
+}
+
@@ -0,0 +1,239 @@
+use super::types::*;
+use super::LlmProvider;
+use crate::error::{OllmError, Result};
+use crate::retry::{retry_async_if, BackoffPolicy};
+use crate::types::{Message, Tool};
+use async_trait::async_trait;
+use futures::Stream;
+use std::time::Duration;
+use tracing::warn;
+
+/// Wraps a provider and retries requests that fail with a retryable error
+/// (timeouts, connection failures, 408/429/5xx)
+///
+/// Streaming requests are only retried while opening the stream, not once
+/// chunks have started arriving.
+pub struct RetryingProvider {
+    inner: Box<dyn LlmProvider>,
+    policy: BackoffPolicy,
+}
+
+impl RetryingProvider {
+    /// Retry up to `max_retries` times with the default backoff
+    pub fn new(inner: Box<dyn LlmProvider>, max_retries: u32) -> Self {
+        Self::with_policy(inner, BackoffPolicy::new(max_retries))
+    }
+
+    pub fn with_policy(inner: Box<dyn LlmProvider>, policy: BackoffPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    /// Whether to retry `error`, logging the retry if so
+    fn should_retry(&self, error: &OllmError, attempt: u32, delay: Duration) -> bool {
+        if !error.is_retryable() {
+            return false;
+        }
+
+        if let OllmError::Overloaded { provider, .. } = error {
+            warn!(
+                "{} is overloaded, retrying in {:?} ({}/{})",
+                provider, delay, attempt, self.policy.max_retries
+            );
+        } else {
+            warn!(
+                "{} request failed ({}), retrying in {:?} ({}/{})",
+                self.inner.name(),
+                error,
+                delay,
+                attempt,
+                self.policy.max_retries
+            );
+        }
+        true
+    }
+
+    /// The error to return once retries are used up, saying so for
+    /// overload errors
+    fn give_up(&self, error: OllmError) -> OllmError {
+        match error {
+            OllmError::Overloaded {
+                provider,
+                status,
+                message,
+            } if self.policy.max_retries > 0 => OllmError::Overloaded {
+                message: format!(
+                    "{} (still overloaded after {} retries; try again later)",
+                    message, self.policy.max_retries
+                ),
+                provider,
+                status,
+            },
+            e => e,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for RetryingProvider {
+    async fn chat(&self, messages: Vec<Message>, tools: Vec<Tool>) -> Result<ChatResponse> {
+        retry_async_if(
+            &self.policy,
+            |e, attempt, delay| self.should_retry(e, attempt, delay),
+            || self.inner.chat(messages.clone(), tools.clone()),
+        )
+        .await
+        .map_err(|e| self.give_up(e))
+    }
+
+    async fn stream_chat(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>> {
+        retry_async_if(
+            &self.policy,
+            |e, attempt, delay| self.should_retry(e, attempt, delay),
+            || self.inner.stream_chat(messages.clone(), tools.clone()),
+        )
+        .await
+        .map_err(|e| self.give_up(e))
+    }
+
+    async fn stream_chat_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Box<dyn Stream<Item = Result<StreamEvent>> + Send + Unpin>> {
+        retry_async_if(
+            &self.policy,
+            |e, attempt, delay| self.should_retry(e, attempt, delay),
+            || self.inner.stream_chat_events(messages.clone(), tools.clone()),
+        )
+        .await
+        .map_err(|e| self.give_up(e))
+    }
+
+    fn request_body(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+        stream: bool,
+    ) -> Result<Option<serde_json::Value>> {
+        self.inner.request_body(messages, tools, stream)
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.inner.supports_tools()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    fn supports_prefill(&self) -> bool {
+        self.inner.supports_prefill()
+    }
+
+    fn estimate_tokens(&self, text: &str) -> usize {
+        self.inner.estimate_tokens(text)
+    }
+
+    fn max_tokens(&self) -> usize {
+        self.inner.max_tokens()
+    }
+
+    fn model_max_output_tokens(&self) -> Option<usize> {
+        self.inner.model_max_output_tokens()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    /// Fails with `error()` on every call, counting the calls
+    struct Failing {
+        calls: Arc<AtomicU32>,
+        error: fn() -> OllmError,
+    }
+
+    #[async_trait]
+    impl LlmProvider for Failing {
+        async fn chat(&self, _messages: Vec<Message>, _tools: Vec<Tool>) -> Result<ChatResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Err((self.error)())
+        }
+
+        async fn stream_chat(
+            &self,
+            _messages: Vec<Message>,
+            _tools: Vec<Tool>,
+        ) -> Result<Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Err((self.error)())
+        }
+
+        fn supports_tools(&self) -> bool {
+            false
+        }
+
+        fn max_tokens(&self) -> usize {
+            4096
+        }
+
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn model(&self) -> &str {
+            "failing"
+        }
+    }
+
+    /// Calls made per request by a `RetryingProvider` allowing
+    /// `max_retries` before it gives up on `error`
+    async fn calls(error: fn() -> OllmError, max_retries: u32) -> u32 {
+        let calls = Arc::new(AtomicU32::new(0));
+        let inner = Failing {
+            calls: calls.clone(),
+            error,
+        };
+        let policy = BackoffPolicy {
+            initial_delay: Duration::from_millis(1),
+            jitter: false,
+            ..BackoffPolicy::new(max_retries)
+        };
+        let provider = RetryingProvider::with_policy(Box::new(inner), policy);
+
+        assert!(provider.chat(Vec::new(), Vec::new()).await.is_err());
+        assert!(provider.stream_chat(Vec::new(), Vec::new()).await.is_err());
+        calls.load(Ordering::SeqCst) / 2
+    }
+
+    #[tokio::test]
+    async fn retryable_errors_are_retried_up_to_the_limit() {
+        assert_eq!(calls(|| OllmError::Timeout(30), 3).await, 4);
+        assert_eq!(calls(|| OllmError::from_api_response(503, "busy"), 1).await, 2);
+        assert_eq!(calls(|| OllmError::Timeout(30), 0).await, 1);
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_returned_at_once() {
+        assert_eq!(calls(|| OllmError::from_api_response(400, "bad request"), 3).await, 1);
+        assert_eq!(calls(|| OllmError::Config("bad".to_string()), 3).await, 1);
+    }
+}
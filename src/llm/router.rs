@@ -0,0 +1,254 @@
+use super::types::*;
+use super::LlmProvider;
+use crate::config::{Config, RouteRule};
+use crate::error::{OllmError, Result};
+use crate::types::{Message, Role, Tool};
+use async_trait::async_trait;
+use futures::Stream;
+use regex::Regex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::debug;
+
+/// Delegates each request to one of several backends, chosen by `[router]`
+/// rules matched against the latest user message
+///
+/// `model()` and `max_tokens()` report the backend chosen for the most
+/// recent request (the default backend before any request).
+pub struct RouterProvider {
+    /// Backends by name, in name order
+    backends: Vec<(String, Box<dyn LlmProvider>)>,
+    rules: Vec<Rule>,
+    default: usize,
+    chosen: AtomicUsize,
+}
+
+struct Rule {
+    backend: usize,
+    keywords: Vec<String>,
+    min_chars: Option<usize>,
+    max_chars: Option<usize>,
+    pattern: Option<Regex>,
+}
+
+impl Rule {
+    fn matches(&self, text: &str) -> bool {
+        let chars = text.chars().count();
+        let lower = text.to_lowercase();
+
+        (self.keywords.is_empty() || self.keywords.iter().any(|k| lower.contains(k.as_str())))
+            && self.min_chars.is_none_or(|min| chars >= min)
+            && self.max_chars.is_none_or(|max| chars <= max)
+            && self.pattern.as_ref().is_none_or(|p| p.is_match(text))
+    }
+}
+
+impl RouterProvider {
+    /// Build every `[router]` backend with `create`, which receives a copy of
+    /// `config` pointed at the backend's provider and model
+    pub fn new<F>(config: &Config, create: F) -> Result<Self>
+    where
+        F: Fn(&Config) -> Result<Box<dyn LlmProvider>>,
+    {
+        let router = &config.router;
+
+        let mut names: Vec<&String> = router.backends.keys().collect();
+        names.sort();
+
+        let mut backends = Vec::with_capacity(names.len());
+        for name in names {
+            let backend = &router.backends[name];
+            if backend.provider == "router" {
+                return Err(OllmError::Config(format!(
+                    "router.backends.{}: a router backend can't be another router",
+                    name
+                )));
+            }
+
+            let mut backend_config = config.clone();
+            backend_config.llm.provider = backend.provider.clone();
+            backend_config.llm.model = backend.model.clone();
+
+            backends.push((name.clone(), create(&backend_config)?));
+        }
+
+        let index_of = |name: &str, context: &str| {
+            backends
+                .iter()
+                .position(|(n, _)| n == name)
+                .ok_or_else(|| {
+                    OllmError::Config(format!("{}: '{}' is not a configured backend", context, name))
+                })
+        };
+
+        let default = index_of(&router.default, "router.default")?;
+        let rules = router
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| compile_rule(rule, index_of(&rule.backend, &format!("router.rules[{}]", i))?))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            backends,
+            rules,
+            default,
+            chosen: AtomicUsize::new(default),
+        })
+    }
+
+    /// Pick the backend for `messages` and remember it as the current one
+    fn route(&self, messages: &[Message]) -> &dyn LlmProvider {
+        let text = messages
+            .iter()
+            .rev()
+            .find(|m| m.role == Role::User)
+            .map(super::templates::message_text)
+            .unwrap_or_default();
+
+        let index = self
+            .rules
+            .iter()
+            .find(|rule| rule.matches(&text))
+            .map_or(self.default, |rule| rule.backend);
+
+        let (name, backend) = &self.backends[index];
+        debug!("Routing request to backend '{}' ({})", name, backend.model());
+        self.chosen.store(index, Ordering::Relaxed);
+        backend.as_ref()
+    }
+
+    fn current(&self) -> &dyn LlmProvider {
+        self.backends[self.chosen.load(Ordering::Relaxed)].1.as_ref()
+    }
+}
+
+fn compile_rule(rule: &RouteRule, backend: usize) -> Result<Rule> {
+    let pattern = match &rule.pattern {
+        Some(pattern) => Some(Regex::new(pattern).map_err(|e| {
+            OllmError::Config(format!("Invalid router pattern '{}': {}", pattern, e))
+        })?),
+        None => None,
+    };
+
+    Ok(Rule {
+        backend,
+        keywords: rule.keywords.iter().map(|k| k.to_lowercase()).collect(),
+        min_chars: rule.min_chars,
+        max_chars: rule.max_chars,
+        pattern,
+    })
+}
+
+#[async_trait]
+impl LlmProvider for RouterProvider {
+    async fn chat(&self, messages: Vec<Message>, tools: Vec<Tool>) -> Result<ChatResponse> {
+        self.route(&messages).chat(messages, tools).await
+    }
+
+    async fn stream_chat(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>> {
+        self.route(&messages).stream_chat(messages, tools).await
+    }
+
+    async fn stream_chat_events(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Box<dyn Stream<Item = Result<StreamEvent>> + Send + Unpin>> {
+        self.route(&messages).stream_chat_events(messages, tools).await
+    }
+
+    fn request_body(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+        stream: bool,
+    ) -> Result<Option<serde_json::Value>> {
+        self.route(&messages).request_body(messages, tools, stream)
+    }
+
+    /// Only when every backend does, since any of them may get the request
+    fn supports_tools(&self) -> bool {
+        self.backends.iter().all(|(_, b)| b.supports_tools())
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.backends.iter().all(|(_, b)| b.supports_streaming())
+    }
+
+    fn supports_prefill(&self) -> bool {
+        self.backends.iter().all(|(_, b)| b.supports_prefill())
+    }
+
+    fn estimate_tokens(&self, text: &str) -> usize {
+        self.current().estimate_tokens(text)
+    }
+
+    fn max_tokens(&self) -> usize {
+        self.current().max_tokens()
+    }
+
+    fn model_max_output_tokens(&self) -> Option<usize> {
+        self.current().model_max_output_tokens()
+    }
+
+    fn name(&self) -> &str {
+        "router"
+    }
+
+    fn model(&self) -> &str {
+        self.current().model()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock::MockProvider;
+
+    /// A router whose backends each answer with their own model name
+    fn router() -> RouterProvider {
+        let mut config: Config = toml::from_str(&Config::example()).unwrap();
+        config.router = toml::from_str(
+            r#"
+            default = "fast"
+            [backends.fast]
+            provider = "mock"
+            model = "small"
+            [backends.strong]
+            provider = "mock"
+            model = "large"
+            [[rules]]
+            backend = "strong"
+            min_chars = 40
+            "#,
+        )
+        .unwrap();
+
+        RouterProvider::new(&config, |config| {
+            Ok(Box::new(MockProvider::with_responses(vec![config.llm.model.clone(); 4])))
+        })
+        .unwrap()
+    }
+
+    async fn answer(router: &RouterProvider, prompt: &str) -> String {
+        router
+            .chat(vec![Message::new_user(prompt.to_string())], vec![])
+            .await
+            .unwrap()
+            .text()
+    }
+
+    #[tokio::test]
+    async fn short_and_long_prompts_reach_different_backends() {
+        let router = router();
+
+        assert_eq!(answer(&router, "hi").await, "small");
+
+        let long = "Explain how the borrow checker handles two-phase borrows";
+        assert_eq!(answer(&router, long).await, "large");
+    }
+}
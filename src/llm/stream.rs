@@ -0,0 +1,418 @@
+use super::types::*;
+use crate::error::{OllmError, Result};
+use crate::types::ContentBlock;
+use futures::{Stream, StreamExt};
+use std::collections::BTreeMap;
+
+/// Accumulates streamed `ChatChunk`s into a complete `ChatResponse`
+#[derive(Debug, Default)]
+pub struct StreamAccumulator {
+    model: String,
+    blocks: BTreeMap<usize, ContentBlock>,
+    partial_json: BTreeMap<usize, String>,
+    /// Tool-use blocks stopped since the last `take_completed_tool_uses`
+    completed_tool_uses: Vec<usize>,
+    stop_reason: Option<StopReason>,
+    usage: Option<TokenUsage>,
+    service_tier: Option<String>,
+}
+
+impl StreamAccumulator {
+    pub fn new(model: String) -> Self {
+        Self {
+            model,
+            ..Default::default()
+        }
+    }
+
+    /// Apply a single chunk to the accumulated response
+    pub fn push(&mut self, chunk: ChatChunk) -> Result<()> {
+        match chunk {
+            ChatChunk::MessageStart { message } => {
+                self.model = message.model;
+                if message.service_tier.is_some() {
+                    self.service_tier = message.service_tier;
+                }
+                if let Some(usage) = message.usage {
+                    self.merge_usage(usage);
+                }
+            }
+            ChatChunk::ContentBlockStart {
+                index,
+                content_block,
+            } => {
+                self.blocks.insert(index, content_block);
+            }
+            ChatChunk::ContentBlockDelta { index, delta } => self.apply_delta(index, delta),
+            ChatChunk::ContentBlockStop { index } => {
+                self.finish_block(index)?;
+                if let Some(ContentBlock::ToolUse { .. }) = self.blocks.get(&index) {
+                    self.completed_tool_uses.push(index);
+                }
+            }
+            ChatChunk::MessageDelta { delta } => {
+                if delta.stop_reason.is_some() {
+                    self.stop_reason = delta.stop_reason;
+                }
+                if let Some(usage) = delta.usage {
+                    self.merge_usage(usage);
+                }
+            }
+            ChatChunk::MessageStop | ChatChunk::Ping => {}
+            ChatChunk::Error { error } => {
+                return Err(OllmError::StreamInterrupted {
+                    message: error,
+                    partial: self.text(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Text accumulated so far across all text blocks
+    pub fn text(&self) -> String {
+        self.blocks
+            .values()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Why generation stopped, once a `MessageDelta` has reported it
+    pub fn stop_reason(&self) -> Option<&StopReason> {
+        self.stop_reason.as_ref()
+    }
+
+    /// Tool-use blocks whose input finished streaming since the last call,
+    /// in the order they stopped
+    ///
+    /// Only providers that send `ContentBlockStop` report blocks here; the
+    /// rest are complete only in `finish`.
+    pub fn take_completed_tool_uses(&mut self) -> Vec<ContentBlock> {
+        std::mem::take(&mut self.completed_tool_uses)
+            .into_iter()
+            .filter_map(|index| self.blocks.get(&index).cloned())
+            .collect()
+    }
+
+    /// Get the block accumulated so far at `index`
+    pub fn block(&self, index: usize) -> Option<&ContentBlock> {
+        self.blocks.get(&index)
+    }
+
+    /// Merge usage reports, keeping earlier counts the new report omits
+    /// (Anthropic sends input tokens at start and output tokens at the end)
+    fn merge_usage(&mut self, usage: TokenUsage) {
+        let current = self.usage.get_or_insert(TokenUsage {
+            input_tokens: 0,
+            output_tokens: 0,
+        });
+        if usage.input_tokens > 0 {
+            current.input_tokens = usage.input_tokens;
+        }
+        if usage.output_tokens > 0 {
+            current.output_tokens = usage.output_tokens;
+        }
+    }
+
+    fn apply_delta(&mut self, index: usize, delta: ContentDelta) {
+        match delta {
+            ContentDelta::TextDelta { text } => {
+                // Providers without block start events (Ollama, HuggingFace)
+                // only send text deltas, so create the block on demand
+                let block = self.blocks.entry(index).or_insert(ContentBlock::Text {
+                    text: String::new(),
+                });
+                if let ContentBlock::Text { text: existing } = block {
+                    existing.push_str(&text);
+                }
+            }
+            ContentDelta::InputJsonDelta { partial_json } => {
+                self.partial_json
+                    .entry(index)
+                    .or_default()
+                    .push_str(&partial_json);
+            }
+            ContentDelta::ThinkingDelta { thinking } => {
+                if let Some(ContentBlock::Thinking {
+                    thinking: existing,
+                    ..
+                }) = self.blocks.get_mut(&index)
+                {
+                    existing.push_str(&thinking);
+                }
+            }
+            ContentDelta::SignatureDelta { signature } => {
+                if let Some(ContentBlock::Thinking {
+                    signature: existing,
+                    ..
+                }) = self.blocks.get_mut(&index)
+                {
+                    *existing = signature;
+                }
+            }
+        }
+    }
+
+    fn finish_block(&mut self, index: usize) -> Result<()> {
+        let Some(json) = self.partial_json.remove(&index) else {
+            return Ok(());
+        };
+
+        if let Some(ContentBlock::ToolUse { input, .. }) = self.blocks.get_mut(&index) {
+            if !json.trim().is_empty() {
+                *input = serde_json::from_str(&json).map_err(|e| {
+                    OllmError::LlmProvider(format!("Failed to parse tool input: {}", e))
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Consume the accumulator and build the final response
+    pub fn finish(mut self) -> Result<ChatResponse> {
+        let pending: Vec<usize> = self.partial_json.keys().copied().collect();
+        for index in pending {
+            self.finish_block(index)?;
+        }
+
+        Ok(ChatResponse {
+            content: self.blocks.into_values().collect(),
+            model: self.model,
+            stop_reason: self.stop_reason,
+            usage: self.usage.unwrap_or(TokenUsage {
+                input_tokens: 0,
+                output_tokens: 0,
+            }),
+            service_tier: self.service_tier,
+        })
+    }
+}
+
+/// Wrap a chunk stream so it also yields `StreamEvent::UsageUpdate`s
+///
+/// Updates are emitted whenever a `MessageDelta` reports usage; with
+/// `count_deltas`, each content delta also counts as one output token (for
+/// providers like Ollama that stream roughly one token per line and only
+/// report usage at the end).
+pub fn with_usage_updates(
+    stream: Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>,
+    count_deltas: bool,
+) -> Box<dyn Stream<Item = Result<StreamEvent>> + Send + Unpin> {
+    let stream = stream
+        .scan(0usize, move |output_tokens, chunk| {
+            let events = match chunk {
+                Ok(chunk) => {
+                    let update = match &chunk {
+                        ChatChunk::MessageDelta {
+                            delta:
+                                MessageDelta {
+                                    usage: Some(usage), ..
+                                },
+                        } => {
+                            *output_tokens = usage.output_tokens;
+                            true
+                        }
+                        ChatChunk::ContentBlockDelta { .. } if count_deltas => {
+                            *output_tokens += 1;
+                            true
+                        }
+                        _ => false,
+                    };
+
+                    let mut events = vec![Ok(StreamEvent::Chunk(chunk))];
+                    if update {
+                        events.push(Ok(StreamEvent::UsageUpdate {
+                            output_tokens_so_far: *output_tokens,
+                        }));
+                    }
+                    events
+                }
+                Err(e) => vec![Err(e)],
+            };
+            futures::future::ready(Some(futures::stream::iter(events)))
+        })
+        .flatten();
+
+    Box::new(Box::pin(stream))
+}
+
+/// Fail a chunk stream once its output grows past `max_bytes`
+///
+/// Counts the bytes of text, thinking, and tool-input deltas. The error
+/// replaces the chunk that crossed the limit, and the stream ends there.
+pub fn limit_response_bytes(
+    stream: Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>,
+    max_bytes: usize,
+) -> Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin> {
+    let stream = stream.scan(Some(0usize), move |received, chunk| {
+        let Some(total) = received else {
+            return futures::future::ready(None);
+        };
+
+        if let Ok(ChatChunk::ContentBlockDelta { delta, .. }) = &chunk {
+            *total += match delta {
+                ContentDelta::TextDelta { text } => text.len(),
+                ContentDelta::InputJsonDelta { partial_json } => partial_json.len(),
+                ContentDelta::ThinkingDelta { thinking } => thinking.len(),
+                ContentDelta::SignatureDelta { .. } => 0,
+            };
+            if *total > max_bytes {
+                *received = None;
+                return futures::future::ready(Some(Err(super::http::response_too_large(max_bytes))));
+            }
+        }
+
+        futures::future::ready(Some(chunk))
+    });
+
+    Box::new(Box::pin(stream))
+}
+
+/// Pump `stream` through a bounded channel holding at most `capacity`
+/// chunks
+///
+/// A background task reads ahead of the consumer until the channel is
+/// full, then waits, so a slow consumer pauses the underlying HTTP read
+/// instead of letting chunks pile up. Dropping the returned stream stops
+/// the task.
+pub fn buffered(
+    mut stream: Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>,
+    capacity: usize,
+) -> Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin> {
+    let (tx, rx) = tokio::sync::mpsc::channel(capacity.max(1));
+
+    tokio::spawn(async move {
+        while let Some(chunk) = stream.next().await {
+            if tx.send(chunk).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let stream = futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+    Box::new(Box::pin(stream))
+}
+
+/// The chunks a stream of `response` would have produced
+pub fn response_chunks(response: ChatResponse) -> Vec<Result<ChatChunk>> {
+    let mut chunks = vec![Ok(ChatChunk::MessageStart {
+        message: MessageMetadata {
+            id: String::new(),
+            role: crate::types::Role::Assistant,
+            model: response.model,
+            usage: None,
+            service_tier: response.service_tier,
+        },
+    })];
+
+    for (index, block) in response.content.into_iter().enumerate() {
+        match block {
+            ContentBlock::Text { text } => {
+                chunks.push(Ok(ChatChunk::ContentBlockStart {
+                    index,
+                    content_block: ContentBlock::Text {
+                        text: String::new(),
+                    },
+                }));
+                chunks.push(Ok(ChatChunk::ContentBlockDelta {
+                    index,
+                    delta: ContentDelta::TextDelta { text },
+                }));
+            }
+            ContentBlock::ToolUse { id, name, input } => {
+                chunks.push(Ok(ChatChunk::ContentBlockStart {
+                    index,
+                    content_block: ContentBlock::ToolUse {
+                        id,
+                        name,
+                        input: serde_json::json!({}),
+                    },
+                }));
+                chunks.push(Ok(ChatChunk::ContentBlockDelta {
+                    index,
+                    delta: ContentDelta::InputJsonDelta {
+                        partial_json: input.to_string(),
+                    },
+                }));
+            }
+            block => chunks.push(Ok(ChatChunk::ContentBlockStart {
+                index,
+                content_block: block,
+            })),
+        }
+        chunks.push(Ok(ChatChunk::ContentBlockStop { index }));
+    }
+
+    chunks.push(Ok(ChatChunk::MessageDelta {
+        delta: MessageDelta {
+            stop_reason: response.stop_reason,
+            usage: Some(response.usage),
+        },
+    }));
+    chunks.push(Ok(ChatChunk::MessageStop));
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn text_delta(text: &str) -> ChatChunk {
+        ChatChunk::ContentBlockDelta {
+            index: 0,
+            delta: ContentDelta::TextDelta {
+                text: text.to_string(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn buffered_stream_stops_reading_when_the_buffer_is_full() {
+        let read = Arc::new(AtomicUsize::new(0));
+        let counter = read.clone();
+        let source = futures::stream::iter(0..20).map(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(text_delta("x"))
+        });
+        let mut stream = buffered(Box::new(source), 4);
+
+        // A consumer that hasn't read yet: the buffer fills, plus the one
+        // chunk waiting to be sent
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(read.load(Ordering::SeqCst), 5);
+
+        // Each chunk taken makes room for one more
+        stream.next().await.unwrap().unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(read.load(Ordering::SeqCst), 6);
+
+        assert_eq!(stream.count().await, 19);
+        assert_eq!(read.load(Ordering::SeqCst), 20);
+    }
+
+    #[tokio::test]
+    async fn stream_over_the_size_limit_ends_with_an_error() {
+        let source =
+            futures::stream::iter(["Hello", ", ", "world"].map(|text| Ok(text_delta(text))));
+        let chunks: Vec<Result<ChatChunk>> =
+            limit_response_bytes(Box::new(source), 8).collect().await;
+
+        // "Hello" and ", " fit; "world" takes it to 12 bytes
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[1].is_ok());
+        assert_eq!(
+            chunks[2].as_ref().unwrap_err().to_string(),
+            "LLM provider error: response exceeded max size (8 bytes, see llm.max_response_bytes)"
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use crate::config::PromptTemplate;
+use crate::types::{ContentBlock, Message, Role};
+
+/// Render a conversation into a single prompt string for models that take
+/// raw text rather than a message list
+///
+/// The rendered prompt always ends with an open assistant turn so the model
+/// continues as the assistant.
+pub fn render(template: PromptTemplate, messages: &[Message]) -> String {
+    let turns: Vec<(Role, String)> = messages
+        .iter()
+        .map(|m| (m.role.clone(), message_text(m)))
+        .collect();
+
+    match template {
+        PromptTemplate::Raw => render_raw(&turns),
+        PromptTemplate::ChatMl => render_chatml(&turns),
+        PromptTemplate::Llama => render_llama(&turns),
+        PromptTemplate::Alpaca => render_alpaca(&turns),
+    }
+}
+
+/// Join all text blocks of a message
+pub fn message_text(message: &Message) -> String {
+    message
+        .content
+        .iter()
+        .filter_map(|c| match c {
+            ContentBlock::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Role-labeled plain text: `User: ...` / `Assistant: ...`
+fn render_raw(turns: &[(Role, String)]) -> String {
+    let mut prompt = turns
+        .iter()
+        .map(|(role, text)| {
+            let label = match role {
+                Role::System => "System",
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+            };
+            format!("{}: {}", label, text)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    if !prompt.is_empty() {
+        prompt.push_str("\n\n");
+    }
+    prompt.push_str("Assistant:");
+    prompt
+}
+
+/// ChatML: `<|im_start|>role\n...<|im_end|>`
+fn render_chatml(turns: &[(Role, String)]) -> String {
+    let mut prompt = String::new();
+    for (role, text) in turns {
+        let role = match role {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        };
+        prompt.push_str(&format!("<|im_start|>{}\n{}<|im_end|>\n", role, text));
+    }
+    prompt.push_str("<|im_start|>assistant\n");
+    prompt
+}
+
+/// Llama 2 chat: `<s>[INST] <<SYS>>...<</SYS>> user [/INST] assistant </s>`
+fn render_llama(turns: &[(Role, String)]) -> String {
+    let system: Vec<&str> = turns
+        .iter()
+        .filter(|(role, _)| *role == Role::System)
+        .map(|(_, text)| text.as_str())
+        .collect();
+    let mut system = if system.is_empty() {
+        None
+    } else {
+        Some(system.join("\n"))
+    };
+
+    let mut prompt = String::new();
+    let mut open_inst = false;
+
+    for (role, text) in turns.iter().filter(|(role, _)| *role != Role::System) {
+        match role {
+            Role::User => {
+                prompt.push_str("<s>[INST] ");
+                if let Some(sys) = system.take() {
+                    prompt.push_str(&format!("<<SYS>>\n{}\n<</SYS>>\n\n", sys));
+                }
+                prompt.push_str(&format!("{} [/INST]", text));
+                open_inst = true;
+            }
+            Role::Assistant => {
+                prompt.push_str(&format!(" {} </s>", text));
+                open_inst = false;
+            }
+            Role::System => {}
+        }
+    }
+
+    // Ensure the prompt ends awaiting an assistant reply
+    if !open_inst {
+        prompt.push_str("<s>[INST] ");
+        if let Some(sys) = system.take() {
+            prompt.push_str(&format!("<<SYS>>\n{}\n<</SYS>>\n\n", sys));
+        }
+        prompt.push_str(" [/INST]");
+    }
+
+    prompt
+}
+
+/// Alpaca: `### Instruction:` / `### Response:` sections
+fn render_alpaca(turns: &[(Role, String)]) -> String {
+    let mut prompt = String::new();
+
+    for (role, text) in turns {
+        match role {
+            Role::System => prompt.push_str(&format!("{}\n\n", text)),
+            Role::User => prompt.push_str(&format!("### Instruction:\n{}\n\n", text)),
+            Role::Assistant => prompt.push_str(&format!("### Response:\n{}\n\n", text)),
+        }
+    }
+
+    prompt.push_str("### Response:\n");
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conversation() -> Vec<Message> {
+        vec![
+            Message::new_system("Be brief".to_string()),
+            Message::new_user("Hi".to_string()),
+            Message::new_assistant(vec![ContentBlock::Text {
+                text: "Hello".to_string(),
+            }]),
+            Message::new_user("Bye".to_string()),
+        ]
+    }
+
+    #[test]
+    fn raw_labels_each_turn() {
+        assert_eq!(
+            render(PromptTemplate::Raw, &conversation()),
+            "System: Be brief\n\nUser: Hi\n\nAssistant: Hello\n\nUser: Bye\n\nAssistant:"
+        );
+    }
+
+    #[test]
+    fn chatml_wraps_each_turn() {
+        assert_eq!(
+            render(PromptTemplate::ChatMl, &conversation()),
+            "<|im_start|>system\nBe brief<|im_end|>\n\
+             <|im_start|>user\nHi<|im_end|>\n\
+             <|im_start|>assistant\nHello<|im_end|>\n\
+             <|im_start|>user\nBye<|im_end|>\n\
+             <|im_start|>assistant\n"
+        );
+    }
+
+    #[test]
+    fn llama_puts_the_system_prompt_in_the_first_instruction() {
+        assert_eq!(
+            render(PromptTemplate::Llama, &conversation()),
+            "<s>[INST] <<SYS>>\nBe brief\n<</SYS>>\n\nHi [/INST] Hello </s><s>[INST] Bye [/INST]"
+        );
+    }
+
+    #[test]
+    fn alpaca_alternates_instructions_and_responses() {
+        assert_eq!(
+            render(PromptTemplate::Alpaca, &conversation()),
+            "Be brief\n\n### Instruction:\nHi\n\n### Response:\nHello\n\n\
+             ### Instruction:\nBye\n\n### Response:\n"
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Token counting for context budgeting and cost estimation
+//!
+//! With the `tokenizer` feature, models with a known BPE encoding are counted
+//! with `tiktoken-rs`. Claude models use `cl100k_base` as a close
+//! approximation (Anthropic doesn't publish its tokenizer). Everything else
+//! falls back to the chars/4 heuristic.
+
+/// Average characters per token used by the fallback heuristic
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Count tokens in `text` for `model`, falling back to a heuristic for
+/// models without a known encoding
+pub fn count_tokens(model: &str, text: &str) -> usize {
+    #[cfg(feature = "tokenizer")]
+    if let Some(count) = encoded_len(model, text) {
+        return count;
+    }
+
+    #[cfg(not(feature = "tokenizer"))]
+    let _ = model;
+
+    heuristic_tokens(text)
+}
+
+/// Rough token estimate: one token per four characters, rounded up
+pub fn heuristic_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Whether `count_tokens` uses a real encoding for `model`
+pub fn has_exact_encoding(model: &str) -> bool {
+    #[cfg(feature = "tokenizer")]
+    return tokenizer_for_model(model).is_some();
+
+    #[cfg(not(feature = "tokenizer"))]
+    {
+        let _ = model;
+        false
+    }
+}
+
+#[cfg(feature = "tokenizer")]
+fn tokenizer_for_model(model: &str) -> Option<tiktoken_rs::tokenizer::Tokenizer> {
+    if model.starts_with("claude") {
+        return Some(tiktoken_rs::tokenizer::Tokenizer::Cl100kBase);
+    }
+    tiktoken_rs::tokenizer::get_tokenizer(model)
+}
+
+#[cfg(feature = "tokenizer")]
+fn encoded_len(model: &str, text: &str) -> Option<usize> {
+    use tiktoken_rs::tokenizer::Tokenizer;
+
+    // Singletons so each encoding is only loaded once per process
+    let bpe = match tokenizer_for_model(model)? {
+        Tokenizer::O200kBase => tiktoken_rs::o200k_base_singleton(),
+        Tokenizer::Cl100kBase => tiktoken_rs::cl100k_base_singleton(),
+        Tokenizer::P50kBase => tiktoken_rs::p50k_base_singleton(),
+        Tokenizer::P50kEdit => tiktoken_rs::p50k_edit_singleton(),
+        Tokenizer::R50kBase | Tokenizer::Gpt2 => tiktoken_rs::r50k_base_singleton(),
+    };
+
+    let len = bpe.lock().encode_with_special_tokens(text).len();
+    Some(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heuristic_rounds_up_by_chars() {
+        assert_eq!(heuristic_tokens(""), 0);
+        assert_eq!(heuristic_tokens("abcd"), 1);
+        assert_eq!(heuristic_tokens("abcde"), 2);
+        // Characters, not bytes
+        assert_eq!(heuristic_tokens("éééé"), 1);
+    }
+
+    #[test]
+    fn unknown_models_use_the_heuristic() {
+        assert!(!has_exact_encoding("llama3.1"));
+        assert_eq!(count_tokens("llama3.1", "Hello, world!"), 4);
+    }
+
+    #[cfg(feature = "tokenizer")]
+    #[test]
+    fn known_models_match_their_encoding() {
+        assert!(has_exact_encoding("gpt-4o"));
+        assert!(has_exact_encoding("claude-sonnet-4"));
+
+        assert_eq!(count_tokens("gpt-4o", "Hello, world!"), 4);
+        assert_eq!(count_tokens("gpt-4", "hello world"), 2);
+        assert_eq!(count_tokens("claude-sonnet-4", "hello world"), 2);
+        assert_eq!(
+            count_tokens("gpt-4", "The quick brown fox jumps over the lazy dog."),
+            10
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use super::tools::tool_description;
+use super::types::*;
+use super::LlmProvider;
+use crate::error::Result;
+use crate::types::{ContentBlock, Message, Role, Tool, ToolResultContent};
+use async_trait::async_trait;
+use futures::Stream;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{debug, warn};
+
+const CALL_OPEN: &str = "<tool_call>";
+const CALL_CLOSE: &str = "</tool_call>";
+
+/// Gives tools to a provider without native tool use by describing them in
+/// the system prompt and parsing `<tool_call>` JSON out of the reply
+///
+/// Prior tool calls and results in the history are rendered as text in the
+/// same convention, so the model sees what it asked for and what came back.
+/// Requests without tools pass through unchanged.
+pub struct ToolPromptingProvider {
+    inner: Box<dyn LlmProvider>,
+    max_description_chars: Option<usize>,
+}
+
+impl ToolPromptingProvider {
+    /// Tool descriptions in the prompt are cut to `max_description_chars`
+    /// (`llm.max_tool_description_chars`)
+    pub fn new(inner: Box<dyn LlmProvider>, max_description_chars: Option<usize>) -> Self {
+        Self {
+            inner,
+            max_description_chars,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for ToolPromptingProvider {
+    async fn chat(&self, messages: Vec<Message>, tools: Vec<Tool>) -> Result<ChatResponse> {
+        if tools.is_empty() {
+            return self.inner.chat(messages, tools).await;
+        }
+
+        debug!("Describing {} tools in the prompt for {}", tools.len(), self.inner.name());
+        let messages = prompt_messages(messages, &tools, self.max_description_chars);
+        let mut response = self.inner.chat(messages, Vec::new()).await?;
+
+        response.content = parse_tool_calls(response.content);
+        if response.content.iter().any(|c| matches!(c, ContentBlock::ToolUse { .. })) {
+            response.stop_reason = Some(StopReason::ToolUse);
+        }
+        Ok(response)
+    }
+
+    async fn stream_chat(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+    ) -> Result<Box<dyn Stream<Item = Result<ChatChunk>> + Send + Unpin>> {
+        if tools.is_empty() {
+            return self.inner.stream_chat(messages, tools).await;
+        }
+
+        // Tool calls can't be recognized until the reply is complete, so
+        // send the whole response as one burst of chunks
+        let response = self.chat(messages, tools).await?;
+        Ok(Box::new(futures::stream::iter(super::stream::response_chunks(response))))
+    }
+
+    fn request_body(
+        &self,
+        messages: Vec<Message>,
+        tools: Vec<Tool>,
+        stream: bool,
+    ) -> Result<Option<serde_json::Value>> {
+        if tools.is_empty() {
+            return self.inner.request_body(messages, tools, stream);
+        }
+        let messages = prompt_messages(messages, &tools, self.max_description_chars);
+        self.inner.request_body(messages, Vec::new(), stream)
+    }
+
+    fn supports_tools(&self) -> bool {
+        true
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    fn supports_prefill(&self) -> bool {
+        self.inner.supports_prefill()
+    }
+
+    fn estimate_tokens(&self, text: &str) -> usize {
+        self.inner.estimate_tokens(text)
+    }
+
+    fn max_tokens(&self) -> usize {
+        self.inner.max_tokens()
+    }
+
+    fn model_max_output_tokens(&self) -> Option<usize> {
+        self.inner.model_max_output_tokens()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            tools: true,
+            ..self.inner.capabilities()
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+}
+
+/// System prompt text describing `tools` and how to call them
+fn tools_prompt(tools: &[Tool], max_description_chars: Option<usize>) -> String {
+    let mut prompt = String::from(
+        "You can call the following tools. To call one, reply with a block of the form\n\
+         <tool_call>\n{\"name\": \"<tool name>\", \"arguments\": {<arguments>}}\n</tool_call>\n\
+         and nothing after it. You may emit several blocks to call several tools. \
+         The results will be sent back to you in the next message.\n\nTools:\n",
+    );
+
+    for tool in tools {
+        prompt.push_str(&format!(
+            "- {}: {}\n  Arguments schema: {}\n",
+            tool.name,
+            tool_description(tool, max_description_chars),
+            tool.input_schema
+        ));
+    }
+
+    prompt
+}
+
+/// Add the tools description to the system prompt and render tool calls
+/// and results in the history as text
+fn prompt_messages(
+    messages: Vec<Message>,
+    tools: &[Tool],
+    max_description_chars: Option<usize>,
+) -> Vec<Message> {
+    let description = tools_prompt(tools, max_description_chars);
+    let mut has_system = false;
+
+    let mut messages: Vec<Message> = messages
+        .into_iter()
+        .map(|mut message| {
+            if message.role == Role::System && !has_system {
+                has_system = true;
+                message.content.push(ContentBlock::Text {
+                    text: description.clone(),
+                });
+            }
+            message.content = message.content.into_iter().map(block_as_text).collect();
+            message
+        })
+        .collect();
+
+    if !has_system {
+        messages.insert(0, Message::new_system(description));
+    }
+    messages
+}
+
+fn block_as_text(block: ContentBlock) -> ContentBlock {
+    match block {
+        ContentBlock::ToolUse { name, input, .. } => ContentBlock::Text {
+            text: format!(
+                "{}\n{}\n{}",
+                CALL_OPEN,
+                serde_json::json!({ "name": name, "arguments": input }),
+                CALL_CLOSE
+            ),
+        },
+        ContentBlock::ToolResult {
+            content, is_error, ..
+        } => {
+            let output = content
+                .iter()
+                .map(|c| match c {
+                    ToolResultContent::Text { text } => text.clone(),
+                    ToolResultContent::Image { media_type, .. } => format!("[{} image]", media_type),
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let label = if is_error == Some(true) {
+                "Tool error"
+            } else {
+                "Tool result"
+            };
+            ContentBlock::Text {
+                text: format!("{}:\n{}", label, output),
+            }
+        }
+        block => block,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EmittedCall {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+/// Split `<tool_call>` blocks out of the text content into `ToolUse` blocks
+///
+/// A block whose JSON doesn't parse is left in the text, so the reply is
+/// still shown rather than lost.
+pub fn parse_tool_calls(content: Vec<ContentBlock>) -> Vec<ContentBlock> {
+    let mut parsed = Vec::new();
+
+    for block in content {
+        let ContentBlock::Text { text } = block else {
+            parsed.push(block);
+            continue;
+        };
+
+        let mut remaining = text.as_str();
+        let mut kept = String::new();
+        let mut calls = Vec::new();
+
+        while let Some(start) = remaining.find(CALL_OPEN) {
+            let after_open = &remaining[start + CALL_OPEN.len()..];
+            let Some(end) = after_open.find(CALL_CLOSE) else {
+                break;
+            };
+
+            match serde_json::from_str::<EmittedCall>(after_open[..end].trim()) {
+                Ok(call) => {
+                    kept.push_str(&remaining[..start]);
+                    calls.push(ContentBlock::ToolUse {
+                        id: format!("call_{}", uuid::Uuid::new_v4().simple()),
+                        name: call.name,
+                        input: if call.arguments.is_null() {
+                            serde_json::json!({})
+                        } else {
+                            call.arguments
+                        },
+                    });
+                }
+                Err(e) => {
+                    warn!("Ignoring malformed tool call in model output: {}", e);
+                    kept.push_str(&remaining[..start + CALL_OPEN.len() + end + CALL_CLOSE.len()]);
+                }
+            }
+            remaining = &after_open[end + CALL_CLOSE.len()..];
+        }
+        kept.push_str(remaining);
+
+        // Any surrounding text goes ahead of the calls it introduced
+        let kept = kept.trim();
+        if !kept.is_empty() {
+            parsed.push(ContentBlock::Text {
+                text: kept.to_string(),
+            });
+        }
+        parsed.extend(calls);
+    }
+
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Vec<ContentBlock> {
+        vec![ContentBlock::Text {
+            text: text.to_string(),
+        }]
+    }
+
+    #[test]
+    fn well_formed_call_becomes_a_tool_use() {
+        let parsed = parse_tool_calls(text(
+            "Let me check.\n<tool_call>{\"name\": \"fs::read_file\", \"arguments\": {\"path\": \"a.txt\"}}</tool_call>",
+        ));
+
+        assert_eq!(parsed.len(), 2);
+        assert!(matches!(&parsed[0], ContentBlock::Text { text } if text == "Let me check."));
+        let ContentBlock::ToolUse { name, input, .. } = &parsed[1] else {
+            panic!("expected a tool use, got {:?}", parsed[1]);
+        };
+        assert_eq!(name, "fs::read_file");
+        assert_eq!(input, &serde_json::json!({ "path": "a.txt" }));
+    }
+
+    #[test]
+    fn malformed_call_is_kept_as_text() {
+        let output = "<tool_call>{\"name\": \"fs::read_file\", </tool_call>";
+        let parsed = parse_tool_calls(text(output));
+
+        assert_eq!(parsed.len(), 1);
+        assert!(matches!(&parsed[0], ContentBlock::Text { text } if text == output));
+    }
+
+    #[test]
+    fn unclosed_call_is_kept_as_text() {
+        let output = "<tool_call>{\"name\": \"fs::read_file\"}";
+        let parsed = parse_tool_calls(text(output));
+
+        assert!(matches!(&parsed[..], [ContentBlock::Text { text }] if text == output));
+    }
+}
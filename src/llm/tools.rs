@@ -0,0 +1,241 @@
+use crate::config::ToolChoice;
+use crate::error::{OllmError, Result};
+use crate::types::{ContentBlock, Tool};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Wire format a provider uses for tool definitions and tool calls
+///
+/// Providers convert through these helpers so the rest of the crate only
+/// deals in `Tool` and `ContentBlock::ToolUse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolFormat {
+    /// `tools: [{name, description, input_schema}]`, calls as `tool_use`
+    /// content blocks
+    Anthropic,
+    /// `tools: [{type: "function", function: {...}}]`, calls as
+    /// `tool_calls` with JSON-string arguments
+    OpenAi,
+    /// OpenAI-style definitions, calls as `tool_calls` with object
+    /// arguments and no ids
+    Ollama,
+}
+
+/// Tool definitions in the provider's request format, with descriptions
+/// cut to `max_description_chars` (`llm.max_tool_description_chars`)
+pub fn to_provider_tools(
+    format: ToolFormat,
+    tools: &[Tool],
+    max_description_chars: Option<usize>,
+) -> Vec<Value> {
+    tools
+        .iter()
+        .map(|tool| {
+            let description = tool_description(tool, max_description_chars);
+            match format {
+                ToolFormat::Anthropic => json!({
+                    "name": tool.name,
+                    "description": description,
+                    "input_schema": tool.input_schema,
+                }),
+                ToolFormat::OpenAi | ToolFormat::Ollama => json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": description,
+                        "parameters": tool.input_schema,
+                    },
+                }),
+            }
+        })
+        .collect()
+}
+
+/// `tool`'s description as sent to the model, cut to `max_chars` if set
+pub(crate) fn tool_description(tool: &Tool, max_chars: Option<usize>) -> String {
+    match max_chars {
+        Some(max_chars) => crate::tools::truncate_end(&tool.description, max_chars),
+        None => tool.description.clone(),
+    }
+}
+
+/// `tool_choice` in the provider's request format, or `None` when the
+/// provider has no equivalent (Ollama)
+pub fn to_provider_tool_choice(format: ToolFormat, choice: &ToolChoice) -> Option<Value> {
+    match format {
+        ToolFormat::Anthropic => Some(match choice {
+            ToolChoice::Auto => json!({ "type": "auto" }),
+            ToolChoice::Any => json!({ "type": "any" }),
+            ToolChoice::None => json!({ "type": "none" }),
+            ToolChoice::Tool(name) => json!({ "type": "tool", "name": name }),
+        }),
+        ToolFormat::OpenAi => Some(match choice {
+            ToolChoice::Auto => json!("auto"),
+            ToolChoice::Any => json!("required"),
+            ToolChoice::None => json!("none"),
+            ToolChoice::Tool(name) => json!({ "type": "function", "function": { "name": name } }),
+        }),
+        ToolFormat::Ollama => None,
+    }
+}
+
+/// The `ToolUse` blocks among `content`, as the provider's tool calls
+///
+/// Anthropic calls are `tool_use` content blocks; the others are entries
+/// for an assistant message's `tool_calls`.
+pub fn to_provider_tool_calls(format: ToolFormat, content: &[ContentBlock]) -> Vec<Value> {
+    content
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::ToolUse { id, name, input } => Some((id, name, input)),
+            _ => None,
+        })
+        .map(|(id, name, input)| match format {
+            ToolFormat::Anthropic => json!({
+                "type": "tool_use",
+                "id": id,
+                "name": name,
+                "input": input,
+            }),
+            ToolFormat::OpenAi => json!({
+                "id": id,
+                "type": "function",
+                "function": { "name": name, "arguments": input.to_string() },
+            }),
+            ToolFormat::Ollama => json!({
+                "function": { "name": name, "arguments": input },
+            }),
+        })
+        .collect()
+}
+
+/// Parse provider tool calls into `ContentBlock::ToolUse`s
+///
+/// `calls` is an array of Anthropic content blocks (non-`tool_use` blocks
+/// are skipped) or of OpenAI/Ollama `tool_calls` entries. Calls without an
+/// id get a generated one.
+pub fn from_provider_tool_calls(format: ToolFormat, calls: &Value) -> Result<Vec<ContentBlock>> {
+    let Some(calls) = calls.as_array() else {
+        return Ok(Vec::new());
+    };
+
+    let mut blocks = Vec::with_capacity(calls.len());
+    for call in calls {
+        let (id, name, input) = match format {
+            ToolFormat::Anthropic => {
+                if call["type"] != "tool_use" {
+                    continue;
+                }
+                (call["id"].as_str(), call["name"].as_str(), call["input"].clone())
+            }
+            ToolFormat::OpenAi | ToolFormat::Ollama => {
+                let function = &call["function"];
+                let input = match &function["arguments"] {
+                    // OpenAI sends arguments as a JSON-encoded string
+                    Value::String(arguments) if arguments.trim().is_empty() => json!({}),
+                    Value::String(arguments) => serde_json::from_str(arguments).map_err(|e| {
+                        OllmError::LlmProvider(format!("Invalid tool call arguments: {}", e))
+                    })?,
+                    Value::Null => json!({}),
+                    arguments => arguments.clone(),
+                };
+                (call["id"].as_str(), function["name"].as_str(), input)
+            }
+        };
+
+        let name = name.ok_or_else(|| {
+            OllmError::LlmProvider(format!("Tool call without a name: {}", call))
+        })?;
+
+        blocks.push(ContentBlock::ToolUse {
+            id: id
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("call_{}", Uuid::new_v4().simple())),
+            name: name.to_string(),
+            input,
+        });
+    }
+
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openai_tool_choice_variants_serialize() {
+        let choice = |choice| to_provider_tool_choice(ToolFormat::OpenAi, &choice).unwrap();
+
+        assert_eq!(choice(ToolChoice::Auto), json!("auto"));
+        assert_eq!(choice(ToolChoice::Any), json!("required"));
+        assert_eq!(choice(ToolChoice::None), json!("none"));
+        assert_eq!(
+            choice(ToolChoice::Tool("search".to_string())),
+            json!({ "type": "function", "function": { "name": "search" } })
+        );
+        assert!(to_provider_tool_choice(ToolFormat::Ollama, &ToolChoice::Any).is_none());
+    }
+
+    #[test]
+    fn tool_calls_round_trip_through_each_format() {
+        let content = vec![
+            ContentBlock::Text {
+                text: "Let me look".to_string(),
+            },
+            ContentBlock::ToolUse {
+                id: "call_1".to_string(),
+                name: "fs::read_file".to_string(),
+                input: json!({ "path": "Cargo.toml" }),
+            },
+            ContentBlock::ToolUse {
+                id: "call_2".to_string(),
+                name: "fs::list_dir".to_string(),
+                input: json!({}),
+            },
+        ];
+
+        for format in [ToolFormat::Anthropic, ToolFormat::OpenAi, ToolFormat::Ollama] {
+            let calls = Value::Array(to_provider_tool_calls(format, &content));
+            let parsed = from_provider_tool_calls(format, &calls).unwrap();
+
+            assert_eq!(parsed.len(), 2, "{:?}", format);
+            for (parsed, original) in parsed.iter().zip(&content[1..]) {
+                let (
+                    ContentBlock::ToolUse { id, name, input },
+                    ContentBlock::ToolUse {
+                        id: original_id,
+                        name: original_name,
+                        input: original_input,
+                    },
+                ) = (parsed, original)
+                else {
+                    panic!("expected tool uses, got {:?}", parsed);
+                };
+
+                assert_eq!(name, original_name, "{:?}", format);
+                assert_eq!(input, original_input, "{:?}", format);
+                // Ollama tool calls carry no id, so one is generated
+                if format == ToolFormat::Ollama {
+                    assert!(id.starts_with("call_") && id != original_id);
+                } else {
+                    assert_eq!(id, original_id, "{:?}", format);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn openai_arguments_must_be_json() {
+        let calls = json!([{
+            "id": "call_1",
+            "type": "function",
+            "function": { "name": "search", "arguments": "{not json" },
+        }]);
+        assert!(from_provider_tool_calls(ToolFormat::OpenAi, &calls).is_err());
+
+        let calls = json!([{ "function": { "name": "search", "arguments": "" } }]);
+        let parsed = from_provider_tool_calls(ToolFormat::OpenAi, &calls).unwrap();
+        assert!(matches!(&parsed[0], ContentBlock::ToolUse { input, .. } if *input == json!({})));
+    }
+}
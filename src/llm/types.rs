@@ -15,6 +15,42 @@ pub struct ChatResponse {
 
     /// Token usage information
     pub usage: TokenUsage,
+
+    /// Service tier that handled the request, when the provider reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
+}
+
+impl ChatResponse {
+    /// All text blocks, joined with newlines
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The tool-use blocks, in order
+    pub fn tool_uses(&self) -> Vec<&ContentBlock> {
+        self.content
+            .iter()
+            .filter(|block| matches!(block, ContentBlock::ToolUse { .. }))
+            .collect()
+    }
+
+    /// Whether the response has no tool calls and only empty or
+    /// whitespace-only text
+    pub fn is_blank(&self) -> bool {
+        self.content.iter().all(|block| match block {
+            ContentBlock::Text { text } => text.trim().is_empty(),
+            ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => true,
+            _ => false,
+        })
+    }
 }
 
 /// Reason why generation stopped
@@ -31,8 +67,39 @@ pub enum StopReason {
     ToolUse,
 }
 
+/// What a provider and its model support, from `LlmProvider::capabilities`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProviderCapabilities {
+    /// Native tool use
+    pub tools: bool,
+    /// Real streaming via `stream_chat`
+    pub streaming: bool,
+    /// Image input
+    pub vision: bool,
+    /// Configured max tokens per response
+    pub max_tokens: usize,
+    /// Most tokens the model can produce in one response, if known
+    pub max_output_tokens: Option<usize>,
+    /// Input plus output tokens the model can handle, if known
+    pub context_window: Option<usize>,
+}
+
+impl ProviderCapabilities {
+    /// Capabilities as reported by the provider's individual methods
+    pub fn of<P: super::LlmProvider + ?Sized>(provider: &P) -> Self {
+        Self {
+            tools: provider.supports_tools(),
+            streaming: provider.supports_streaming(),
+            vision: false,
+            max_tokens: provider.max_tokens(),
+            max_output_tokens: provider.model_max_output_tokens(),
+            context_window: super::models::context_window(provider.model()),
+        }
+    }
+}
+
 /// Token usage statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TokenUsage {
     pub input_tokens: usize,
     pub output_tokens: usize,
@@ -83,12 +150,23 @@ pub enum ChatChunk {
     },
 }
 
+/// Event from `stream_chat_events`: a raw chunk or a running usage update
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    /// Chunk as produced by `stream_chat`
+    Chunk(ChatChunk),
+    /// Output tokens generated so far (exact or estimated, per provider)
+    UsageUpdate { output_tokens_so_far: usize },
+}
+
 /// Content delta in a stream
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ContentDelta {
     TextDelta { text: String },
     InputJsonDelta { partial_json: String },
+    ThinkingDelta { thinking: String },
+    SignatureDelta { signature: String },
 }
 
 /// Message metadata from stream
@@ -97,6 +175,12 @@ pub struct MessageMetadata {
     pub id: String,
     pub role: Role,
     pub model: String,
+    /// Usage known at message start (e.g. input tokens)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    /// Service tier handling the request, when the provider reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
 }
 
 /// Message delta from stream
@@ -105,3 +189,62 @@ pub struct MessageDelta {
     pub stop_reason: Option<StopReason>,
     pub usage: Option<TokenUsage>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tool_use(id: &str, name: &str) -> ContentBlock {
+        ContentBlock::ToolUse {
+            id: id.to_string(),
+            name: name.to_string(),
+            input: json!({}),
+        }
+    }
+
+    fn text(text: &str) -> ContentBlock {
+        ContentBlock::Text {
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn text_and_tool_uses_pick_their_blocks_in_order() {
+        let response = ChatResponse {
+            content: vec![
+                ContentBlock::Thinking {
+                    thinking: "Two files to read".to_string(),
+                    signature: String::new(),
+                },
+                text("Reading both."),
+                tool_use("call_1", "files::read"),
+                text("Then the other."),
+                tool_use("call_2", "files::stat"),
+            ],
+            model: "m".to_string(),
+            stop_reason: Some(StopReason::ToolUse),
+            usage: TokenUsage::default(),
+            service_tier: None,
+        };
+
+        assert_eq!(response.text(), "Reading both.\nThen the other.");
+        let ids: Vec<&str> = response
+            .tool_uses()
+            .into_iter()
+            .map(|block| match block {
+                ContentBlock::ToolUse { id, .. } => id.as_str(),
+                other => panic!("not a tool use: {:?}", other),
+            })
+            .collect();
+        assert_eq!(ids, ["call_1", "call_2"]);
+        assert!(!response.is_blank());
+
+        let tools_only = ChatResponse {
+            content: vec![tool_use("call_1", "files::read")],
+            ..response.clone()
+        };
+        assert_eq!(tools_only.text(), "");
+        assert_eq!(tools_only.tool_uses().len(), 1);
+    }
+}
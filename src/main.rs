@@ -1,12 +1,39 @@
-mod config;
-mod error;
-mod llm;
-mod mcp;
-mod types;
-
-use clap::{Parser, Subcommand};
-use error::Result;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use colored::Colorize;
+use futures::StreamExt;
+use open_llm_code::error::{self, Result};
+use open_llm_code::session::{self, SessionStore};
+use open_llm_code::ConversationBuffer;
+use open_llm_code::{agent, config, diagnose, format, llm, mcp, retry, timing, tools, types, ui};
+use std::io::Write;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Set by `--quiet` or `--stdout-only`; suppresses `status!` lines
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--stdout-only`; also suppresses `detail!` lines
+static STDOUT_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Print a decorative status line to stderr (suppressed by `--quiet`)
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Print supplementary output (thinking, timing, tool calls) to stderr,
+/// keeping stdout for the answer (suppressed by `--stdout-only`)
+macro_rules! detail {
+    ($($arg:tt)*) => {
+        if !STDOUT_ONLY.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        }
+    };
+}
 
 #[derive(Parser)]
 #[command(name = "ollm")]
@@ -16,146 +43,1585 @@ struct Cli {
     #[arg(short, long)]
     config: Option<PathBuf>,
 
-    /// Enable verbose logging
+    /// Load environment variables (e.g. API keys) from this dotenv file;
+    /// `.env` in the current directory is loaded when present. Variables
+    /// already set in the environment win.
+    #[arg(long, value_name = "PATH")]
+    env_file: Option<PathBuf>,
+
+    /// Enable verbose logging (alias for --log-level debug)
     #[arg(short, long)]
     verbose: bool,
 
+    /// Log level: error, warn, info, debug, or trace (trace includes HTTP internals)
+    #[arg(long, value_enum, value_name = "LEVEL", conflicts_with = "verbose")]
+    log_level: Option<LogLevel>,
+
+    /// Suppress decorative status lines, printing only command output
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Print only the model's answer: no status, thinking, tool call, or
+    /// timing output (errors and logs still go to stderr)
+    #[arg(long)]
+    stdout_only: bool,
+
+    /// Disable colored output (also honors the NO_COLOR env var)
+    #[arg(long)]
+    no_color: bool,
+
+    #[command(flatten)]
+    overrides: ConfigOverrides,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Cli {
+    fn log_level(&self) -> LogLevel {
+        match self.log_level {
+            Some(level) => level,
+            None if self.verbose => LogLevel::Debug,
+            None => LogLevel::Info,
+        }
+    }
+}
+
+impl LogLevel {
+    /// `tracing_subscriber` env filter directives for this level
+    fn env_filter(self) -> String {
+        match self {
+            LogLevel::Error => "open_llm_code=error".to_string(),
+            LogLevel::Warn => "open_llm_code=warn".to_string(),
+            LogLevel::Info => "open_llm_code=info".to_string(),
+            LogLevel::Debug => "open_llm_code=debug".to_string(),
+            LogLevel::Trace => "open_llm_code=trace,reqwest=trace,hyper=trace".to_string(),
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Start interactive REPL
-    Repl,
+    Repl {
+        #[command(flatten)]
+        chat: ChatArgs,
+    },
 
     /// Generate example configuration file
     Init {
         /// Output path for config file
         #[arg(short, long)]
         output: Option<PathBuf>,
+
+        /// Ask for the provider, model, API key variable, and MCP servers
+        /// instead of writing the example config
+        #[arg(short, long)]
+        interactive: bool,
+
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Print the config file path (`--config` or the default) and whether
+    /// it exists
+    ConfigPath,
+
+    /// Print the JSON Schema for the config file
+    Schema {
+        /// Write the schema to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 
     /// Test LLM provider with a simple message
     Test {
-        /// Message to send
-        message: String,
+        /// Message to send (shorthand for a single final --user turn)
+        message: Option<String>,
+
+        /// User turn; repeat with --assistant to script a multi-turn conversation
+        #[arg(long, value_name = "TEXT")]
+        user: Vec<String>,
+
+        /// Assistant turn, placed in command-line order relative to --user
+        #[arg(long, value_name = "TEXT")]
+        assistant: Vec<String>,
+
+        /// Start of the assistant's reply for the model to continue (e.g. "{"
+        /// to force JSON); only Anthropic supports this
+        #[arg(long, value_name = "TEXT")]
+        prefill: Option<String>,
+
+        /// Print the full response (content, usage, stop reason) as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Append the response (each run's, with --count) to this file
+        /// instead of printing it; text is written as it streams
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Send the request this many times and print latency and throughput
+        /// stats (per-run output only with --verbose)
+        #[arg(long, value_name = "N", default_value_t = 1,
+              value_parser = clap::value_parser!(u32).range(1..))]
+        count: u32,
+
+        #[command(flatten)]
+        chat: ChatArgs,
+    },
+
+    /// Run the agent with tools on a prompt and print the final answer
+    Run {
+        /// Task for the agent
+        prompt: String,
+
+        /// Maximum model calls before giving up
+        #[arg(long, default_value_t = agent::DEFAULT_MAX_ITERATIONS)]
+        max_iterations: usize,
+
+        /// Print the answer, iterations, and usage as JSON
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        chat: ChatArgs,
     },
 
     /// List tools from MCP servers
-    ListTools,
+    ListTools {
+        /// Print each tool's full input JSON schema instead of an argument
+        /// summary
+        #[arg(long)]
+        schema: bool,
+    },
+
+    /// Embed text with the `[embeddings]` model and print the vector size
+    Embed {
+        /// Text to embed (each argument is embedded separately)
+        #[arg(required = true)]
+        text: Vec<String>,
+
+        /// Print the vectors as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Manage saved sessions
+    Sessions {
+        #[command(subcommand)]
+        command: SessionsCommand,
+    },
+
+    /// Manage the response cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+
+    /// Check config, credentials, and connectivity to providers and servers
+    Diagnose,
+
+    /// Validate the config file without network calls or starting MCP servers
+    ValidateConfig,
 
     /// Show version information
-    Version,
+    Version {
+        /// Print name, version, built-in providers, and compiled features as
+        /// JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Global overrides applied to the config right after it's loaded
+#[derive(Args, Clone, Copy)]
+struct ConfigOverrides {
+    /// Override the LLM request timeout (seconds)
+    #[arg(long, global = true, value_name = "SECS")]
+    timeout: Option<u64>,
+
+    /// Override the retry count for transient LLM failures
+    #[arg(long, global = true, value_name = "N")]
+    retries: Option<u32>,
+
+    /// Override how long to wait for an MCP server to answer a request
+    /// (seconds)
+    #[arg(long, global = true, value_name = "SECS")]
+    mcp_timeout: Option<u64>,
+
+    /// Don't start any MCP servers (chat without tools)
+    #[arg(long, global = true)]
+    no_mcp: bool,
+}
+
+impl ConfigOverrides {
+    /// Load the config and apply the overrides
+    fn load(&self, path: Option<PathBuf>) -> Result<config::Config> {
+        let mut config = config::Config::load(path)?;
+        if let Some(timeout) = self.timeout {
+            config.override_timeout(timeout)?;
+        }
+        if let Some(retries) = self.retries {
+            config.llm.max_retries = retries;
+        }
+        if let Some(timeout) = self.mcp_timeout {
+            config.override_mcp_timeout(timeout)?;
+        }
+        if self.no_mcp {
+            config.mcp_enabled = false;
+        }
+        for warning in config.warnings() {
+            status!("⚠️  {}", warning);
+        }
+        Ok(config)
+    }
+}
+
+/// Options shared by commands that talk to the LLM
+#[derive(Args, Default)]
+struct ChatArgs {
+    /// Override max tokens in response
+    #[arg(long)]
+    max_tokens: Option<usize>,
+
+    /// System prompt for this run (takes precedence over --preset and the
+    /// config system_prompt)
+    #[arg(long)]
+    system: Option<String>,
+
+    /// Use the system prompt from `[presets]` with this name
+    #[arg(long, value_name = "NAME")]
+    preset: Option<String>,
+
+    /// Don't start MCP servers or offer tools to the model
+    #[arg(long)]
+    no_tools: bool,
+
+    /// Tool choice: auto, any, none, or the name of a tool to force
+    #[arg(long, value_name = "CHOICE")]
+    tool_choice: Option<String>,
+
+    /// Override sampling temperature
+    #[arg(long)]
+    temperature: Option<f32>,
+
+    /// Sampling seed for reproducible output (where the provider supports it)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Print request timing (also shown at debug log level)
+    #[arg(long)]
+    timing: bool,
+
+    /// Print each request body sent to the provider to stderr (secrets
+    /// redacted)
+    #[arg(long)]
+    dump_request: bool,
+}
+
+impl ChatArgs {
+    /// Apply command-line overrides to the loaded config
+    fn apply(&self, config: &mut config::Config) -> Result<()> {
+        if let Some(max_tokens) = self.max_tokens {
+            config.override_max_tokens(max_tokens)?;
+        }
+        if let Some(name) = &self.preset {
+            let prompt = config.presets.get(name).cloned().ok_or_else(|| {
+                let mut known: Vec<&str> = config.presets.keys().map(String::as_str).collect();
+                known.sort_unstable();
+                if known.is_empty() {
+                    known.push("none");
+                }
+                error::OllmError::Config(format!(
+                    "Unknown preset '{}' (configured: {})",
+                    name,
+                    known.join(", ")
+                ))
+            })?;
+            config.llm.system_prompt = Some(prompt);
+        }
+        if let Some(system) = &self.system {
+            config.llm.system_prompt = Some(system.clone());
+        }
+        if self.no_tools {
+            config.disable_tools = true;
+        }
+        if let Some(choice) = &self.tool_choice {
+            config.llm.tool_choice = config::ToolChoice::from(choice.clone());
+        }
+        if let Some(temperature) = self.temperature {
+            config.llm.temperature = Some(temperature);
+        }
+        if let Some(seed) = self.seed {
+            config.llm.seed = Some(seed);
+        }
+        Ok(())
+    }
+
+    /// Create the configured provider, dumping requests for `--dump-request`
+    async fn create_provider(&self, config: &config::Config) -> Result<Box<dyn llm::LlmProvider>> {
+        llm::ProviderRegistry::new()
+            .dump_requests(self.dump_request)
+            .create(config)
+    }
+}
+
+/// `version --json` output: version, built-in providers, compiled features
+fn version_json() -> serde_json::Value {
+    let features: Vec<&str> = [
+        ("highlight", cfg!(feature = "highlight")),
+        ("opensearch", cfg!(feature = "opensearch")),
+        ("tokenizer", cfg!(feature = "tokenizer")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect();
+
+    serde_json::json!({
+        "name": "ollm",
+        "version": env!("CARGO_PKG_VERSION"),
+        "providers": llm::BUILTIN_PROVIDERS,
+        "features": features,
+    })
+}
+
+/// Load `path` (required to exist) or else `./.env` (if present) into the
+/// process environment, without overriding variables that are already set
+fn load_env_file(path: Option<&std::path::Path>) -> Result<()> {
+    match path {
+        Some(path) => {
+            dotenvy::from_path(path).map_err(|e| {
+                error::OllmError::Config(format!(
+                    "Failed to load env file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        }
+        None => match dotenvy::from_path(".env") {
+            Ok(_) => {}
+            Err(e) if e.not_found() => {}
+            Err(e) => eprintln!("⚠️  Ignoring .env: {}", e),
+        },
+    }
+    Ok(())
+}
+
+/// Parse an age like `30d` or `12h` for `sessions prune --older-than`
+fn parse_age(value: &str) -> std::result::Result<chrono::Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("missing unit in '{}' (use s, m, h, d, or w)", value))?;
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| format!("expected a number before the unit in '{}'", value))?;
+
+    let age = match unit {
+        "s" => chrono::Duration::try_seconds(amount),
+        "m" => chrono::Duration::try_minutes(amount),
+        "h" => chrono::Duration::try_hours(amount),
+        "d" => chrono::Duration::try_days(amount),
+        "w" => chrono::Duration::try_weeks(amount),
+        _ => return Err(format!("unknown unit '{}' (use s, m, h, d, or w)", unit)),
+    };
+    age.ok_or_else(|| format!("'{}' is too large", value))
+}
+
+/// The start of a conversation: the system prompt, if one is set
+fn system_messages(config: &config::Config) -> Vec<types::Message> {
+    config
+        .llm
+        .system_prompt
+        .iter()
+        .map(|system| types::Message::new_system(system.clone()))
+        .collect()
+}
+
+/// An MCP manager that is disabled when `mcp_enabled` is off
+fn mcp_manager(config: &config::Config) -> mcp::McpManager {
+    if config.mcp_enabled {
+        mcp::McpManager::new()
+    } else {
+        mcp::McpManager::disabled()
+    }
+}
+
+/// Start MCP servers and collect their tools, unless tools are disabled
+async fn start_tools(config: &config::Config) -> Result<(mcp::McpManager, Vec<types::Tool>)> {
+    let mut mcp_manager = mcp_manager(config);
+
+    if config.disable_tools {
+        return Ok((mcp_manager, Vec::new()));
+    }
+
+    let summary = mcp_manager
+        .start_servers(
+            config.mcp_servers.clone(),
+            config.mcp_fail_fast,
+            Duration::from_secs(config.mcp_request_timeout_secs),
+            config.mcp_startup_concurrency,
+        )
+        .await?;
+    for (name, error) in &summary.failed {
+        eprintln!("❌ MCP server '{}' failed: {}", name, error);
+    }
+
+    let tools = mcp_manager.get_all_tools();
+    Ok((mcp_manager, tools))
+}
+
+/// Render agent progress as indented `detail!` lines on stderr
+fn print_agent_event(event: &agent::AgentEvent) {
+    match event {
+        agent::AgentEvent::ModelTurn { iteration } if *iteration > 1 => {
+            detail!("  {}", format!("↻ model turn {}", iteration).dimmed());
+        }
+        // The renderer shows tool activity, and the caller the answer and
+        // usage
+        _ => {}
+    }
+}
+
+/// Renderer for agent output: streamed text on stdout, and on stderr the
+/// model's thinking plus tool activity when `trace` is set (neither with
+/// `--stdout-only`)
+fn terminal_renderer(trace: bool) -> ui::TerminalRenderer {
+    let details = !STDOUT_ONLY.load(Ordering::Relaxed);
+    ui::TerminalRenderer::new()
+        .show_tools(trace && details)
+        .show_thinking(details)
+}
+
+/// Print a `Test` response with its tool calls and token usage; the text
+/// itself is left out when it went to `--output`
+fn print_test_response(
+    response: &llm::ChatResponse,
+    json: bool,
+    prefill: Option<&str>,
+    print_text: bool,
+) -> Result<()> {
+    status!("Response:");
+    for content in &response.content {
+        if let types::ContentBlock::Thinking { thinking, .. } = content {
+            detail!("{}", format!("💭 {}", thinking).dimmed());
+        }
+    }
+    let text = test_response_text(response, json, prefill)?;
+    if print_text && !text.is_empty() {
+        if json {
+            println!("{}", text);
+        } else {
+            println!("{}", ui::highlight(&text));
+        }
+    }
+    for tool_use in response.tool_uses() {
+        if let types::ContentBlock::ToolUse { name, input, .. } = tool_use {
+            detail!("🔧 Tool call requested: {} {}", name, input);
+        }
+    }
+    status!();
+    status!("Tokens: {}", format::usage(&response.usage));
+    if let Some(tier) = &response.service_tier {
+        status!("Service tier: {}", tier);
+    }
+
+    Ok(())
+}
+
+/// A `Test` response as printed: pretty JSON, or the text after any prefill
+fn test_response_text(
+    response: &llm::ChatResponse,
+    json: bool,
+    prefill: Option<&str>,
+) -> Result<String> {
+    Ok(if json {
+        serde_json::to_string_pretty(response)?
+    } else {
+        match prefill {
+            Some(prefill) => format!("{}{}", prefill.trim_end(), response.text()),
+            None => response.text(),
+        }
+    })
+}
+
+/// Build the ordered conversation for `Test` from repeated `--user` and
+/// `--assistant` flags plus the optional positional message
+fn conversation_turns(
+    matches: Option<&ArgMatches>,
+    message: Option<String>,
+) -> Result<Vec<types::Message>> {
+    let mut turns: Vec<(usize, types::Role, String)> = Vec::new();
+
+    if let Some(matches) = matches {
+        for (id, role) in [("user", types::Role::User), ("assistant", types::Role::Assistant)] {
+            if let (Some(indices), Some(values)) =
+                (matches.indices_of(id), matches.get_many::<String>(id))
+            {
+                for (index, value) in indices.zip(values) {
+                    turns.push((index, role.clone(), value.clone()));
+                }
+            }
+        }
+    }
+
+    turns.sort_by_key(|(index, _, _)| *index);
+    let mut turns: Vec<(types::Role, String)> =
+        turns.into_iter().map(|(_, role, text)| (role, text)).collect();
+
+    if let Some(message) = message {
+        turns.push((types::Role::User, message));
+    }
+
+    if turns.is_empty() {
+        return Err(error::OllmError::Config(
+            "Conversation is empty: pass a message or --user".to_string(),
+        ));
+    }
+
+    if turns[0].0 != types::Role::User {
+        return Err(error::OllmError::Config(
+            "Conversation must start with a --user turn".to_string(),
+        ));
+    }
+
+    if turns.last().map(|(role, _)| role) != Some(&types::Role::User) {
+        return Err(error::OllmError::Config(
+            "Conversation must end with a --user turn".to_string(),
+        ));
+    }
+
+    if let Some(pair) = turns.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(error::OllmError::Config(format!(
+            "Turns must alternate between --user and --assistant (two {:?} turns in a row)",
+            pair[0].0
+        )));
+    }
+
+    Ok(turns
+        .into_iter()
+        .map(|(role, text)| match role {
+            types::Role::Assistant => {
+                types::Message::new_assistant(vec![types::ContentBlock::Text { text }])
+            }
+            _ => types::Message::new_user(text),
+        })
+        .collect())
+}
+
+#[derive(Subcommand)]
+enum SessionsCommand {
+    /// List saved sessions
+    List,
+
+    /// Show a saved session's details and messages
+    Show {
+        /// Session id
+        id: String,
+    },
+
+    /// Export a saved session as a Markdown transcript
+    Export {
+        /// Session id
+        id: String,
+
+        /// Write the transcript to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Delete sessions not updated within the given age
+    Prune {
+        /// Age such as 30d, 12h, or 2w (units: s, m, h, d, w)
+        #[arg(long, value_name = "AGE", value_parser = parse_age)]
+        older_than: chrono::Duration,
+
+        /// List the sessions that would be deleted without deleting them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Copy a saved session's first N messages into a new session, to try a
+    /// different direction without losing the original
+    Fork {
+        /// Session id
+        id: String,
+
+        /// Number of messages to keep
+        n: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Delete every cached response
+    Clear,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // Before logging and config, so RUST_LOG and api_key_env can come from it
+    load_env_file(cli.env_file.as_deref())?;
 
-    // Initialize logging
-    let log_level = if cli.verbose { "debug" } else { "info" };
+    // Initialize logging (on stderr, so stdout stays clean for piping)
     tracing_subscriber::fmt()
-        .with_env_filter(format!("open_llm_code={}", log_level))
+        .with_env_filter(cli.log_level().env_filter())
+        .with_writer(std::io::stderr)
         .init();
 
-    match cli.command {
-        Some(Commands::Init { output }) => {
-            let config_example = config::Config::example();
+    QUIET.store(cli.quiet || cli.stdout_only, Ordering::Relaxed);
+    STDOUT_ONLY.store(cli.stdout_only, Ordering::Relaxed);
+
+    if cli.no_color || std::env::var_os("NO_COLOR").is_some() {
+        colored::control::set_override(false);
+    }
+
+    // Dropping the running command on a signal drops any McpManager it owns,
+    // which shuts down the MCP server process groups
+    tokio::select! {
+        result = run(cli, &matches) => result,
+        signal = shutdown_signal() => {
+            Err(anyhow::anyhow!("Interrupted by {}", signal).into())
+        }
+    }
+}
+
+/// Resolve when SIGINT (or SIGTERM on Unix) is received
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = terminate.recv() => "SIGTERM",
+            },
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl-C"
+    }
+}
+
+/// Run the selected command
+async fn run(cli: Cli, matches: &ArgMatches) -> Result<()> {
+    let log_level = cli.log_level();
+    let command = cli.command.unwrap_or(Commands::Repl {
+        chat: ChatArgs::default(),
+    });
+
+    match command {
+        Commands::Init { output, interactive, force } => {
             let output_path = output.unwrap_or_else(|| {
-                let mut p = dirs::home_dir().expect("Cannot determine home directory");
-                p.push(".config");
-                p.push("open-llm-code");
-                std::fs::create_dir_all(&p).ok();
-                p.push("config.toml");
+                let p = config::Config::default_path();
+                if let Some(dir) = p.parent() {
+                    std::fs::create_dir_all(dir).ok();
+                }
                 p
             });
 
-            std::fs::write(&output_path, config_example)
-                .map_err(|e| error::OllmError::Config(format!("Failed to write config: {}", e)))?;
+            if interactive {
+                let stdin = std::io::stdin();
+                let written = config::wizard::write_config(
+                    &output_path,
+                    force,
+                    &mut stdin.lock(),
+                    &mut std::io::stderr(),
+                )?;
+                if !written {
+                    status!("Nothing written");
+                    return Ok(());
+                }
+                println!("✅ Created config at: {}", output_path.display());
+                status!("Run `ollm diagnose` to check it");
+            } else {
+                config::wizard::check_overwrite(&output_path, force)?;
+                std::fs::write(&output_path, config::Config::example()).map_err(|e| {
+                    error::OllmError::Config(format!("Failed to write config: {}", e))
+                })?;
+                println!("✅ Created example config at: {}", output_path.display());
+                status!("Edit this file and add your API keys/credentials");
+            }
+            Ok(())
+        }
+
+        Commands::ConfigPath => {
+            let path = cli.config.unwrap_or_else(config::Config::default_path);
+            println!("{}", path.display());
+            if path.exists() {
+                status!("✅ File exists");
+            } else {
+                status!("❌ File not found (run `ollm init` to create it)");
+            }
+            Ok(())
+        }
 
-            println!("✅ Created example config at: {}", output_path.display());
-            println!("Edit this file and add your API keys/credentials");
+        Commands::Schema { output } => {
+            let schema = config::Config::json_schema();
+
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, schema)?;
+                    eprintln!("✅ Wrote config schema to: {}", path.display());
+                }
+                None => println!("{}", schema),
+            }
             Ok(())
         }
 
-        Some(Commands::Test { message }) => {
-            println!("🧪 Testing LLM provider...");
-            println!();
+        Commands::Test {
+            message,
+            user: _,
+            assistant: _,
+            prefill,
+            json,
+            output,
+            count,
+            chat,
+        } => {
+            let turns = conversation_turns(matches.subcommand_matches("test"), message)?;
 
-            let config = config::Config::load(cli.config)?;
-            println!("Provider: {} ({})", config.llm.provider, config.llm.model);
+            status!("🧪 Testing LLM provider...");
+            status!();
 
-            let provider = llm::create_provider(&config).await?;
+            let mut config = cli.overrides.load(cli.config)?;
+            chat.apply(&mut config)?;
+            status!("Provider: {} ({})", config.llm.provider, config.effective_model());
 
-            println!("Sending message: {}", message);
-            println!();
+            let provider = chat.create_provider(&config).await?;
+            let capabilities = provider.capabilities();
+            status!("Max tokens: {}", capabilities.max_tokens);
+            if let Some(window) = capabilities.context_window {
+                status!("Context window: {}", window);
+            }
 
-            let user_message = types::Message::new_user(message);
-            let response = provider.chat(vec![user_message], vec![]).await?;
+            let (_mcp_manager, tools) = start_tools(&config).await?;
+            if !tools.is_empty() {
+                status!("Tools: {} available", tools.len());
+            }
 
-            println!("Response:");
-            for content in response.content {
-                if let types::ContentBlock::Text { text } = content {
-                    println!("{}", text);
+            if turns.len() == 1 {
+                if let Some(types::ContentBlock::Text { text }) = turns[0].content.first() {
+                    status!("Sending message: {}", text);
                 }
+            } else {
+                status!("Sending conversation of {} turns", turns.len());
+            }
+            status!();
+
+            let mut messages = system_messages(&config);
+            messages.extend(turns);
+            if let Some(prefill) = &prefill {
+                llm::push_prefill(provider.as_ref(), &mut messages, prefill);
+            }
+
+            let show_runs = count == 1 || log_level >= LogLevel::Debug;
+            let mut runs = Vec::with_capacity(count as usize);
+
+            // Every run's response is appended as it arrives
+            let mut output_file = match &output {
+                Some(path) => Some(
+                    std::fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(path)?,
+                ),
+                None => None,
+            };
+
+            for run in 1..=count {
+                let message = if count == 1 {
+                    "Waiting for response...".to_string()
+                } else {
+                    format!("Run {}/{}...", run, count)
+                };
+                let spinner = ui::Spinner::start(
+                    &message,
+                    !QUIET.load(Ordering::Relaxed) && !json,
+                );
+                // JSON needs the whole response, so only text is written
+                // to the file while it streams
+                let stream_to_file = output_file.is_some() && !json && provider.supports_streaming();
+                let mut timing = timing::Timing::start();
+                let response = if provider.supports_streaming() {
+                    let mut stream = provider
+                        .buffered_stream_chat(
+                            messages.clone(),
+                            tools.clone(),
+                            config.llm.stream_buffer_chunks,
+                        )
+                        .await?;
+                    let mut accumulator = llm::StreamAccumulator::new(provider.model().to_string());
+                    if let (Some(file), Some(prefill), true) =
+                        (output_file.as_mut(), &prefill, stream_to_file)
+                    {
+                        file.write_all(prefill.trim_end().as_bytes())?;
+                    }
+                    while let Some(chunk) = stream.next().await {
+                        let chunk = chunk?;
+                        if let llm::ChatChunk::ContentBlockDelta { delta, .. } = &chunk {
+                            timing.mark_first_token();
+                            if let (Some(file), llm::ContentDelta::TextDelta { text }, true) =
+                                (output_file.as_mut(), delta, stream_to_file)
+                            {
+                                file.write_all(text.as_bytes())?;
+                            }
+                        }
+                        accumulator.push(chunk)?;
+                    }
+                    accumulator.finish()?
+                } else {
+                    provider.chat(messages.clone(), tools.clone()).await?
+                };
+                timing.finish();
+                spinner.stop();
+
+                if let Some(file) = output_file.as_mut() {
+                    if !stream_to_file {
+                        let text = test_response_text(&response, json, prefill.as_deref())?;
+                        file.write_all(text.as_bytes())?;
+                    }
+                    writeln!(file)?;
+                }
+
+                if show_runs {
+                    if count > 1 {
+                        status!("Run {}/{}:", run, count);
+                    }
+                    print_test_response(&response, json, prefill.as_deref(), output.is_none())?;
+                    if chat.timing || log_level >= LogLevel::Debug {
+                        detail!("Timing: {}", timing.summary(response.usage.output_tokens));
+                    }
+                }
+
+                runs.push((timing, response.usage.output_tokens));
+            }
+
+            if let Some(path) = &output {
+                let responses = if count == 1 { "response" } else { "responses" };
+                status!("✅ Appended {} {} to: {}", count, responses, path.display());
+            }
+
+            if count > 1 {
+                if let Some(stats) = timing::BenchmarkStats::from_runs(&runs) {
+                    status!();
+                    if json {
+                        println!("{}", serde_json::to_string_pretty(&stats)?);
+                    } else {
+                        println!("{}", stats.summary());
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        Commands::Run {
+            prompt,
+            max_iterations,
+            json,
+            chat,
+        } => {
+            let mut config = cli.overrides.load(cli.config)?;
+            chat.apply(&mut config)?;
+            status!("Provider: {} ({})", config.llm.provider, config.effective_model());
+
+            let provider = chat.create_provider(&config).await?;
+            let (mcp_manager, tools) = start_tools(&config).await?;
+            status!("Tools: {} available", tools.len());
+
+            let mut messages = system_messages(&config);
+            messages.push(types::Message::new_user(prompt));
+
+            let executor = tools::ToolExecutor::new(tools, &config.tools);
+            let trace = config.tools.trace && !json;
+            let agent = agent::Agent::new(provider.as_ref(), &mcp_manager, executor)
+                .max_iterations(max_iterations)
+                .max_history_messages(config.llm.max_history_messages)
+                .early_tool_dispatch(config.tools.early_dispatch)
+                .refresh_tools(true)
+                .stream_buffer_chunks(config.llm.stream_buffer_chunks)
+                .on_event(move |event| {
+                    if trace {
+                        print_agent_event(event);
+                    }
+                });
+            // JSON output needs the whole answer, so only stream otherwise
+            let outcome = if json {
+                agent.run(messages).await?
+            } else {
+                agent.run_rendered(messages, &mut terminal_renderer(trace)).await?
+            };
+
+            if json {
+                let output = serde_json::json!({
+                    "answer": outcome.response.text(),
+                    "iterations": outcome.iterations,
+                    "stop_reason": outcome.response.stop_reason,
+                    "usage": outcome.usage,
+                });
+                println!("{}", serde_json::to_string_pretty(&output)?);
+            } else {
+                status!();
+                status!(
+                    "{} iterations · Tokens: {}",
+                    outcome.iterations,
+                    format::usage(&outcome.usage)
+                );
             }
-            println!();
-            println!("Tokens: {} in, {} out ({})",
-                response.usage.input_tokens,
-                response.usage.output_tokens,
-                response.usage.total()
-            );
 
             Ok(())
         }
 
-        Some(Commands::ListTools) => {
-            println!("🔧 Listing MCP tools...");
-            println!();
+        Commands::ListTools { schema } => {
+            status!("🔧 Listing MCP tools...");
+            status!();
 
-            let config = config::Config::load(cli.config)?;
+            let config = cli.overrides.load(cli.config)?;
 
-            println!("Loaded {} MCP server configs", config.mcp_servers.len());
+            status!("Loaded {} MCP server configs", config.mcp_servers.len());
 
-            let mut mcp_manager = mcp::McpManager::new();
+            let mut mcp_manager = mcp_manager(&config);
 
-            mcp_manager.start_servers(config.mcp_servers)?;
+            let summary = mcp_manager
+                .start_servers(
+                    config.mcp_servers,
+                    config.mcp_fail_fast,
+                    Duration::from_secs(config.mcp_request_timeout_secs),
+                    config.mcp_startup_concurrency,
+                )
+                .await?;
+            for (name, error) in &summary.failed {
+                eprintln!("❌ MCP server '{}' failed: {}", name, error);
+            }
 
-            let tools = mcp_manager.get_all_tools();
+            let servers = mcp_manager.describe();
+            let tool_count: usize = servers.iter().map(|s| s.tools.len()).sum();
 
-            println!("Found {} tools from {} servers:", tools.len(), mcp_manager.server_count());
-            println!();
+            status!("Found {} tools from {} servers:", tool_count, servers.len());
+            status!();
 
-            for tool in tools {
-                println!("📦 {}", tool.name);
-                println!("   {}", tool.description);
+            for server in servers {
+                let info = server
+                    .server_info
+                    .map(|i| format!(" ({} v{})", i.name, i.version))
+                    .unwrap_or_default();
+                println!("🖥  {}{}", server.name, info);
                 println!();
+
+                for tool in server.tools {
+                    println!("   📦 {}", tool.name);
+                    println!("      {}", tool.description);
+                    if schema {
+                        let pretty = serde_json::to_string_pretty(&tool.input_schema)?;
+                        for line in pretty.lines() {
+                            println!("      {}", line.dimmed());
+                        }
+                    } else {
+                        for argument in tools::summarize_schema(&tool.input_schema) {
+                            println!("      • {}", argument.dimmed());
+                        }
+                    }
+                    println!();
+                }
+            }
+
+            Ok(())
+        }
+
+        Commands::Embed { text, json } => {
+            use llm::embeddings::Embedder;
+
+            let config = cli.overrides.load(cli.config)?;
+            let embedder = llm::embeddings::HttpEmbedder::new(&config)?;
+            status!(
+                "Embedding {} inputs with {} ({:?} API)",
+                text.len(),
+                config.embeddings.model,
+                config.embeddings.api
+            );
+
+            let vectors = embedder.embed(text).await?;
+            let dimensions = vectors.first().map_or(0, Vec::len);
+
+            if json {
+                println!("{}", serde_json::to_string(&vectors)?);
+            } else {
+                println!("Dimensions: {}", dimensions);
+            }
+            if dimensions != embedder.dimensions() {
+                eprintln!(
+                    "⚠️  embeddings.dimensions is {} but the model returned {}",
+                    embedder.dimensions(),
+                    dimensions
+                );
             }
 
             Ok(())
         }
 
-        Some(Commands::Version) => {
+        Commands::Cache { command } => {
+            let config = cli.overrides.load(cli.config)?;
+            let cache = llm::ResponseCache::new(config.cache_dir());
+
+            match command {
+                CacheCommand::Clear => {
+                    let removed = cache.clear().await?;
+                    status!("🗑️  Removed {} cached responses from {}", removed, config.cache_dir().display());
+                }
+            }
+
+            Ok(())
+        }
+
+        Commands::Sessions { command } => {
+            let config = cli.overrides.load(cli.config)?;
+            let store = session::FileSessionStore::new(config.session_dir());
+
+            match command {
+                SessionsCommand::List => {
+                    let sessions = store.list().await?;
+
+                    status!("📚 {} sessions", sessions.len());
+                    status!();
+
+                    for s in sessions {
+                        println!("{}  {}", s.id, s.title.as_deref().unwrap_or("(untitled)"));
+                        println!(
+                            "   {} messages · {} ({}) · updated {}",
+                            s.messages.len(),
+                            s.provider,
+                            s.model,
+                            s.updated_at.format("%Y-%m-%d %H:%M")
+                        );
+                    }
+                }
+                SessionsCommand::Show { id } => {
+                    let s = store.load(&id).await?;
+
+                    println!("{}  {}", s.id, s.title.as_deref().unwrap_or("(untitled)"));
+                    println!("   Provider: {} ({})", s.provider, s.model);
+                    if let Some(parent) = s.metadata.get("parent_id").and_then(|v| v.as_str()) {
+                        println!("   Forked from {}", parent);
+                    }
+                    println!(
+                        "   Created {} · updated {}",
+                        s.created_at.format("%Y-%m-%d %H:%M"),
+                        s.updated_at.format("%Y-%m-%d %H:%M")
+                    );
+                    println!("   Tokens: {}", format::usage(&s.total_usage));
+                    println!("   {} messages", s.messages.len());
+
+                    for message in &s.messages {
+                        let speaker = match message.role {
+                            types::Role::User => config.display.user_name.as_str(),
+                            types::Role::Assistant => config.display.assistant_name.as_str(),
+                            types::Role::System => "system",
+                        };
+                        println!();
+                        println!("{}:", speaker);
+                        println!("{}", llm::templates::message_text(message));
+                    }
+                }
+                SessionsCommand::Export { id, output } => {
+                    let s = store.load(&id).await?;
+                    let markdown = session::to_markdown(
+                        &s,
+                        &config.display.user_name,
+                        &config.display.assistant_name,
+                    );
+
+                    match output {
+                        Some(path) => {
+                            std::fs::write(&path, markdown)?;
+                            status!("✅ Exported session to: {}", path.display());
+                        }
+                        None => print!("{}", markdown),
+                    }
+                }
+                SessionsCommand::Prune { older_than, dry_run } => {
+                    let cutoff = chrono::Utc::now() - older_than;
+
+                    if dry_run {
+                        let stale: Vec<_> = store
+                            .list()
+                            .await?
+                            .into_iter()
+                            .filter(|s| s.updated_at < cutoff)
+                            .collect();
+                        for s in &stale {
+                            println!(
+                                "{}  {} (updated {})",
+                                s.id,
+                                s.title.as_deref().unwrap_or("(untitled)"),
+                                s.updated_at.format("%Y-%m-%d %H:%M")
+                            );
+                        }
+                        status!("Would delete {} sessions", stale.len());
+                    } else {
+                        let deleted = store.prune(cutoff).await?;
+                        status!("🗑️  Deleted {} sessions", deleted);
+                    }
+                }
+                SessionsCommand::Fork { id, n } => {
+                    let parent = store.load(&id).await?;
+                    let fork = parent.fork(n);
+                    store.save(&fork).await?;
+
+                    status!(
+                        "✅ Forked {} messages of {} into a new session",
+                        fork.messages.len(),
+                        parent.id
+                    );
+                    println!("{}", fork.id);
+                }
+            }
+
+            Ok(())
+        }
+
+        Commands::Diagnose => {
+            status!("🩺 Running diagnostics...");
+            status!();
+
+            diagnose::run(cli.config).await
+        }
+
+        Commands::ValidateConfig => {
+            let config = cli.overrides.load(cli.config)?;
+            config.validate()?;
+
+            println!("✅ Config is valid");
+            println!("Provider: {} ({})", config.llm.provider, config.effective_model());
+            println!("Max tokens: {}", config.llm.max_tokens);
+            println!("MCP servers: {}", config.mcp_servers.len());
+            for server in &config.mcp_servers {
+                println!("   {}: {} {}", server.name, server.command, server.args.join(" "));
+            }
+
+            Ok(())
+        }
+
+        Commands::Version { json: true } => {
+            println!("{}", serde_json::to_string_pretty(&version_json())?);
+            Ok(())
+        }
+
+        Commands::Version { json: false } => {
             println!("ollm v{}", env!("CARGO_PKG_VERSION"));
             println!("A Rust-based AI coding assistant with pluggable LLM backends");
             Ok(())
         }
 
-        Some(Commands::Repl) | None => {
-            println!("🚀 Open LLM Code v{}", env!("CARGO_PKG_VERSION"));
-            println!();
-            println!("Loading configuration...");
+        Commands::Repl { chat } => {
+            status!("🚀 Open LLM Code v{}", env!("CARGO_PKG_VERSION"));
+            status!();
+            status!("Loading configuration...");
 
             // Load config
-            let _config = config::Config::load(cli.config)?;
+            let mut config = cli.overrides.load(cli.config)?;
+            chat.apply(&mut config)?;
+
+            status!("✅ Configuration loaded");
+
+            let provider = chat.create_provider(&config).await?;
+            let (mut mcp_manager, tools) = start_tools(&config).await?;
+            status!(
+                "Provider: {} ({}) · {} tools",
+                provider.name(),
+                provider.model(),
+                tools.len()
+            );
+
+            let trace = config.tools.trace;
 
-            println!("✅ Configuration loaded");
-            println!();
-            println!("🔧 REPL mode not yet implemented");
-            println!("   Run `ollm init` to generate a config file");
+            // Carries the cache breakpoints from one turn to the next
+            let mut conversation = match &config.llm.system_prompt {
+                Some(system) => ConversationBuffer::with_system(system.clone()),
+                None => ConversationBuffer::new(),
+            };
 
+            // The conversation is saved after every exchange
+            let store = session::FileSessionStore::new(config.session_dir());
+            let mut session =
+                types::Session::new(provider.name().to_string(), provider.model().to_string());
+            session.mcp_servers = config.mcp_servers.iter().map(|s| s.name.clone()).collect();
+            session.messages = conversation.to_messages();
+            let mut saved = false;
+
+            // Piped stdin: each line is a turn, and EOF ends the session
+            let mut input = ui::repl::LineSource::from_stdin()?;
+            if input.is_interactive() {
+                status!("Type /exit or press Ctrl-D to quit");
+            }
+            status!();
+
+            let prompt = format!("{}> ", config.display.user_name);
+            let mut renderer = terminal_renderer(trace);
+            if input.is_interactive() {
+                renderer = renderer
+                    .label(format!("{}>", config.display.assistant_name))
+                    .spinner(!QUIET.load(Ordering::Relaxed));
+            }
+            while let Some(line) = input.read_line(&prompt)? {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                if line == "/exit" || line == "/quit" {
+                    break;
+                }
+
+                // Servers whose requests timed out get a fresh start before
+                // the next turn
+                let restart_policy = retry::BackoffPolicy::default();
+                for (name, e) in mcp_manager.restart_unhealthy(&restart_policy).await {
+                    eprintln!("⚠️  MCP server '{}' could not be restarted: {}", name, e);
+                }
+
+                let before = conversation.messages().len();
+                conversation.push(types::Message::new_user(line.to_string()));
+
+                // Servers may have changed their tools since the last turn
+                let executor = tools::ToolExecutor::new(mcp_manager.get_all_tools(), &config.tools);
+                let agent = agent::Agent::new(provider.as_ref(), &mcp_manager, executor)
+                    .max_history_messages(config.llm.max_history_messages)
+                    .early_tool_dispatch(config.tools.early_dispatch)
+                    .refresh_tools(true)
+                    .stream_buffer_chunks(config.llm.stream_buffer_chunks)
+                    .on_event(move |event| {
+                        if trace {
+                            print_agent_event(event);
+                        }
+                    });
+
+                renderer.wait();
+                let result = agent.run_conversation(&mut conversation, &mut renderer).await;
+                renderer.stop_waiting();
+                match result {
+                    Ok(outcome) => {
+                        if input.is_interactive() {
+                            println!();
+                        }
+                        for message in &outcome.messages[session.messages.len()..] {
+                            session.add_message(message.clone());
+                        }
+                        session.record_usage(&outcome.usage);
+
+                        if config.session.auto_title {
+                            if let Err(e) = session::ensure_title(&mut session, provider.as_ref()).await {
+                                status!("⚠️  Failed to generate a session title: {}", e);
+                            }
+                        }
+                        match store.save(&session).await {
+                            Ok(()) => saved = true,
+                            Err(e) => eprintln!("❌ Failed to save session: {}", e),
+                        }
+                    }
+                    Err(e) => {
+                        // Forget the failed turn, as if it was never sent
+                        conversation.truncate(before);
+                        eprintln!("❌ {}", e);
+                    }
+                }
+            }
+
+            if saved {
+                status!("💾 Session saved: {}", session.id);
+            }
             Ok(())
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_json_lists_providers_and_features() {
+        let output = version_json();
+
+        assert_eq!(output["name"], "ollm");
+        assert_eq!(output["version"], env!("CARGO_PKG_VERSION"));
+        let providers = output["providers"].as_array().unwrap();
+        for name in ["anthropic", "ollama", "huggingface"] {
+            assert!(providers.iter().any(|p| p == name), "missing {}", name);
+        }
+
+        let features = output["features"].as_array().unwrap();
+        assert_eq!(features.iter().any(|f| f == "opensearch"), cfg!(feature = "opensearch"));
+        assert_eq!(features.iter().any(|f| f == "tokenizer"), cfg!(feature = "tokenizer"));
+    }
+
+    /// Write the example config, changed by `configure`, to a temp file
+    fn config_file(configure: impl FnOnce(&mut config::Config)) -> PathBuf {
+        let mut config: config::Config = toml::from_str(&config::Config::example()).unwrap();
+        configure(&mut config);
+        let path = std::env::temp_dir().join(format!("ollm-main-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+        path
+    }
+
+    /// The config `args` run with: the example config changed by
+    /// `configure`, then the command-line overrides
+    fn run_config(
+        args: &[&str],
+        configure: impl FnOnce(&mut config::Config),
+    ) -> Result<config::Config> {
+        let path = config_file(configure);
+        let cli = Cli::try_parse_from(args).unwrap();
+        let config = cli.overrides.load(Some(path.clone())).and_then(|mut config| {
+            match &cli.command {
+                Some(Commands::Test { chat, .. })
+                | Some(Commands::Run { chat, .. })
+                | Some(Commands::Repl { chat }) => chat.apply(&mut config)?,
+                _ => {}
+            }
+            Ok(config)
+        });
+        std::fs::remove_file(path).unwrap();
+        config
+    }
+
+    /// Provider settings for an Anthropic model with a test API key
+    fn anthropic(config: &mut config::Config) {
+        std::env::set_var("OLLM_TEST_MAIN_KEY", "test-key");
+        config.llm.provider = "anthropic".to_string();
+        config.llm.model = "claude-sonnet-4-20250514".to_string();
+        config.llm.api_key_env = Some("OLLM_TEST_MAIN_KEY".to_string());
+        config.llm.tool_prompting = false;
+    }
+
+    #[tokio::test]
+    async fn max_tokens_flag_reaches_the_request() {
+        let config =
+            run_config(&["ollm", "test", "--max-tokens", "1234", "hi"], anthropic).unwrap();
+        let provider = llm::ProviderRegistry::new().create(&config).unwrap();
+        let body = provider
+            .request_body(vec![types::Message::new_user("hi".to_string())], vec![], false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(body["max_tokens"], 1234);
+
+        for bad in ["0", "200001"] {
+            let error = run_config(&["ollm", "test", "--max-tokens", bad, "hi"], anthropic)
+                .err()
+                .unwrap();
+            assert_eq!(
+                error.to_string(),
+                format!(
+                    "Configuration error: max_tokens must be between 1 and 200000, got {}",
+                    bad
+                )
+            );
+        }
+        assert!(run_config(&["ollm", "test", "--max-tokens", "200000", "hi"], anthropic).is_ok());
+    }
+
+    /// The request body `args` would send for "hi"
+    fn request_sent(
+        args: &[&str],
+        configure: impl FnOnce(&mut config::Config),
+    ) -> serde_json::Value {
+        let config = run_config(args, |config| {
+            config.llm.system_prompt = Some("From the config".to_string());
+            configure(config);
+        })
+        .unwrap();
+        let mut messages = system_messages(&config);
+        messages.push(types::Message::new_user("hi".to_string()));
+        llm::ProviderRegistry::new()
+            .create(&config)
+            .unwrap()
+            .request_body(messages, vec![], false)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn system_flag_replaces_the_config_system_prompt() {
+        let ollama = |config: &mut config::Config| {
+            config.llm.provider = "ollama".to_string();
+            config.llm.tool_prompting = false;
+        };
+        let ollama_system = |body: serde_json::Value| body["messages"][0].clone();
+
+        let body = request_sent(&["ollm", "test", "--system", "From the flag", "hi"], anthropic);
+        assert_eq!(body["system"], "From the flag");
+        let body = request_sent(&["ollm", "test", "hi"], anthropic);
+        assert_eq!(body["system"], "From the config");
+
+        let body = request_sent(&["ollm", "test", "--system", "From the flag", "hi"], ollama);
+        assert_eq!(
+            ollama_system(body),
+            serde_json::json!({ "role": "system", "content": "From the flag" })
+        );
+        let body = request_sent(&["ollm", "test", "hi"], ollama);
+        assert_eq!(
+            ollama_system(body),
+            serde_json::json!({ "role": "system", "content": "From the config" })
+        );
+    }
+
+    #[test]
+    fn preset_becomes_the_system_prompt() {
+        let with_presets = |config: &mut config::Config| {
+            anthropic(config);
+            config.presets.clear();
+            config.presets.insert("terse".to_string(), "Answer in one line.".to_string());
+            config.presets.insert("review".to_string(), "Review the code.".to_string());
+        };
+
+        let body = request_sent(&["ollm", "test", "--preset", "terse", "hi"], with_presets);
+        assert_eq!(body["system"], "Answer in one line.");
+        // --system still wins over the preset
+        let body = request_sent(
+            &["ollm", "test", "--preset", "terse", "--system", "From the flag", "hi"],
+            with_presets,
+        );
+        assert_eq!(body["system"], "From the flag");
+
+        let error = run_config(&["ollm", "test", "--preset", "poem", "hi"], with_presets)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Configuration error: Unknown preset 'poem' (configured: review, terse)"
+        );
+    }
+
+    #[test]
+    fn env_file_supplies_unset_variables_only() {
+        let path = std::env::temp_dir().join(format!("ollm-env-{}", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            "OLLM_TEST_ENV_FILE_KEY=from-file\nOLLM_TEST_ENV_FILE_SET=from-file\n",
+        )
+        .unwrap();
+        std::env::set_var("OLLM_TEST_ENV_FILE_SET", "already-set");
+
+        let mut config: config::Config = toml::from_str(&config::Config::example()).unwrap();
+        anthropic(&mut config);
+        config.llm.api_key_env = Some("OLLM_TEST_ENV_FILE_KEY".to_string());
+        assert!(llm::ProviderRegistry::new().create(&config).is_err());
+
+        load_env_file(Some(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(llm::ProviderRegistry::new().create(&config).is_ok());
+        assert_eq!(std::env::var("OLLM_TEST_ENV_FILE_KEY").unwrap(), "from-file");
+        assert_eq!(std::env::var("OLLM_TEST_ENV_FILE_SET").unwrap(), "already-set");
+
+        let error = load_env_file(Some(&path)).unwrap_err();
+        assert!(error.to_string().contains("Failed to load env file"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn timeout_and_retries_flags_reach_the_provider() {
+        // A server that accepts connections and never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let attempts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = attempts.clone();
+        tokio::spawn(async move {
+            let mut open = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                open.push(socket);
+            }
+        });
+
+        let path = config_file(|config| {
+            config.llm.provider = "ollama".to_string();
+            config.llm.timeout_secs = Some(60);
+            config.llm.max_retries = 0;
+            config.ollama.endpoint = endpoint;
+        });
+        let cli = Cli::try_parse_from(["ollm", "--timeout", "1", "--retries", "1", "test", "hi"]).unwrap();
+        let config = cli.overrides.load(Some(path.clone())).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(config.llm.timeout_secs, Some(1));
+        assert_eq!(config.llm.max_retries, 1);
+
+        let provider = llm::ProviderRegistry::new().create(&config).unwrap();
+        let started = std::time::Instant::now();
+        let error = provider
+            .chat(vec![types::Message::new_user("hi".to_string())], vec![])
+            .await
+            .unwrap_err();
+
+        // Each of the two attempts gave up after a second, not sixty
+        assert!(matches!(error, error::OllmError::Timeout(1)), "{:?}", error);
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn disabled_mcp_starts_no_servers() {
+        let marker = std::env::temp_dir().join(format!("ollm-spawned-{}", uuid::Uuid::new_v4()));
+        let server = config::McpServerConfig {
+            name: "a".to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), format!("touch {}", marker.display())],
+            env: Default::default(),
+            inherit_env: true,
+            omit_empty_arguments: false,
+            content_length_framing: false,
+        };
+
+        // Off in the config, or with --no-mcp
+        for (enabled, args) in [
+            (false, vec!["ollm", "test", "hi"]),
+            (true, vec!["ollm", "--no-mcp", "test", "hi"]),
+        ] {
+            let path = config_file(|config| {
+                config.mcp_enabled = enabled;
+                config.mcp_servers = vec![server.clone()];
+            });
+            let cli = Cli::try_parse_from(args).unwrap();
+            let config = cli.overrides.load(Some(path.clone())).unwrap();
+            std::fs::remove_file(path).unwrap();
+
+            let (manager, tools) = start_tools(&config).await.unwrap();
+            assert!(!manager.is_enabled());
+            assert_eq!(manager.server_count(), 0);
+            assert!(tools.is_empty());
+            assert!(manager.get_all_tools().is_empty());
+        }
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!marker.exists(), "a server was spawned");
+    }
+
+    #[test]
+    fn zero_timeout_flag_is_rejected() {
+        let path = config_file(|_| {});
+        let cli = Cli::try_parse_from(["ollm", "--timeout", "0", "test", "hi"]).unwrap();
+        let error = cli.overrides.load(Some(path.clone())).err().unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(error.to_string().contains("timeout must be at least 1 second"));
+    }
+}
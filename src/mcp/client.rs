@@ -1,43 +1,100 @@
 use super::types::*;
 use crate::error::{OllmError, Result};
-use crate::types::Tool;
+use crate::types::{Tool, ToolResultContent};
 use serde_json::{json, Value};
-use std::io::{BufRead, BufReader, Write};
-use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
-use std::sync::atomic::{AtomicI32, Ordering};
-use std::sync::Arc;
-use tracing::{debug, error, info, warn};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Maximum number of consecutive non-JSON-RPC stdout lines to skip before
+/// giving up on the server
+const MAX_SKIPPED_LINES: usize = 50;
+
+/// Largest `Content-Length` body accepted from a server
+const MAX_FRAME_BYTES: usize = 64 * 1024 * 1024;
+
+/// Pending-request key for responses with a `null` id, which servers send
+/// when they can't parse a request at all (e.g. a batch they don't support)
+const NULL_ID: i64 = -1;
+
+/// Requests awaiting a response, keyed by JSON-RPC id
+type PendingRequests = Arc<Mutex<HashMap<i64, oneshot::Sender<JsonRpcResponse>>>>;
 
 /// MCP Server connection via stdio
+///
+/// A background task reads the server's stdout and routes each response to
+/// the request with the matching id, so requests can be issued concurrently
+/// through `&self`. When the server sends `notifications/tools/list_changed`
+/// the reader refreshes the cached tool list.
 pub struct McpClient {
-    name: String,
     process: Child,
-    stdin: ChildStdin,
-    stdout: BufReader<ChildStdout>,
-    request_id: Arc<AtomicI32>,
+    reader: JoinHandle<()>,
     server_info: Option<Implementation>,
-    tools: Vec<McpTool>,
+    connection: Arc<Connection>,
+    omit_empty_arguments: bool,
+}
+
+/// State shared between the client and its stdout reader task
+struct Connection {
+    name: String,
+    stdin: tokio::sync::Mutex<ChildStdin>,
+    pending: PendingRequests,
+    request_id: AtomicI32,
+    request_timeout: Duration,
+    /// Cleared when a request times out, so the server can be restarted
+    healthy: AtomicBool,
+    /// Write `Content-Length`-framed messages instead of newline-delimited
+    /// ones; set by config or once the server sends a framed message
+    content_length: AtomicBool,
+    tools: RwLock<Vec<McpTool>>,
 }
 
 impl McpClient {
     /// Start an MCP server process
+    ///
+    /// Without `inherit_env` the server's environment contains only `env`.
+    /// Requests that get no response within `request_timeout` fail.
     pub fn start(
         name: String,
         command: String,
         args: Vec<String>,
-        env: std::collections::HashMap<String, String>,
+        env: HashMap<String, String>,
+        inherit_env: bool,
+        request_timeout: Duration,
     ) -> Result<Self> {
         info!("Starting MCP server '{}': {} {:?}", name, command, args);
 
-        let mut child = Command::new(&command)
-            .args(&args)
+        let mut cmd = Command::new(&command);
+        if !inherit_env {
+            cmd.env_clear();
+        }
+        cmd.args(&args)
             .envs(&env)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::null()) // Silence stderr to avoid mixing with stdout
+            .kill_on_drop(true);
+
+        // Own process group, so shutdown also reaches grandchildren (e.g. the
+        // node process behind `npx`)
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        let mut child = cmd
             .spawn()
-            .map_err(|e| {
-                OllmError::Mcp(format!("Failed to start MCP server '{}': {}", name, e))
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => OllmError::Mcp(format!(
+                    "Failed to start MCP server '{}': command `{}` not found on PATH; is it installed?",
+                    name, command
+                )),
+                _ => OllmError::Mcp(format!("Failed to start MCP server '{}': {}", name, e)),
             })?;
 
         let stdin = child.stdin.take().ok_or_else(|| {
@@ -48,22 +105,48 @@ impl McpClient {
             OllmError::Mcp(format!("Failed to get stdout for MCP server '{}'", name))
         })?;
 
-        let stdout = BufReader::new(stdout);
+        let connection = Arc::new(Connection {
+            name,
+            stdin: tokio::sync::Mutex::new(stdin),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            request_id: AtomicI32::new(1),
+            request_timeout,
+            healthy: AtomicBool::new(true),
+            content_length: AtomicBool::new(false),
+            tools: RwLock::new(Vec::new()),
+        });
+        let reader = tokio::spawn(read_loop(
+            connection.name.clone(),
+            stdout,
+            connection.pending.clone(),
+            Arc::downgrade(&connection),
+        ));
 
         Ok(Self {
-            name,
             process: child,
-            stdin,
-            stdout,
-            request_id: Arc::new(AtomicI32::new(1)),
+            reader,
             server_info: None,
-            tools: Vec::new(),
+            connection,
+            omit_empty_arguments: false,
         })
     }
 
+    /// Leave `arguments` out of `tools/call` when there are none, instead
+    /// of sending `{}`
+    pub fn set_omit_empty_arguments(&mut self, omit: bool) {
+        self.omit_empty_arguments = omit;
+    }
+
+    /// Frame messages to the server with `Content-Length` headers instead
+    /// of newlines, before the server has sent a framed message itself
+    pub fn set_content_length_framing(&mut self, framed: bool) {
+        self.connection.content_length.store(framed, Ordering::Relaxed);
+    }
+
     /// Initialize the MCP server
-    pub fn initialize(&mut self) -> Result<()> {
-        info!("Initializing MCP server '{}'", self.name);
+    pub async fn initialize(&mut self) -> Result<()> {
+        let connection = self.connection.clone();
+        info!("Initializing MCP server '{}'", connection.name);
 
         let params = InitializeParams {
             protocol_version: MCP_VERSION.to_string(),
@@ -74,7 +157,9 @@ impl McpClient {
             },
         };
 
-        let response = self.send_request("initialize", Some(json!(params)))?;
+        let response = connection
+            .send_request("initialize", Some(json!(params)))
+            .await?;
 
         let result: InitializeResult = serde_json::from_value(response).map_err(|e| {
             OllmError::Mcp(format!("Failed to parse initialize response: {}", e))
@@ -82,44 +167,60 @@ impl McpClient {
 
         info!(
             "MCP server '{}' initialized: {} v{}",
-            self.name, result.server_info.name, result.server_info.version
+            connection.name, result.server_info.name, result.server_info.version
         );
 
         self.server_info = Some(result.server_info);
 
-        // List available tools
-        self.list_tools()?;
+        connection
+            .send_notification("notifications/initialized", None)
+            .await?;
 
-        Ok(())
-    }
-
-    /// List available tools from the server
-    fn list_tools(&mut self) -> Result<()> {
-        debug!("Listing tools from MCP server '{}'", self.name);
-
-        let response = self.send_request("tools/list", None)?;
-
-        let result: ListToolsResult = serde_json::from_value(response).map_err(|e| {
-            OllmError::Mcp(format!("Failed to parse tools/list response: {}", e))
-        })?;
-
-        info!(
-            "MCP server '{}' has {} tools",
-            self.name,
-            result.tools.len()
-        );
+        // Discover tools, plus whatever else the server offers, in one round
+        // trip
+        let mut requests = vec![("tools/list", None)];
+        if result.capabilities.resources.is_some() {
+            requests.push(("resources/list", None));
+        }
+        if result.capabilities.prompts.is_some() {
+            requests.push(("prompts/list", None));
+        }
 
-        self.tools = result.tools;
+        let mut responses = connection.send_batch(&requests).await.into_iter();
+        if let Some(tools) = responses.next() {
+            connection.store_tools(tools?)?;
+        }
+        for ((method, _), response) in requests.iter().skip(1).zip(responses) {
+            let key = method.trim_end_matches("/list");
+            match response {
+                Ok(result) => {
+                    let count = result.get(key).and_then(Value::as_array).map_or(0, Vec::len);
+                    info!("MCP server '{}' has {} {}", connection.name, count, key);
+                }
+                Err(e) => warn!("MCP server '{}' failed {}: {}", connection.name, method, e),
+            }
+        }
 
         Ok(())
     }
 
+    /// Send several requests in one round trip, returning their results in
+    /// request order
+    ///
+    /// Servers that reject JSON-RPC batches get the requests one at a time.
+    pub async fn send_batch(&self, requests: &[(&str, Option<Value>)]) -> Vec<Result<Value>> {
+        self.connection.send_batch(requests).await
+    }
+
     /// Get all available tools
     pub fn get_tools(&self) -> Vec<Tool> {
-        self.tools
+        self.connection
+            .tools
+            .read()
+            .unwrap()
             .iter()
             .map(|t| Tool {
-                name: format!("{}::{}", self.name, t.name),
+                name: format!("{}::{}", self.connection.name, t.name),
                 description: t.description.clone(),
                 input_schema: t.input_schema.clone(),
             })
@@ -127,54 +228,132 @@ impl McpClient {
     }
 
     /// Call a tool on the MCP server
-    pub fn call_tool(&mut self, tool_name: &str, arguments: Option<Value>) -> Result<String> {
+    pub async fn call_tool(
+        &self,
+        tool_name: &str,
+        arguments: Option<Value>,
+    ) -> Result<Vec<ToolResultContent>> {
         debug!(
             "Calling tool '{}' on MCP server '{}'",
-            tool_name, self.name
+            tool_name, self.connection.name
         );
 
+        // The spec default is an empty object; `null` is rejected by some
+        // servers, and a few reject `{}` too
+        let arguments = match arguments {
+            Some(Value::Null) | None => Some(json!({})),
+            arguments => arguments,
+        }
+        .filter(|a| !(self.omit_empty_arguments && a.as_object().is_some_and(|o| o.is_empty())));
         let params = CallToolParams {
             name: tool_name.to_string(),
             arguments,
         };
 
-        let response = self.send_request("tools/call", Some(json!(params)))?;
+        let response = self
+            .connection
+            .send_request("tools/call", Some(json!(params)))
+            .await?;
 
         let result: CallToolResult = serde_json::from_value(response).map_err(|e| {
             OllmError::Mcp(format!("Failed to parse tools/call response: {}", e))
         })?;
 
-        // Check for errors
+        // Content the model can't take directly becomes a text placeholder,
+        // so one odd part doesn't lose the rest of the result
+        let content: Vec<ToolResultContent> = result
+            .content
+            .into_iter()
+            .map(|c| match c {
+                ToolContent::Text { text } => ToolResultContent::Text { text },
+                ToolContent::Image { data, mime_type } => ToolResultContent::Image {
+                    data,
+                    media_type: mime_type,
+                },
+                ToolContent::Audio { data, mime_type } => {
+                    ToolResultContent::text(binary_placeholder(&data, &mime_type))
+                }
+                ToolContent::Resource { resource } => {
+                    // Embedded text resources carry their content inline
+                    let mime_type = resource
+                        .get("mimeType")
+                        .and_then(Value::as_str)
+                        .unwrap_or("unknown");
+                    if let Some(text) = resource.get("text").and_then(Value::as_str) {
+                        ToolResultContent::text(text)
+                    } else if let Some(blob) = resource.get("blob").and_then(Value::as_str) {
+                        ToolResultContent::text(binary_placeholder(blob, mime_type))
+                    } else {
+                        let uri = resource.get("uri").and_then(Value::as_str).unwrap_or("unknown");
+                        ToolResultContent::text(format!("[resource: {}]", uri))
+                    }
+                }
+                ToolContent::Unsupported => {
+                    debug!("Unsupported content type from tool '{}'", tool_name);
+                    ToolResultContent::text("[unsupported content]")
+                }
+            })
+            .collect();
+
         if result.is_error == Some(true) {
-            let error_text = result
-                .content
-                .iter()
-                .filter_map(|c| match c {
-                    ToolContent::Text { text } => Some(text.clone()),
-                    _ => None,
-                })
-                .collect::<Vec<_>>()
-                .join("\n");
-
-            return Err(OllmError::Mcp(format!("Tool call error: {}", error_text)));
+            return Err(OllmError::Mcp(format!(
+                "Tool call error: {}",
+                ToolResultContent::joined_text(&content)
+            )));
         }
 
-        // Combine all text content
-        let result_text = result
-            .content
-            .iter()
-            .filter_map(|c| match c {
-                ToolContent::Text { text } => Some(text.clone()),
-                _ => None,
-            })
-            .collect::<Vec<_>>()
-            .join("\n");
+        Ok(content)
+    }
 
-        Ok(result_text)
+    /// Get server name
+    pub fn name(&self) -> &str {
+        &self.connection.name
     }
 
-    /// Send a JSON-RPC request and wait for response
-    fn send_request(&mut self, method: &str, params: Option<Value>) -> Result<Value> {
+    /// Server name and version reported by `initialize`
+    pub fn server_info(&self) -> Option<&Implementation> {
+        self.server_info.as_ref()
+    }
+
+    /// The server's tools as it names them (without the `server::` prefix)
+    pub fn server_tools(&self) -> Vec<McpTool> {
+        self.connection.tools.read().unwrap().clone()
+    }
+
+    /// Whether every request so far got a response in time
+    pub fn is_healthy(&self) -> bool {
+        self.connection.healthy.load(Ordering::SeqCst)
+    }
+}
+
+impl Connection {
+    /// List available tools from the server, replacing the cached list
+    async fn list_tools(&self) -> Result<()> {
+        debug!("Listing tools from MCP server '{}'", self.name);
+
+        let response = self.send_request("tools/list", None).await?;
+        self.store_tools(response)
+    }
+
+    /// Replace the cached tool list with a `tools/list` result
+    fn store_tools(&self, response: Value) -> Result<()> {
+        let result: ListToolsResult = serde_json::from_value(response).map_err(|e| {
+            OllmError::Mcp(format!("Failed to parse tools/list response: {}", e))
+        })?;
+
+        info!(
+            "MCP server '{}' has {} tools",
+            self.name,
+            result.tools.len()
+        );
+
+        *self.tools.write().unwrap() = result.tools;
+
+        Ok(())
+    }
+
+    /// Send a JSON-RPC request and wait for the matching response
+    async fn send_request(&self, method: &str, params: Option<Value>) -> Result<Value> {
         let id = self.request_id.fetch_add(1, Ordering::SeqCst);
         let request = JsonRpcRequest::new(id, method.to_string(), params);
 
@@ -183,54 +362,161 @@ impl McpClient {
             OllmError::Mcp(format!("Failed to serialize JSON-RPC request: {}", e))
         })?;
 
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id as i64, tx);
+
         debug!("Sending request to '{}': {}", self.name, request_json);
 
-        writeln!(self.stdin, "{}", request_json).map_err(|e| {
-            OllmError::Mcp(format!("Failed to write to MCP server '{}': {}", self.name, e))
-        })?;
+        if let Err(e) = self.write_line(&request_json).await {
+            self.pending.lock().unwrap().remove(&(id as i64));
+            return Err(e);
+        }
 
-        self.stdin.flush().map_err(|e| {
-            OllmError::Mcp(format!(
-                "Failed to flush stdin for MCP server '{}': {}",
-                self.name, e
-            ))
-        })?;
+        let response = match tokio::time::timeout(self.request_timeout, rx).await {
+            Ok(response) => response.map_err(|_| {
+                OllmError::Mcp(format!(
+                    "MCP server '{}' closed stdout before responding",
+                    self.name
+                ))
+            })?,
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&(id as i64));
+                return Err(self.timed_out(method));
+            }
+        };
 
-        // Read response
-        let mut response_line = String::new();
-        self.stdout.read_line(&mut response_line).map_err(|e| {
-            OllmError::Mcp(format!(
-                "Failed to read from MCP server '{}': {}",
-                self.name, e
-            ))
-        })?;
+        response_result(response)
+    }
 
-        debug!("Received response from '{}': {}", self.name, response_line);
+    /// Send several requests as one JSON-RPC batch, returning their results
+    /// in request order
+    ///
+    /// Responses are matched to requests by id, in whatever order they
+    /// arrive. If the server rejects the batch with a single error response
+    /// (id `null`), the requests are sent again one at a time.
+    async fn send_batch(&self, requests: &[(&str, Option<Value>)]) -> Vec<Result<Value>> {
+        if requests.len() < 2 {
+            return self.send_sequential(requests).await;
+        }
 
-        let response: JsonRpcResponse = serde_json::from_str(&response_line).map_err(|e| {
-            OllmError::Mcp(format!("Failed to parse JSON-RPC response: {} - Response was: {}", e, response_line))
-        })?;
+        let (rejected_tx, rejected_rx) = oneshot::channel();
+        let registered = {
+            let mut pending = self.pending.lock().unwrap();
+            // With another batch in flight a rejection couldn't be told apart
+            let free = !pending.contains_key(&NULL_ID);
+            if free {
+                pending.insert(NULL_ID, rejected_tx);
+            }
+            free
+        };
+        if !registered {
+            return self.send_sequential(requests).await;
+        }
 
-        // Check for error
-        if let Some(error) = response.error {
-            return Err(OllmError::Mcp(format!(
-                "JSON-RPC error {}: {}",
-                error.code, error.message
-            )));
+        let mut ids = Vec::new();
+        let mut receivers = Vec::new();
+        let mut batch = Vec::new();
+        for (method, params) in requests {
+            let id = self.request_id.fetch_add(1, Ordering::SeqCst);
+            let (tx, rx) = oneshot::channel();
+            self.pending.lock().unwrap().insert(id as i64, tx);
+            batch.push(JsonRpcRequest::new(id, method.to_string(), params.clone()));
+            ids.push(id as i64);
+            receivers.push(rx);
         }
+        let forget = || {
+            let mut pending = self.pending.lock().unwrap();
+            pending.remove(&NULL_ID);
+            for id in &ids {
+                pending.remove(id);
+            }
+        };
 
-        response.result.ok_or_else(|| {
-            OllmError::Mcp("JSON-RPC response missing result field".to_string())
-        })
+        let written = match serde_json::to_string(&batch) {
+            Ok(batch_json) => {
+                debug!("Sending batch to '{}': {}", self.name, batch_json);
+                self.write_line(&batch_json).await
+            }
+            Err(e) => Err(OllmError::Mcp(format!(
+                "Failed to serialize JSON-RPC batch: {}",
+                e
+            ))),
+        };
+        if let Err(e) = written {
+            forget();
+            let message = e.to_string();
+            return requests
+                .iter()
+                .map(|_| Err(OllmError::Mcp(message.clone())))
+                .collect();
+        }
+
+        let responses = tokio::time::timeout(
+            self.request_timeout,
+            futures::future::join_all(receivers),
+        );
+        tokio::select! {
+            responses = responses => {
+                forget();
+                match responses {
+                    Ok(responses) => responses
+                        .into_iter()
+                        .map(|response| {
+                            response
+                                .map_err(|_| {
+                                    OllmError::Mcp(format!(
+                                        "MCP server '{}' closed stdout before responding",
+                                        self.name
+                                    ))
+                                })
+                                .and_then(response_result)
+                        })
+                        .collect(),
+                    Err(_) => requests
+                        .iter()
+                        .map(|(method, _)| Err(self.timed_out(method)))
+                        .collect(),
+                }
+            }
+            Ok(rejection) = rejected_rx => {
+                forget();
+                debug!(
+                    "MCP server '{}' rejected the batch ({}), sending requests one at a time",
+                    self.name,
+                    rejection.error.map(|e| e.message).unwrap_or_default()
+                );
+                self.send_sequential(requests).await
+            }
+        }
+    }
+
+    /// Send requests one after another, collecting each result
+    async fn send_sequential(&self, requests: &[(&str, Option<Value>)]) -> Vec<Result<Value>> {
+        let mut results = Vec::with_capacity(requests.len());
+        for (method, params) in requests {
+            results.push(self.send_request(method, params.clone()).await);
+        }
+        results
+    }
+
+    /// Mark the server unhealthy and describe the timed-out request
+    fn timed_out(&self, method: &str) -> OllmError {
+        self.healthy.store(false, Ordering::SeqCst);
+        OllmError::Mcp(format!(
+            "request timed out: MCP server '{}' did not answer '{}' within {:?}",
+            self.name, method, self.request_timeout
+        ))
     }
 
     /// Send a JSON-RPC notification (no response expected)
-    fn send_notification(&mut self, method: &str, params: Option<Value>) -> Result<()> {
-        let notification = json!({
+    async fn send_notification(&self, method: &str, params: Option<Value>) -> Result<()> {
+        let mut notification = json!({
             "jsonrpc": "2.0",
             "method": method,
-            "params": params,
         });
+        if let Some(params) = params {
+            notification["params"] = params;
+        }
 
         let notification_json = serde_json::to_string(&notification).map_err(|e| {
             OllmError::Mcp(format!("Failed to serialize JSON-RPC notification: {}", e))
@@ -241,33 +527,555 @@ impl McpClient {
             self.name, notification_json
         );
 
-        writeln!(self.stdin, "{}", notification_json).map_err(|e| {
-            OllmError::Mcp(format!(
-                "Failed to write notification to MCP server '{}': {}",
-                self.name, e
-            ))
+        self.write_line(&notification_json).await
+    }
+
+    /// Write one message to the server's stdin, newline-delimited or with a
+    /// `Content-Length` header to match the server's framing
+    async fn write_line(&self, line: &str) -> Result<()> {
+        let mut stdin = self.stdin.lock().await;
+
+        let framed = if self.content_length.load(Ordering::Relaxed) {
+            format!("Content-Length: {}\r\n\r\n{}", line.len(), line)
+        } else {
+            format!("{}\n", line)
+        };
+        stdin.write_all(framed.as_bytes()).await.map_err(|e| {
+            OllmError::Mcp(format!("Failed to write to MCP server '{}': {}", self.name, e))
         })?;
 
-        self.stdin.flush().map_err(|e| {
+        stdin.flush().await.map_err(|e| {
             OllmError::Mcp(format!(
                 "Failed to flush stdin for MCP server '{}': {}",
                 self.name, e
             ))
-        })?;
+        })
+    }
+}
 
-        Ok(())
+/// One message read from a server's stdout
+struct Frame {
+    body: String,
+    /// Whether it came with a `Content-Length` header
+    content_length: bool,
+}
+
+/// Read the next message from a server's stdout, or `None` at EOF
+///
+/// Messages are newline-delimited JSON, unless the line is a
+/// `Content-Length:` header (LSP-style framing), in which case the headers
+/// run to a blank line and the body is exactly that many bytes. Invalid
+/// UTF-8 is replaced rather than failing the read.
+async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> std::io::Result<Option<Frame>> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line).await? == 0 {
+        return Ok(None);
     }
+    let line = String::from_utf8_lossy(&line).into_owned();
 
-    /// Get server name
-    pub fn name(&self) -> &str {
-        &self.name
+    let Some(length) = content_length(&line) else {
+        return Ok(Some(Frame {
+            body: line,
+            content_length: false,
+        }));
+    };
+    if length > MAX_FRAME_BYTES {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Content-Length {} exceeds {} bytes", length, MAX_FRAME_BYTES),
+        ));
+    }
+
+    // Skip any further headers (e.g. Content-Type) up to the blank line
+    loop {
+        let mut header = Vec::new();
+        if reader.read_until(b'\n', &mut header).await? == 0 {
+            return Ok(None);
+        }
+        if header.iter().all(u8::is_ascii_whitespace) {
+            break;
+        }
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(Frame {
+        body: String::from_utf8_lossy(&body).into_owned(),
+        content_length: true,
+    }))
+}
+
+/// The length from a `Content-Length: N` header line
+fn content_length(line: &str) -> Option<usize> {
+    let (header, value) = line.split_once(':')?;
+    if !header.trim().eq_ignore_ascii_case("content-length") {
+        return None;
+    }
+    value.trim().parse().ok()
+}
+
+/// The result of a JSON-RPC response, or its error
+fn response_result(response: JsonRpcResponse) -> Result<Value> {
+    if let Some(error) = response.error {
+        return Err(OllmError::Mcp(format!(
+            "JSON-RPC error {}: {}",
+            error.code, error.message
+        )));
+    }
+
+    response
+        .result
+        .ok_or_else(|| OllmError::Mcp("JSON-RPC response missing result field".to_string()))
+}
+
+/// Placeholder text for base64 binary content the model can't receive
+fn binary_placeholder(base64: &str, mime_type: &str) -> String {
+    let padding = base64.bytes().rev().take_while(|&b| b == b'=').count();
+    let bytes = (base64.len() / 4 * 3).saturating_sub(padding);
+    format!("[binary content: {} bytes, mime {}]", bytes, mime_type)
+}
+
+/// Read the server's stdout, routing responses to pending requests and
+/// skipping any non-JSON-RPC lines (banners, warnings) the server prints
+async fn read_loop(
+    name: String,
+    stdout: ChildStdout,
+    pending: PendingRequests,
+    connection: Weak<Connection>,
+) {
+    let mut reader = BufReader::new(stdout);
+    let mut skipped = 0;
+
+    loop {
+        let line = match read_message(&mut reader).await {
+            Ok(Some(frame)) => {
+                // Answer in the framing the server uses
+                if frame.content_length {
+                    if let Some(connection) = connection.upgrade() {
+                        if !connection.content_length.swap(true, Ordering::Relaxed) {
+                            debug!("MCP server '{}' uses Content-Length framing", name);
+                        }
+                    }
+                }
+                frame.body
+            }
+            Ok(None) => break,
+            Err(e) => {
+                warn!("Failed to read from MCP server '{}': {}", name, e);
+                break;
+            }
+        };
+
+        debug!("Received from '{}': {}", name, line);
+
+        let message: Value = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                skipped += 1;
+                debug!(
+                    "Skipping non-JSON-RPC line from '{}': {} ({})",
+                    name,
+                    line.trim_end(),
+                    e
+                );
+                if skipped > MAX_SKIPPED_LINES {
+                    warn!(
+                        "MCP server '{}' sent more than {} non-JSON-RPC lines, giving up",
+                        name, MAX_SKIPPED_LINES
+                    );
+                    break;
+                }
+                continue;
+            }
+        };
+        skipped = 0;
+
+        // A batch response is an array of ordinary messages
+        match message {
+            Value::Array(messages) => {
+                for message in messages {
+                    dispatch(&name, message, &pending, &connection);
+                }
+            }
+            message => dispatch(&name, message, &pending, &connection),
+        }
+    }
+
+    // Fail any requests still waiting
+    pending.lock().unwrap().clear();
+}
+
+/// Route one message from the server: responses go to the pending request
+/// with the matching id, notifications are handled or ignored
+fn dispatch(name: &str, message: Value, pending: &PendingRequests, connection: &Weak<Connection>) {
+    if let Some(method) = message.get("method").and_then(Value::as_str) {
+        if method == "notifications/tools/list_changed" {
+            refresh_tools(name, connection);
+        } else {
+            // Other notification or server-initiated request
+            debug!("Ignoring message from '{}': {}", name, message);
+        }
+        return;
+    }
+
+    let response: JsonRpcResponse = match serde_json::from_value(message) {
+        Ok(response) => response,
+        Err(e) => {
+            debug!("Skipping malformed JSON-RPC response from '{}': {}", name, e);
+            return;
+        }
+    };
+
+    let id = if response.id.is_null() {
+        Some(NULL_ID)
+    } else {
+        response.id.as_i64()
+    };
+    let sender = id.and_then(|id| pending.lock().unwrap().remove(&id));
+
+    match sender {
+        Some(sender) => {
+            let _ = sender.send(response);
+        }
+        None => debug!("Dropping response with unknown id from '{}'", name),
     }
 }
 
+/// Re-list the server's tools in the background; the reader must keep
+/// running to deliver the `tools/list` response
+fn refresh_tools(name: &str, connection: &Weak<Connection>) {
+    let Some(connection) = connection.upgrade() else {
+        return;
+    };
+
+    info!("MCP server '{}' tool list changed, refreshing", name);
+    tokio::spawn(async move {
+        if let Err(e) = connection.list_tools().await {
+            warn!(
+                "Failed to refresh tools from MCP server '{}': {}",
+                connection.name, e
+            );
+        }
+    });
+}
+
 impl Drop for McpClient {
     fn drop(&mut self) {
-        debug!("Shutting down MCP server '{}'", self.name);
-        let _ = self.process.kill();
-        let _ = self.process.wait();
+        debug!("Shutting down MCP server '{}'", self.connection.name);
+        self.reader.abort();
+        // Fail requests from any in-flight tool refresh
+        self.connection.pending.lock().unwrap().clear();
+
+        #[cfg(unix)]
+        if let Some(pid) = self.process.id() {
+            // The group id is the server's pid (see `process_group(0)`)
+            unsafe {
+                libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+            }
+        }
+
+        let _ = self.process.start_kill();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INITIALIZE_RESULT: &str = r#"{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05","capabilities":{},"serverInfo":{"name":"fake","version":"1.0"}}}"#;
+
+    const TOOLS_RESULT: &str = r#"{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"echo","description":"Echo","inputSchema":{"type":"object"}}]}}"#;
+
+    /// Start a fake server that runs `script` under `sh`
+    fn fake_server(script: &str) -> McpClient {
+        McpClient::start(
+            "fake".to_string(),
+            "sh".to_string(),
+            vec!["-c".to_string(), script.to_string()],
+            HashMap::new(),
+            true,
+            Duration::from_secs(5),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn missing_command_names_the_server_and_command() {
+        let error = McpClient::start(
+            "files".to_string(),
+            "ollm-test-no-such-command".to_string(),
+            Vec::new(),
+            HashMap::new(),
+            true,
+            Duration::from_secs(5),
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "MCP error: Failed to start MCP server 'files': command `ollm-test-no-such-command` not found on PATH; is it installed?"
+        );
+    }
+
+    /// What a server started with `inherit_env` sees of a variable set in
+    /// this process and of one set in its config
+    async fn server_env(inherit_env: bool) -> String {
+        std::env::set_var("OLLM_TEST_PARENT_VAR", "parent");
+        let out = std::env::temp_dir().join(format!("ollm-env-{}", uuid::Uuid::new_v4()));
+        let script = format!(
+            "echo \"${{OLLM_TEST_PARENT_VAR:-unset}} $CONFIGURED\" > {}.tmp && mv {0}.tmp {0}",
+            out.display()
+        );
+        let _client = McpClient::start(
+            "fake".to_string(),
+            "sh".to_string(),
+            vec!["-c".to_string(), script],
+            HashMap::from([("CONFIGURED".to_string(), "configured".to_string())]),
+            inherit_env,
+            Duration::from_secs(5),
+        )
+        .unwrap();
+
+        for _ in 0..100 {
+            if let Ok(seen) = std::fs::read_to_string(&out) {
+                let _ = std::fs::remove_file(&out);
+                return seen.trim().to_string();
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("server didn't write its environment");
+    }
+
+    #[tokio::test]
+    async fn parent_environment_is_hidden_without_inherit_env() {
+        assert_eq!(server_env(false).await, "unset configured");
+        assert_eq!(server_env(true).await, "parent configured");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn dropping_the_client_kills_the_servers_children() {
+        // A zombie has exited and only waits to be reaped by its parent
+        fn running(pid: libc::pid_t) -> bool {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+            let alive = unsafe { libc::kill(pid, 0) == 0 };
+            alive && !stat.contains(") Z ")
+        }
+
+        let pid_file =
+            std::env::temp_dir().join(format!("ollm-grandchild-{}", uuid::Uuid::new_v4()));
+        let client = fake_server(&format!(
+            "sleep 30 & echo $! > {}; cat >/dev/null",
+            pid_file.display()
+        ));
+
+        let mut pid = None;
+        for _ in 0..50 {
+            if let Some(p) = std::fs::read_to_string(&pid_file)
+                .ok()
+                .and_then(|s| s.trim().parse::<libc::pid_t>().ok())
+            {
+                pid = Some(p);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let _ = std::fs::remove_file(&pid_file);
+        let pid = pid.expect("server didn't start its child");
+        assert!(running(pid));
+
+        drop(client);
+
+        for _ in 0..50 {
+            if !running(pid) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("grandchild {} outlived the server", pid);
+    }
+
+    /// Shell functions for a fake server speaking `Content-Length` framing:
+    /// `frame` reads one framed message, `reply` writes one
+    const FRAMING: &str = r#"frame() { read -r header; read -r _; dd bs=1 count="$(echo "$header" | tr -dc 0-9)" >/dev/null 2>&1; }; reply() { printf 'Content-Length: %d\r\n\r\n%s' "${#1}" "$1"; }; "#;
+
+    #[tokio::test]
+    async fn both_framings_are_read() {
+        let body = r#"{"jsonrpc":"2.0","id":1,"result":{}}"#;
+        let input = format!(
+            "Content-Length: {}\r\nContent-Type: application/json\r\n\r\n{}{}\n",
+            body.len(),
+            body,
+            body
+        );
+        let mut reader = input.as_bytes();
+
+        let framed = read_message(&mut reader).await.unwrap().unwrap();
+        assert_eq!(framed.body, body);
+        assert!(framed.content_length);
+
+        let line = read_message(&mut reader).await.unwrap().unwrap();
+        assert_eq!(line.body.trim_end(), body);
+        assert!(!line.content_length);
+
+        assert!(read_message(&mut reader).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn configured_framing_is_written_from_the_start() {
+        // Every request, starting with initialize, must arrive framed for
+        // the server to answer it
+        let mut client = fake_server(&format!(
+            "{}frame; reply '{}'; frame; frame; reply '{}'; frame; reply '{}'; cat >/dev/null",
+            FRAMING,
+            INITIALIZE_RESULT,
+            TOOLS_RESULT,
+            r#"{"jsonrpc":"2.0","id":3,"result":{"content":[{"type":"text","text":"framed"}]}}"#
+        ));
+        client.set_content_length_framing(true);
+        client.initialize().await.unwrap();
+
+        let content = client.call_tool("echo", None).await.unwrap();
+        assert_eq!(content, vec![ToolResultContent::text("framed")]);
+    }
+
+    #[tokio::test]
+    async fn framing_detected_on_read_is_used_for_writes() {
+        // initialize arrives as a line; once the server answers with a
+        // framed message, everything after it must be framed too
+        let mut client = fake_server(&format!(
+            "{}read -r _; reply '{}'; frame; frame; reply '{}'; frame; reply '{}'; cat >/dev/null",
+            FRAMING,
+            INITIALIZE_RESULT,
+            TOOLS_RESULT,
+            r#"{"jsonrpc":"2.0","id":3,"result":{"content":[{"type":"text","text":"framed"}]}}"#
+        ));
+        client.initialize().await.unwrap();
+        assert_eq!(client.get_tools()[0].name, "fake::echo");
+
+        let content = client.call_tool("echo", None).await.unwrap();
+        assert_eq!(content, vec![ToolResultContent::text("framed")]);
+    }
+
+    #[tokio::test]
+    async fn non_json_preamble_is_skipped() {
+        // Answer initialize, then (after the initialized notification)
+        // tools/list, printing a banner first like `npx` does
+        let mut client = fake_server(&format!(
+            "echo 'npm WARN exec The following package was not found'; \
+             read -r _; echo '{}'; read -r _; read -r _; echo '{}'; cat >/dev/null",
+            INITIALIZE_RESULT, TOOLS_RESULT
+        ));
+
+        client.initialize().await.unwrap();
+        assert_eq!(client.server_info().unwrap().name, "fake");
+        assert_eq!(client.get_tools()[0].name, "fake::echo");
+    }
+
+    #[tokio::test]
+    async fn mixed_text_and_image_result_is_kept_whole() {
+        let mut client = fake_server(&format!(
+            "read -r _; echo '{}'; read -r _; read -r _; echo '{}'; read -r _; \
+             echo '{}'; cat >/dev/null",
+            INITIALIZE_RESULT,
+            TOOLS_RESULT,
+            r#"{"jsonrpc":"2.0","id":3,"result":{"content":[{"type":"text","text":"A chart"},{"type":"image","data":"iVBORw0=","mimeType":"image/png"}]}}"#
+        ));
+        client.initialize().await.unwrap();
+
+        let content = client.call_tool("echo", None).await.unwrap();
+        assert_eq!(
+            content,
+            vec![
+                ToolResultContent::text("A chart"),
+                ToolResultContent::Image {
+                    data: "iVBORw0=".to_string(),
+                    media_type: "image/png".to_string(),
+                },
+            ]
+        );
+        assert_eq!(ToolResultContent::joined_text(&content), "A chart");
+    }
+
+    /// A fake server that answers one `tools/call` with the `arguments` it
+    /// received: `{}`, absent, or something else
+    fn arguments_echo_server() -> McpClient {
+        let result = |text: &str| {
+            format!(
+                r#"{{"jsonrpc":"2.0","id":3,"result":{{"content":[{{"type":"text","text":"{}"}}]}}}}"#,
+                text
+            )
+        };
+        fake_server(&format!(
+            r#"read -r _; echo '{}'; read -r _; read -r _; echo '{}'; read -r call; case "$call" in *'"arguments":{{}}'*) echo '{}';; *'"arguments"'*) echo '{}';; *) echo '{}';; esac; cat >/dev/null"#,
+            INITIALIZE_RESULT,
+            TOOLS_RESULT,
+            result("empty"),
+            result("other"),
+            result("absent")
+        ))
+    }
+
+    #[tokio::test]
+    async fn missing_arguments_are_sent_as_an_empty_object() {
+        for arguments in [None, Some(Value::Null)] {
+            let mut client = arguments_echo_server();
+            client.initialize().await.unwrap();
+
+            let content = client.call_tool("echo", arguments).await.unwrap();
+            assert_eq!(content, vec![ToolResultContent::text("empty")]);
+        }
+
+        let mut client = arguments_echo_server();
+        client.initialize().await.unwrap();
+        let content = client.call_tool("echo", Some(json!({ "text": "hi" }))).await.unwrap();
+        assert_eq!(content, vec![ToolResultContent::text("other")]);
+    }
+
+    #[tokio::test]
+    async fn empty_arguments_can_be_omitted() {
+        let mut client = arguments_echo_server();
+        client.set_omit_empty_arguments(true);
+        client.initialize().await.unwrap();
+
+        let content = client.call_tool("echo", None).await.unwrap();
+        assert_eq!(content, vec![ToolResultContent::text("absent")]);
+    }
+
+    /// Script prefix that answers initialize and tools/list (ids 1 and 2),
+    /// leaving the next request id at 3
+    fn initialized(script: &str) -> String {
+        format!(
+            "read -r _; echo '{}'; read -r _; read -r _; echo '{}'; {}; cat >/dev/null",
+            INITIALIZE_RESULT, TOOLS_RESULT, script
+        )
+    }
+
+    fn values(results: Vec<Result<Value>>) -> Vec<Value> {
+        results.into_iter().map(Result::unwrap).collect()
+    }
+
+    #[tokio::test]
+    async fn batch_results_come_back_in_request_order() {
+        // One line carries the whole batch; the answers arrive reversed
+        let mut client = fake_server(&initialized(
+            r#"read -r _; echo '[{"jsonrpc":"2.0","id":4,"result":{"n":"second"}},{"jsonrpc":"2.0","id":3,"result":{"n":"first"}}]'"#,
+        ));
+        client.initialize().await.unwrap();
+
+        let results = client.send_batch(&[("resources/list", None), ("prompts/list", None)]).await;
+        assert_eq!(values(results), [json!({ "n": "first" }), json!({ "n": "second" })]);
+    }
+
+    #[tokio::test]
+    async fn rejected_batch_is_sent_one_request_at_a_time() {
+        let mut client = fake_server(&initialized(
+            r#"read -r _; echo '{"jsonrpc":"2.0","id":null,"error":{"code":-32600,"message":"Batches not supported"}}'; read -r _; echo '{"jsonrpc":"2.0","id":5,"result":{"n":"first"}}'; read -r _; echo '{"jsonrpc":"2.0","id":6,"result":{"n":"second"}}'"#,
+        ));
+        client.initialize().await.unwrap();
+
+        let results = client.send_batch(&[("resources/list", None), ("prompts/list", None)]).await;
+        assert_eq!(values(results), [json!({ "n": "first" }), json!({ "n": "second" })]);
     }
 }
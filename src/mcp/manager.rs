@@ -1,60 +1,206 @@
 use super::client::McpClient;
+use super::types::Implementation;
 use crate::config::McpServerConfig;
 use crate::error::{OllmError, Result};
-use crate::types::Tool;
+use crate::retry::{retry_async_if, BackoffPolicy};
+use crate::types::{Tool, ToolResultContent};
+use futures::StreamExt;
+use serde::Serialize;
 use serde_json::Value;
-use std::collections::HashMap;
-use tracing::{error, info};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Request timeout for servers started before `start_servers` sets one
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Outcome of starting the configured MCP servers
+#[derive(Debug, Clone, Default)]
+pub struct McpStartupSummary {
+    /// Servers that started and initialized
+    pub started: Vec<String>,
+    /// Servers that failed, with the error message
+    pub failed: Vec<(String, String)>,
+}
+
+/// Snapshot of one running MCP server, from `McpManager::describe`
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerDescription {
+    pub name: String,
+    /// Name and version the server reported at initialize
+    pub server_info: Option<Implementation>,
+    pub tools: Vec<ToolDescription>,
+    /// False once a request to the server has timed out
+    pub healthy: bool,
+}
+
+/// A tool as named by its server (without the `server::` prefix)
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolDescription {
+    pub name: String,
+    pub description: String,
+    pub input_schema: Value,
+}
 
 /// Manages multiple MCP server connections
+///
+/// Servers are kept sorted by name, so tools and server lists come out in
+/// the same order on every run.
 pub struct McpManager {
-    clients: HashMap<String, McpClient>,
+    clients: BTreeMap<String, McpClient>,
+    /// Config of each server passed to `start_servers`, for restarts
+    configs: HashMap<String, McpServerConfig>,
+    request_timeout: Duration,
+    enabled: bool,
 }
 
 impl McpManager {
     /// Create a new MCP manager
     pub fn new() -> Self {
         Self {
-            clients: HashMap::new(),
+            clients: BTreeMap::new(),
+            configs: HashMap::new(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            enabled: true,
         }
     }
 
+    /// A manager that never starts servers, so it has no tools
+    pub fn disabled() -> Self {
+        let mut manager = Self::new();
+        manager.enabled = false;
+        manager
+    }
+
+    /// False for a `disabled()` manager
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
     /// Start and initialize MCP servers from configuration
-    pub fn start_servers(&mut self, configs: Vec<McpServerConfig>) -> Result<()> {
-        info!("Starting {} MCP servers...", configs.len());
-
-        for config in configs {
-            info!("Starting MCP server: {}", config.name);
-
-            match McpClient::start(
-                config.name.clone(),
-                config.command.clone(),
-                config.args.clone(),
-                config.env.clone(),
-            ) {
-                Ok(mut client) => {
-                    info!("MCP server '{}' process started, initializing...", config.name);
-                    if let Err(e) = client.initialize() {
-                        error!("Failed to initialize MCP server '{}': {}", config.name, e);
-                        eprintln!("❌ Failed to initialize MCP server '{}': {}", config.name, e);
-                        continue;
-                    }
-                    info!("MCP server '{}' initialized successfully", config.name);
-                    self.clients.insert(config.name, client);
+    ///
+    /// Up to `concurrency` servers start at once; the summary lists them in
+    /// config order regardless of which finished first. Failed servers are
+    /// skipped and reported in the summary, unless `fail_fast` is set, in
+    /// which case the first failure is returned. Requests to the servers fail
+    /// after `request_timeout`. A disabled manager starts nothing.
+    pub async fn start_servers(
+        &mut self,
+        configs: Vec<McpServerConfig>,
+        fail_fast: bool,
+        request_timeout: Duration,
+        concurrency: usize,
+    ) -> Result<McpStartupSummary> {
+        if !self.enabled {
+            info!("MCP is disabled; not starting {} configured servers", configs.len());
+            return Ok(McpStartupSummary::default());
+        }
+
+        info!(
+            "Starting {} MCP servers ({} at a time)...",
+            configs.len(),
+            concurrency.max(1)
+        );
+
+        self.request_timeout = request_timeout;
+        self.configs.extend(configs.iter().map(|c| (c.name.clone(), c.clone())));
+
+        let mut results = futures::stream::iter(configs)
+            .map(|config| async move {
+                let result = Self::start_server(&config, request_timeout).await;
+                (config.name, result)
+            })
+            .buffered(concurrency.max(1));
+
+        let mut summary = McpStartupSummary::default();
+
+        while let Some((name, result)) = results.next().await {
+            match result {
+                Ok(client) => {
+                    info!("MCP server '{}' initialized successfully", name);
+                    self.clients.insert(name.clone(), client);
+                    summary.started.push(name);
                 }
                 Err(e) => {
-                    error!("Failed to start MCP server '{}': {}", config.name, e);
-                    eprintln!("❌ Failed to start MCP server '{}': {}", config.name, e);
-                    continue;
+                    error!("Failed to start MCP server '{}': {}", name, e);
+                    if fail_fast {
+                        return Err(OllmError::Mcp(format!(
+                            "MCP server '{}' failed to start: {}",
+                            name, e
+                        )));
+                    }
+                    summary.failed.push((name, e.to_string()));
                 }
             }
         }
 
         info!("Successfully started {} MCP servers", self.clients.len());
 
+        Ok(summary)
+    }
+
+    async fn start_server(config: &McpServerConfig, request_timeout: Duration) -> Result<McpClient> {
+        info!("Starting MCP server: {}", config.name);
+
+        let mut client = McpClient::start(
+            config.name.clone(),
+            config.command.clone(),
+            config.args.clone(),
+            config.env.clone(),
+            config.inherit_env,
+            request_timeout,
+        )?;
+        client.set_omit_empty_arguments(config.omit_empty_arguments);
+        client.set_content_length_framing(config.content_length_framing);
+
+        info!("MCP server '{}' process started, initializing...", config.name);
+        client.initialize().await?;
+        Ok(client)
+    }
+
+    /// Stop `name` (if running) and start it again from its config,
+    /// retrying failed starts under `policy`
+    ///
+    /// For servers listed by `unhealthy_servers` after a request timed out.
+    pub async fn restart_server(&mut self, name: &str, policy: &BackoffPolicy) -> Result<()> {
+        let config = self
+            .configs
+            .get(name)
+            .cloned()
+            .ok_or_else(|| OllmError::Mcp(format!("Unknown MCP server: {}", name)))?;
+
+        if let Some(client) = self.clients.remove(name) {
+            info!("Stopping MCP server '{}' for restart", name);
+            drop(client);
+        }
+
+        let request_timeout = self.request_timeout;
+        let client = retry_async_if(
+            policy,
+            |e, attempt, delay| {
+                warn!(
+                    "Restarting MCP server '{}' failed ({}), retrying in {:?} ({}/{})",
+                    name, e, delay, attempt, policy.max_retries
+                );
+                true
+            },
+            || Self::start_server(&config, request_timeout),
+        )
+        .await?;
+
+        info!("MCP server '{}' restarted", name);
+        self.clients.insert(name.to_string(), client);
         Ok(())
     }
 
+    /// Stop every server, killing each one's whole process group
+    pub fn shutdown_all(&mut self) {
+        for (name, client) in std::mem::take(&mut self.clients) {
+            info!("Stopping MCP server '{}'", name);
+            drop(client);
+        }
+    }
+
     /// Get all available tools from all MCP servers
     pub fn get_all_tools(&self) -> Vec<Tool> {
         self.clients
@@ -64,7 +210,14 @@ impl McpManager {
     }
 
     /// Call a tool on the appropriate MCP server
-    pub fn call_tool(&mut self, tool_name: &str, arguments: Option<Value>) -> Result<String> {
+    ///
+    /// Takes `&self` so the manager can be shared (e.g. behind `Arc`) and
+    /// tools on different servers called concurrently.
+    pub async fn call_tool(
+        &self,
+        tool_name: &str,
+        arguments: Option<Value>,
+    ) -> Result<Vec<ToolResultContent>> {
         // Tool name format: "server_name::tool_name"
         let parts: Vec<&str> = tool_name.split("::").collect();
 
@@ -78,11 +231,11 @@ impl McpManager {
         let server_name = parts[0];
         let actual_tool_name = parts[1];
 
-        let client = self.clients.get_mut(server_name).ok_or_else(|| {
+        let client = self.clients.get(server_name).ok_or_else(|| {
             OllmError::Mcp(format!("MCP server '{}' not found", server_name))
         })?;
 
-        client.call_tool(actual_tool_name, arguments)
+        client.call_tool(actual_tool_name, arguments).await
     }
 
     /// Get number of connected servers
@@ -94,6 +247,49 @@ impl McpManager {
     pub fn server_names(&self) -> Vec<String> {
         self.clients.keys().cloned().collect()
     }
+
+    /// Describe each server and its tools, sorted by server name
+    pub fn describe(&self) -> Vec<ServerDescription> {
+        self.clients
+            .iter()
+            .map(|(name, client)| ServerDescription {
+                name: name.clone(),
+                server_info: client.server_info().cloned(),
+                tools: client
+                    .server_tools()
+                    .into_iter()
+                    .map(|tool| ToolDescription {
+                        name: tool.name,
+                        description: tool.description,
+                        input_schema: tool.input_schema,
+                    })
+                    .collect(),
+                healthy: client.is_healthy(),
+            })
+            .collect()
+    }
+
+    /// Servers with a timed-out request, which may need restarting
+    pub fn unhealthy_servers(&self) -> Vec<String> {
+        self.clients
+            .iter()
+            .filter(|(_, client)| !client.is_healthy())
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Restart every server listed by `unhealthy_servers` under `policy`,
+    /// returning those that couldn't be restarted (they stay stopped)
+    pub async fn restart_unhealthy(&mut self, policy: &BackoffPolicy) -> Vec<(String, OllmError)> {
+        let mut failed = Vec::new();
+        for name in self.unhealthy_servers() {
+            if let Err(e) = self.restart_server(&name, policy).await {
+                error!("Failed to restart MCP server '{}': {}", name, e);
+                failed.push((name, e));
+            }
+        }
+        failed
+    }
 }
 
 impl Default for McpManager {
@@ -101,3 +297,293 @@ impl Default for McpManager {
         Self::new()
     }
 }
+
+impl Drop for McpManager {
+    fn drop(&mut self) {
+        self.shutdown_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    const INITIALIZE: &str = r#"{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2024-11-05","capabilities":{},"serverInfo":{"name":"fake","version":"1.0"}}}"#;
+
+    /// Config for a fake server named `name` that runs `script` under `sh`
+    fn server(name: &str, script: &str) -> McpServerConfig {
+        McpServerConfig {
+            name: name.to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            env: HashMap::new(),
+            inherit_env: true,
+            omit_empty_arguments: false,
+            content_length_framing: false,
+        }
+    }
+
+    /// Config for a fake server that waits `delay` before answering
+    /// `initialize`, then offers one tool named after the server
+    fn delayed_server(name: &str, delay: &str) -> McpServerConfig {
+        let script = format!(
+            r#"
+            read -r _
+            sleep {delay}
+            echo '{INITIALIZE}'
+            read -r _
+            read -r _
+            echo '{{"jsonrpc":"2.0","id":2,"result":{{"tools":[{{"name":"{name}","description":"","inputSchema":{{"type":"object"}}}}]}}}}'
+            cat >/dev/null
+            "#
+        );
+        server(name, &script)
+    }
+
+    /// Config for a fake server with one tool, `slow`, that answers each
+    /// call after a second
+    fn slow_server(name: &str) -> McpServerConfig {
+        let script = format!(
+            r#"
+            read -r _
+            echo '{INITIALIZE}'
+            read -r _
+            read -r _
+            echo '{{"jsonrpc":"2.0","id":2,"result":{{"tools":[{{"name":"slow","description":"Slow","inputSchema":{{"type":"object"}}}}]}}}}'
+            read -r _
+            sleep 1
+            echo '{{"jsonrpc":"2.0","id":3,"result":{{"content":[{{"type":"text","text":"done"}}]}}}}'
+            cat >/dev/null
+            "#
+        );
+        server(name, &script)
+    }
+
+    #[tokio::test]
+    async fn servers_start_concurrently_up_to_the_limit() {
+        let servers = || ["a", "b", "c", "d"].map(|name| delayed_server(name, "0.5")).to_vec();
+
+        let started = Instant::now();
+        let mut manager = McpManager::new();
+        manager
+            .start_servers(servers(), true, Duration::from_secs(5), 2)
+            .await
+            .unwrap();
+        // Two at a time: two rounds of half a second, not four
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(1000), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(1800), "{:?}", elapsed);
+
+        let started = Instant::now();
+        let mut manager = McpManager::new();
+        manager
+            .start_servers(servers(), true, Duration::from_secs(5), 4)
+            .await
+            .unwrap();
+        let elapsed = started.elapsed();
+        assert!(elapsed < Duration::from_millis(1000), "{:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn servers_and_tools_come_out_in_name_order() {
+        // Later servers finish starting first
+        let configs = vec![
+            delayed_server("c", "0.3"),
+            delayed_server("a", "0.2"),
+            delayed_server("d", "0"),
+            delayed_server("b", "0.1"),
+        ];
+        let mut manager = McpManager::new();
+        manager
+            .start_servers(configs, true, Duration::from_secs(5), 4)
+            .await
+            .unwrap();
+
+        assert_eq!(manager.server_names(), ["a", "b", "c", "d"]);
+        let tools: Vec<String> = manager.get_all_tools().into_iter().map(|t| t.name).collect();
+        assert_eq!(tools, ["a::a", "b::b", "c::c", "d::d"]);
+    }
+
+    #[tokio::test]
+    async fn failed_server_is_skipped_or_fails_fast() {
+        let configs = || {
+            vec![
+                delayed_server("b", "0"),
+                server("broken", "exit 1"),
+                delayed_server("a", "0"),
+            ]
+        };
+
+        let mut manager = McpManager::new();
+        let summary = manager
+            .start_servers(configs(), false, Duration::from_secs(5), 3)
+            .await
+            .unwrap();
+        // The summary keeps config order
+        assert_eq!(summary.started, ["b", "a"]);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].0, "broken");
+        assert_eq!(manager.server_names(), ["a", "b"]);
+
+        let mut manager = McpManager::new();
+        let error = manager
+            .start_servers(configs(), true, Duration::from_secs(5), 3)
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("MCP server 'broken' failed to start"),
+            "{}",
+            error
+        );
+    }
+
+    /// Config for a fake server reporting `info` as its serverInfo and
+    /// offering `tools` (name, description pairs)
+    fn described_server(name: &str, info: &str, tools: &[(&str, &str)]) -> McpServerConfig {
+        let tools: Vec<String> = tools
+            .iter()
+            .map(|(tool, description)| {
+                format!(
+                    r#"{{"name":"{tool}","description":"{description}","inputSchema":{{"type":"object"}}}}"#
+                )
+            })
+            .collect();
+        let script = format!(
+            r#"
+            read -r _
+            echo '{{"jsonrpc":"2.0","id":1,"result":{{"protocolVersion":"2024-11-05","capabilities":{{}},"serverInfo":{info}}}}}'
+            read -r _
+            read -r _
+            echo '{{"jsonrpc":"2.0","id":2,"result":{{"tools":[{}]}}}}'
+            cat >/dev/null
+            "#,
+            tools.join(",")
+        );
+        server(name, &script)
+    }
+
+    #[tokio::test]
+    async fn description_groups_tools_under_their_server() {
+        let mut manager = McpManager::new();
+        manager
+            .start_servers(
+                vec![
+                    described_server(
+                        "git",
+                        r#"{"name":"git-mcp","version":"0.3.1"}"#,
+                        &[("status", "Show status"), ("log", "Show history")],
+                    ),
+                    described_server(
+                        "files",
+                        r#"{"name":"fs","version":"2.0.0"}"#,
+                        &[("read", "Read a file")],
+                    ),
+                ],
+                true,
+                Duration::from_secs(5),
+                2,
+            )
+            .await
+            .unwrap();
+
+        let servers = manager.describe();
+        let summary: Vec<(&str, String, String, Vec<&str>)> = servers
+            .iter()
+            .map(|server| {
+                let info = server.server_info.as_ref().unwrap();
+                (
+                    server.name.as_str(),
+                    info.name.clone(),
+                    info.version.clone(),
+                    server.tools.iter().map(|tool| tool.name.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("files", "fs".to_string(), "2.0.0".to_string(), vec!["read"]),
+                (
+                    "git",
+                    "git-mcp".to_string(),
+                    "0.3.1".to_string(),
+                    vec!["status", "log"]
+                ),
+            ]
+        );
+        assert_eq!(servers[1].tools[1].description, "Show history");
+        assert!(servers.iter().all(|server| server.healthy));
+    }
+
+    #[tokio::test]
+    async fn shared_manager_calls_tools_concurrently() {
+        let mut manager = McpManager::new();
+        manager
+            .start_servers(
+                vec![slow_server("a"), slow_server("b")],
+                true,
+                Duration::from_secs(5),
+                2,
+            )
+            .await
+            .unwrap();
+        let manager = Arc::new(manager);
+
+        let started = Instant::now();
+        let calls: Vec<_> = ["a::slow", "b::slow"]
+            .into_iter()
+            .map(|tool| {
+                let manager = manager.clone();
+                tokio::spawn(async move { manager.call_tool(tool, None).await })
+            })
+            .collect();
+        for call in calls {
+            let content = call.await.unwrap().unwrap();
+            assert_eq!(ToolResultContent::joined_text(&content), "done");
+        }
+
+        // Each call takes a second, so one after the other would take two
+        assert!(started.elapsed() < Duration::from_millis(1800));
+    }
+
+    #[tokio::test]
+    async fn timed_out_server_is_restarted() {
+        let mut manager = McpManager::new();
+        manager
+            .start_servers(vec![slow_server("a")], true, Duration::from_millis(200), 1)
+            .await
+            .unwrap();
+
+        assert!(manager.call_tool("a::slow", None).await.is_err());
+        assert_eq!(manager.unhealthy_servers(), ["a"]);
+
+        let failed = manager.restart_unhealthy(&BackoffPolicy::new(0)).await;
+        assert!(failed.is_empty());
+        assert!(manager.unhealthy_servers().is_empty());
+        assert_eq!(manager.get_all_tools()[0].name, "a::slow");
+    }
+
+    #[tokio::test]
+    async fn descriptions_are_cut_only_in_provider_tools() {
+        use crate::llm::{tools::to_provider_tools, ToolFormat};
+
+        let mut manager = McpManager::new();
+        manager
+            .start_servers(vec![slow_server("a")], true, Duration::from_secs(5), 1)
+            .await
+            .unwrap();
+        let tools = manager.get_all_tools();
+
+        let anthropic = to_provider_tools(ToolFormat::Anthropic, &tools, Some(3));
+        assert_eq!(anthropic[0]["description"], "Sl…");
+        let openai = to_provider_tools(ToolFormat::OpenAi, &tools, Some(3));
+        assert_eq!(openai[0]["function"]["description"], "Sl…");
+        let untouched = to_provider_tools(ToolFormat::Ollama, &tools, None);
+        assert_eq!(untouched[0]["function"]["description"], "Slow");
+
+        // list-tools reads the full description from the manager
+        assert_eq!(manager.get_all_tools()[0].description, "Slow");
+    }
+}
@@ -3,5 +3,5 @@ pub mod manager;
 pub mod types;
 
 pub use client::McpClient;
-pub use manager::McpManager;
+pub use manager::{McpManager, McpStartupSummary, ServerDescription, ToolDescription};
 pub use types::*;
@@ -152,11 +152,23 @@ pub struct CallToolResult {
     pub is_error: Option<bool>,
 }
 
-/// Tool content (text or image/resource)
+/// Tool content (text, image, audio, or resource)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum ToolContent {
     Text { text: String },
-    Image { data: String, mime_type: String },
+    Image {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    Audio {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
     Resource { resource: Value },
+    /// A content type this client doesn't know
+    #[serde(other)]
+    Unsupported,
 }
@@ -0,0 +1,178 @@
+use crate::error::{OllmError, Result};
+use rand::Rng;
+use std::future::Future;
+use std::time::Duration;
+
+/// How often and how long to wait between attempts of a failing operation
+///
+/// Retry `n` (1-based) waits `initial_delay * multiplier^(n-1)`, capped at
+/// `max_delay`. With `jitter`, each delay is scaled by a random factor in
+/// `[0.5, 1.0]` so concurrent clients don't retry in lockstep.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackoffPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    /// Attempts after the first; 0 never retries
+    pub max_retries: u32,
+    pub jitter: bool,
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            max_retries: 2,
+            jitter: true,
+        }
+    }
+}
+
+impl BackoffPolicy {
+    /// The default policy with `max_retries` retries
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before retry `attempt` (1-based)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let secs = self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent);
+        let delay = Duration::from_secs_f64(secs.min(self.max_delay.as_secs_f64()));
+
+        if self.jitter {
+            delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+        } else {
+            delay
+        }
+    }
+}
+
+/// Run `op` until it succeeds, retrying errors that `is_retryable()` under
+/// `policy`
+pub async fn retry_async<T, F, Fut>(policy: &BackoffPolicy, op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    retry_async_if(policy, |e, _, _| e.is_retryable(), op).await
+}
+
+/// Run `op` until it succeeds, `should_retry` declines the error, or the
+/// policy's retries are used up, sleeping between attempts
+///
+/// `should_retry` gets the error, the upcoming retry number (1-based), and
+/// the delay before it, so callers can log the retry.
+pub async fn retry_async_if<T, F, Fut, P>(
+    policy: &BackoffPolicy,
+    mut should_retry: P,
+    mut op: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+    P: FnMut(&OllmError, u32, Duration) -> bool,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                attempt += 1;
+                if attempt > policy.max_retries {
+                    return Err(e);
+                }
+                let delay = policy.delay(attempt);
+                if !should_retry(&e, attempt, delay) {
+                    return Err(e);
+                }
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn policy(max_retries: u32) -> BackoffPolicy {
+        BackoffPolicy {
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            multiplier: 2.0,
+            max_retries,
+            jitter: false,
+        }
+    }
+
+    #[test]
+    fn delay_doubles_up_to_the_cap() {
+        let delays: Vec<u128> = (1..=5).map(|n| policy(5).delay(n).as_millis()).collect();
+        assert_eq!(delays, [1, 2, 4, 5, 5]);
+    }
+
+    #[test]
+    fn jitter_scales_the_delay_down_by_at_most_half() {
+        let policy = BackoffPolicy {
+            jitter: true,
+            ..BackoffPolicy::default()
+        };
+        for _ in 0..20 {
+            let delay = policy.delay(2);
+            assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_secs(1));
+        }
+    }
+
+    #[tokio::test]
+    async fn failing_operation_is_tried_once_plus_max_retries() {
+        let calls = AtomicU32::new(0);
+        let mut retries = Vec::new();
+
+        let result: Result<()> = retry_async_if(
+            &policy(3),
+            |_, attempt, delay| {
+                retries.push((attempt, delay.as_millis()));
+                true
+            },
+            || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(OllmError::Timeout(30))
+            },
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert_eq!(retries, [(1, 1), (2, 2), (3, 4)]);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_on_success_or_a_declined_error() {
+        let calls = AtomicU32::new(0);
+        let result = retry_async(&policy(3), || async {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(OllmError::Timeout(30)),
+                _ => Ok("ok"),
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), "ok");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let calls = AtomicU32::new(0);
+        let result: Result<()> = retry_async(&policy(3), || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(OllmError::Config("bad".to_string()))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::llm::templates::message_text;
+use crate::types::{ContentBlock, Role, Session};
+
+/// Render a session as a Markdown transcript, one `##` section per turn
+/// headed by the speaker's name
+///
+/// Tool calls are listed under the assistant turn that made them; messages
+/// carrying only tool results are skipped.
+pub fn to_markdown(session: &Session, user_name: &str, assistant_name: &str) -> String {
+    let mut out = format!(
+        "# {}\n\n- Session: `{}`\n- Model: {} ({})\n- Created: {}\n",
+        session.title.as_deref().unwrap_or("Untitled session"),
+        session.id,
+        session.model,
+        session.provider,
+        session.created_at.format("%Y-%m-%d %H:%M"),
+    );
+
+    for message in &session.messages {
+        let text = message_text(message);
+        let tool_calls: Vec<String> = message
+            .content
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse { name, input, .. } => {
+                    Some(format!("- 🔧 `{}` `{}`", name, input))
+                }
+                _ => None,
+            })
+            .collect();
+
+        if text.trim().is_empty() && tool_calls.is_empty() {
+            continue;
+        }
+
+        let speaker = match message.role {
+            Role::User => user_name,
+            Role::Assistant => assistant_name,
+            Role::System => "system",
+        };
+        out.push_str(&format!("\n## {}\n\n", speaker));
+        if !text.trim().is_empty() {
+            out.push_str(text.trim_end());
+            out.push('\n');
+        }
+        if !tool_calls.is_empty() {
+            if !text.trim().is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&tool_calls.join("\n"));
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Message;
+    use serde_json::json;
+
+    #[test]
+    fn turns_are_headed_by_the_configured_names() {
+        let mut session = Session::new("ollama".to_string(), "llama3".to_string());
+        session.title = Some("Reading files".to_string());
+        session.add_message(Message::new_user("What's in a.txt?".to_string()));
+        session.add_message(Message::new_assistant(vec![ContentBlock::ToolUse {
+            id: "call_1".to_string(),
+            name: "fs::read_file".to_string(),
+            input: json!({ "path": "a.txt" }),
+        }]));
+        session.add_message(Message::new_tool_results(vec![ContentBlock::ToolResult {
+            tool_use_id: "call_1".to_string(),
+            content: vec![crate::types::ToolResultContent::text("hello")],
+            is_error: None,
+        }]));
+        session.add_message(Message::new_assistant(vec![ContentBlock::Text {
+            text: "It says hello.".to_string(),
+        }]));
+
+        let markdown = to_markdown(&session, "Ada", "Babbage");
+
+        assert!(markdown.starts_with("# Reading files\n"));
+        assert!(markdown.contains("\n## Ada\n\nWhat's in a.txt?\n"));
+        assert!(markdown.contains("\n## Babbage\n\n- 🔧 `fs::read_file` `{\"path\":\"a.txt\"}`\n"));
+        assert!(markdown.ends_with("\n## Babbage\n\nIt says hello.\n"));
+        // The tool-result turn has no section of its own
+        assert_eq!(markdown.matches("\n## ").count(), 3);
+        assert!(!markdown.contains("## user") && !markdown.contains("## assistant"));
+    }
+}
@@ -0,0 +1,11 @@
+pub mod export;
+#[cfg(feature = "opensearch")]
+pub mod opensearch;
+pub mod store;
+pub mod title;
+
+pub use export::to_markdown;
+#[cfg(feature = "opensearch")]
+pub use opensearch::{OpenSearchStore, SemanticHit};
+pub use store::{FileSessionStore, SessionStore};
+pub use title::ensure_title;
@@ -0,0 +1,620 @@
+use super::SessionStore;
+use crate::config::Config;
+use crate::error::{OllmError, Result};
+use crate::llm::embeddings::{mean_vector, Embedder};
+use crate::llm::templates::message_text;
+use crate::types::Session;
+use async_trait::async_trait;
+use opensearch::auth::Credentials;
+use opensearch::http::headers::{HeaderMap, HeaderName, HeaderValue};
+use opensearch::http::response::Response;
+use opensearch::http::transport::{SingleNodeConnectionPool, TransportBuilder};
+use opensearch::http::{StatusCode, Url};
+use opensearch::indices::{IndicesCreateParts, IndicesExistsParts};
+use opensearch::{DeleteParts, GetParts, IndexParts, OpenSearch, SearchParts, UpdateParts};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{debug, info};
+
+/// Maximum sessions returned by `list`
+const LIST_LIMIT: usize = 1000;
+
+/// A session matched by `search_semantic`, with its kNN similarity score
+#[derive(Debug, Clone)]
+pub struct SemanticHit {
+    pub session: Session,
+    pub score: f32,
+}
+
+/// Stores sessions as documents in an OpenSearch index, optionally with an
+/// embedding vector for semantic search
+pub struct OpenSearchStore {
+    client: OpenSearch,
+    endpoint: String,
+    index: String,
+    password_env: String,
+    /// Embedding size for the index mapping
+    dimensions: usize,
+    /// Set once the index is known to exist
+    index_ready: AtomicBool,
+    embedder: Option<Box<dyn Embedder>>,
+}
+
+impl OpenSearchStore {
+    pub fn new(config: &Config) -> Result<Self> {
+        let url = Url::parse(&config.opensearch.endpoint).map_err(|e| {
+            OllmError::Config(format!(
+                "Invalid OpenSearch endpoint '{}': {}",
+                config.opensearch.endpoint, e
+            ))
+        })?;
+
+        let password = std::env::var(&config.opensearch.password_env).map_err(|_| {
+            OllmError::Config(format!(
+                "Environment variable {} not set",
+                config.opensearch.password_env
+            ))
+        })?;
+
+        // The opensearch crate has its own `http` version, so copy the shared
+        // headers over by bytes
+        let mut headers = HeaderMap::new();
+        for (name, value) in &crate::llm::http::default_headers(config, &["authorization"])? {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_str().as_bytes()),
+                HeaderValue::from_bytes(value.as_bytes()),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+
+        let transport = TransportBuilder::new(SingleNodeConnectionPool::new(url))
+            .auth(Credentials::Basic(config.opensearch.username.clone(), password))
+            .headers(headers)
+            .build()
+            .map_err(|e| OllmError::OpenSearch(format!("Failed to build client: {}", e)))?;
+
+        Ok(Self {
+            client: OpenSearch::new(transport),
+            endpoint: config.opensearch.endpoint.clone(),
+            index: config.opensearch.index.clone(),
+            password_env: config.opensearch.password_env.clone(),
+            dimensions: config.embeddings.dimensions,
+            index_ready: AtomicBool::new(false),
+            embedder: None,
+        })
+    }
+
+    /// Use `embedder` for `index_with_embedding` and `search_semantic`
+    pub fn with_embedder(mut self, embedder: Box<dyn Embedder>) -> Self {
+        self.dimensions = embedder.dimensions();
+        self.embedder = Some(embedder);
+        self
+    }
+
+    fn embedder(&self) -> Result<&dyn Embedder> {
+        self.embedder.as_deref().ok_or_else(|| {
+            OllmError::OpenSearch("Semantic search requires an embedder".to_string())
+        })
+    }
+
+    /// Create the sessions index with a kNN vector mapping if it's missing
+    pub async fn ensure_index(&self, dimensions: usize) -> Result<()> {
+        if self.index_ready.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let exists = self
+            .client
+            .indices()
+            .exists(IndicesExistsParts::Index(&[&self.index]))
+            .send()
+            .await
+            .map_err(|e| self.transport_error(e))?;
+
+        if exists.status_code() == StatusCode::OK {
+            self.index_ready.store(true, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        info!("Creating OpenSearch index '{}'", self.index);
+
+        let response = self
+            .client
+            .indices()
+            .create(IndicesCreateParts::Index(&self.index))
+            .body(json!({
+                "settings": { "index.knn": true },
+                "mappings": {
+                    "properties": {
+                        "id": { "type": "keyword" },
+                        "title": { "type": "text" },
+                        "text": { "type": "text" },
+                        "created_at": { "type": "date" },
+                        "updated_at": { "type": "date" },
+                        "provider": { "type": "keyword" },
+                        "model": { "type": "keyword" },
+                        // Tool inputs are arbitrary JSON - store, don't index
+                        "messages": { "type": "object", "enabled": false },
+                        "metadata": { "type": "object", "enabled": false },
+                        "embedding": { "type": "knn_vector", "dimension": dimensions },
+                    }
+                }
+            }))
+            .send()
+            .await
+            .map_err(|e| self.transport_error(e))?;
+
+        self.check_status(response, "create index").await?;
+        self.index_ready.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Save `session` with an embedding of its messages for semantic search
+    ///
+    /// All message texts are embedded in batched requests and averaged into
+    /// one vector per session.
+    pub async fn index_with_embedding(&self, session: &Session) -> Result<()> {
+        let embedder = self.embedder()?;
+        self.ensure_index(self.dimensions).await?;
+
+        let texts: Vec<String> = session
+            .messages
+            .iter()
+            .map(message_text)
+            .filter(|text| !text.trim().is_empty())
+            .collect();
+
+        let mut document = session_document(session)?;
+        if !texts.is_empty() {
+            let vectors = embedder.embed(texts).await?;
+            document["embedding"] = json!(mean_vector(&vectors));
+        }
+
+        self.put(&session.id, document).await
+    }
+
+    /// Find the `k` sessions closest in meaning to `query`
+    pub async fn search_semantic(&self, query: &str, k: usize) -> Result<Vec<SemanticHit>> {
+        let vector = self
+            .embedder()?
+            .embed(vec![query.to_string()])
+            .await?
+            .pop()
+            .unwrap_or_default();
+
+        let hits = self
+            .search(json!({
+                "size": k,
+                "query": { "knn": { "embedding": { "vector": vector, "k": k } } },
+                "_source": { "excludes": ["embedding"] },
+            }))
+            .await?;
+
+        Ok(hits
+            .into_iter()
+            .map(|hit| SemanticHit {
+                session: hit._source,
+                score: hit._score.unwrap_or(0.0),
+            })
+            .collect())
+    }
+
+    async fn put(&self, id: &str, document: Value) -> Result<()> {
+        let response = self
+            .client
+            .index(IndexParts::IndexId(&self.index, id))
+            .body(document)
+            .send()
+            .await
+            .map_err(|e| self.transport_error(e))?;
+
+        self.check_status(response, "index session").await?;
+        debug!("Saved session {} to index '{}'", id, self.index);
+        Ok(())
+    }
+
+    /// Create the document or update the fields it has, leaving any others
+    /// (the `embedding`) in place
+    async fn upsert(&self, id: &str, document: Value) -> Result<()> {
+        let response = self
+            .client
+            .update(UpdateParts::IndexId(&self.index, id))
+            .body(json!({ "doc": document, "doc_as_upsert": true }))
+            .send()
+            .await
+            .map_err(|e| self.transport_error(e))?;
+
+        self.check_status(response, "update session").await?;
+        debug!("Saved session {} to index '{}'", id, self.index);
+        Ok(())
+    }
+
+    async fn search(&self, body: Value) -> Result<Vec<SearchHit>> {
+        let response = self
+            .client
+            .search(SearchParts::Index(&[&self.index]))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| self.transport_error(e))?;
+
+        // Nothing has been saved yet
+        if response.status_code() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+
+        let response: SearchResponse = self
+            .check_status(response, "search sessions")
+            .await?
+            .json()
+            .await
+            .map_err(|e| OllmError::OpenSearch(format!("Failed to parse search response: {}", e)))?;
+
+        Ok(response.hits.hits)
+    }
+
+    /// Describe a request that got no HTTP response
+    fn transport_error(&self, error: opensearch::Error) -> OllmError {
+        if error.is_timeout() {
+            return OllmError::OpenSearch(format!(
+                "Timed out talking to OpenSearch at {}: {}",
+                self.endpoint, error
+            ));
+        }
+        OllmError::OpenSearch(format!(
+            "Cannot connect to OpenSearch at {} (check opensearch.endpoint and that the \
+             server is running): {}",
+            self.endpoint, error
+        ))
+    }
+
+    /// Turn a non-success response into an error naming the likely fix
+    async fn check_status(&self, response: Response, action: &str) -> Result<Response> {
+        let status = response.status_code();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        let message = match status {
+            StatusCode::UNAUTHORIZED => format!(
+                "authentication failed (401); check opensearch.username and the password in {}",
+                self.password_env
+            ),
+            StatusCode::FORBIDDEN => format!(
+                "permission denied (403) on index '{}'; check the user's roles",
+                self.index
+            ),
+            StatusCode::NOT_FOUND if body.contains("index_not_found_exception") => {
+                // The index may have been deleted since we checked
+                self.index_ready.store(false, Ordering::Relaxed);
+                format!("index '{}' not found (404)", self.index)
+            }
+            StatusCode::BAD_REQUEST if is_mapping_conflict(&body) => format!(
+                "mapping conflict in index '{}' (400); it was created with an incompatible \
+                 mapping, so delete or reindex it: {}",
+                self.index,
+                error_reason(&body)
+            ),
+            _ => format!("{} {}", status, error_reason(&body)),
+        };
+
+        Err(OllmError::OpenSearch(format!("Failed to {}: {}", action, message)))
+    }
+}
+
+#[async_trait]
+impl SessionStore for OpenSearchStore {
+    /// Re-embeds the session when an embedder is set; otherwise updates the
+    /// stored document in place, so an earlier `index_with_embedding` vector
+    /// survives
+    async fn save(&self, session: &Session) -> Result<()> {
+        if self.embedder.is_some() {
+            return self.index_with_embedding(session).await;
+        }
+
+        self.ensure_index(self.dimensions).await?;
+        self.upsert(&session.id, session_document(session)?).await
+    }
+
+    async fn load(&self, id: &str) -> Result<Session> {
+        let response = self
+            .client
+            .get(GetParts::IndexId(&self.index, id))
+            .send()
+            .await
+            .map_err(|e| self.transport_error(e))?;
+
+        if response.status_code() == StatusCode::NOT_FOUND {
+            return Err(OllmError::Session(format!("Session {} not found", id)));
+        }
+
+        let hit: SearchHit = self.check_status(response, "load session")
+            .await?
+            .json()
+            .await
+            .map_err(|e| OllmError::Session(format!("Failed to parse session {}: {}", id, e)))?;
+
+        Ok(hit._source)
+    }
+
+    async fn list(&self) -> Result<Vec<Session>> {
+        let hits = self
+            .search(json!({
+                "size": LIST_LIMIT,
+                "query": { "match_all": {} },
+                "sort": [{ "updated_at": "desc" }],
+                "_source": { "excludes": ["embedding"] },
+            }))
+            .await?;
+
+        Ok(hits.into_iter().map(|hit| hit._source).collect())
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        let response = self
+            .client
+            .delete(DeleteParts::IndexId(&self.index, id))
+            .send()
+            .await
+            .map_err(|e| self.transport_error(e))?;
+
+        if response.status_code() == StatusCode::NOT_FOUND {
+            return Err(OllmError::Session(format!("Session {} not found", id)));
+        }
+
+        self.check_status(response, "delete session").await?;
+        Ok(())
+    }
+}
+
+/// Session JSON plus a flattened `text` field for full-text search
+fn session_document(session: &Session) -> Result<Value> {
+    let mut document = serde_json::to_value(session)?;
+    document["text"] = json!(session
+        .messages
+        .iter()
+        .map(message_text)
+        .collect::<Vec<_>>()
+        .join("\n"));
+    Ok(document)
+}
+
+/// Whether an error body reports a field mapping that doesn't fit the document
+fn is_mapping_conflict(body: &str) -> bool {
+    [
+        "mapper_parsing_exception",
+        "illegal_argument_exception",
+        "strict_dynamic_mapping_exception",
+    ]
+    .iter()
+    .any(|kind| body.contains(kind))
+}
+
+/// The `error.reason` from an OpenSearch error body, or the raw body
+fn error_reason(body: &str) -> String {
+    serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|v| v["error"]["reason"].as_str().map(str::to_string))
+        .unwrap_or_else(|| body.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    hits: SearchHits,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchHits {
+    hits: Vec<SearchHit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchHit {
+    #[serde(default)]
+    _score: Option<f32>,
+    _source: Session,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Message;
+    use opensearch::indices::{IndicesDeleteParts, IndicesRefreshParts};
+
+    /// Three-dimensional vectors from keyword counts, so nearby texts are
+    /// predictable without an embeddings server
+    struct KeywordEmbedder;
+
+    #[async_trait]
+    impl Embedder for KeywordEmbedder {
+        async fn embed(&self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>> {
+            Ok(inputs
+                .iter()
+                .map(|text| {
+                    let text = text.to_lowercase();
+                    vec![
+                        text.matches("rust").count() as f32,
+                        text.matches("python").count() as f32,
+                        1.0,
+                    ]
+                })
+                .collect())
+        }
+
+        fn dimensions(&self) -> usize {
+            3
+        }
+    }
+
+    /// A store on a fresh index of the instance at `OPENSEARCH_URL`
+    /// (default `http://localhost:9200`), logging in as `admin` with
+    /// `OPENSEARCH_PASSWORD`
+    fn local_store() -> OpenSearchStore {
+        let mut config: Config = toml::from_str(&Config::example()).unwrap();
+        config.opensearch.endpoint = std::env::var("OPENSEARCH_URL")
+            .unwrap_or_else(|_| "http://localhost:9200".to_string());
+        config.opensearch.username = "admin".to_string();
+        config.opensearch.password_env = "OPENSEARCH_PASSWORD".to_string();
+        config.opensearch.index = format!("ollm-test-{}", uuid::Uuid::new_v4());
+        OpenSearchStore::new(&config).unwrap()
+    }
+
+    /// A store on the `sessions` index of a mock server
+    fn mock_store(server: &mockito::Server) -> OpenSearchStore {
+        std::env::set_var("OLLM_TEST_OPENSEARCH_PASSWORD", "secret");
+        let mut config: Config = toml::from_str(&Config::example()).unwrap();
+        config.opensearch.endpoint = server.url();
+        config.opensearch.password_env = "OLLM_TEST_OPENSEARCH_PASSWORD".to_string();
+        config.opensearch.index = "sessions".to_string();
+        config.embeddings.dimensions = 3;
+        OpenSearchStore::new(&config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn first_save_creates_the_index() {
+        let mut server = mockito::Server::new_async().await;
+        let exists = server
+            .mock("HEAD", "/sessions")
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+        let create = server
+            .mock("PUT", "/sessions")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "settings": { "index.knn": true },
+                "mappings": {
+                    "properties": { "embedding": { "type": "knn_vector", "dimension": 3 } }
+                },
+            })))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"acknowledged":true,"shards_acknowledged":true,"index":"sessions"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let store = mock_store(&server);
+        let mut session = Session::new("mock".to_string(), "mock".to_string());
+        session.add_message(Message::new_user("Hello".to_string()));
+        let update = server
+            .mock("POST", format!("/sessions/_update/{}", session.id).as_str())
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "doc": { "id": session.id, "text": "Hello" },
+                "doc_as_upsert": true,
+            })))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"_index":"sessions","result":"created"}"#)
+            .expect(2)
+            .create_async()
+            .await;
+
+        // The second save knows the index exists
+        store.save(&session).await.unwrap();
+        store.save(&session).await.unwrap();
+
+        exists.assert_async().await;
+        create.assert_async().await;
+        update.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn rejected_credentials_name_the_password_variable() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("HEAD", "/sessions")
+            .with_status(401)
+            .create_async()
+            .await;
+        server
+            .mock("PUT", "/sessions")
+            .with_status(401)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"error":{"reason":"Unauthorized"},"status":401}"#)
+            .create_async()
+            .await;
+
+        let session = Session::new("mock".to_string(), "mock".to_string());
+        let error = mock_store(&server).save(&session).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "OpenSearch error: Failed to create index: authentication failed (401); check \
+             opensearch.username and the password in OLLM_TEST_OPENSEARCH_PASSWORD"
+        );
+    }
+
+    async fn refresh(store: &OpenSearchStore) {
+        store
+            .client
+            .indices()
+            .refresh(IndicesRefreshParts::Index(&[&store.index]))
+            .send()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a local OpenSearch instance (OPENSEARCH_URL, OPENSEARCH_PASSWORD)"]
+    async fn semantic_search_round_trip() {
+        let store = local_store().with_embedder(Box::new(KeywordEmbedder));
+
+        let mut rust = Session::new("mock".to_string(), "mock".to_string());
+        rust.add_message(Message::new_user("How do I borrow in Rust?".to_string()));
+        let mut python = Session::new("mock".to_string(), "mock".to_string());
+        python.add_message(Message::new_user("Python list comprehensions".to_string()));
+
+        store.index_with_embedding(&rust).await.unwrap();
+        store.index_with_embedding(&python).await.unwrap();
+
+        // A later save (e.g. after the next REPL turn) keeps it searchable
+        rust.add_message(Message::new_user("And lifetimes in rust?".to_string()));
+        store.save(&rust).await.unwrap();
+        refresh(&store).await;
+
+        let hits = store.search_semantic("rust rust", 1).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].session.id, rust.id);
+        assert_eq!(hits[0].session.messages.len(), 2);
+
+        let loaded = store.load(&python.id).await.unwrap();
+        assert_eq!(loaded.messages[0].id, python.messages[0].id);
+
+        store
+            .client
+            .indices()
+            .delete(IndicesDeleteParts::Index(&[&store.index]))
+            .send()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a local OpenSearch instance (OPENSEARCH_URL, OPENSEARCH_PASSWORD)"]
+    async fn save_without_embedder_keeps_the_stored_embedding() {
+        let embedded = local_store().with_embedder(Box::new(KeywordEmbedder));
+        let mut session = Session::new("mock".to_string(), "mock".to_string());
+        session.add_message(Message::new_user("rust".to_string()));
+        embedded.index_with_embedding(&session).await.unwrap();
+
+        // Same index, but no embedder: save updates the document in place
+        let mut plain = local_store();
+        plain.index = embedded.index.clone();
+        session.title = Some("Rust".to_string());
+        plain.save(&session).await.unwrap();
+        refresh(&embedded).await;
+
+        let hits = embedded.search_semantic("rust", 1).await.unwrap();
+        assert_eq!(hits[0].session.id, session.id);
+        assert_eq!(hits[0].session.title.as_deref(), Some("Rust"));
+
+        embedded
+            .client
+            .indices()
+            .delete(IndicesDeleteParts::Index(&[&embedded.index]))
+            .send()
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+use crate::error::{OllmError, Result};
+use crate::types::Session;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+/// Persistence backend for sessions
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    /// Create or overwrite a session
+    async fn save(&self, session: &Session) -> Result<()>;
+
+    /// Load a session by id
+    async fn load(&self, id: &str) -> Result<Session>;
+
+    /// List all sessions, most recently updated first
+    async fn list(&self) -> Result<Vec<Session>>;
+
+    /// Delete a session by id
+    async fn delete(&self, id: &str) -> Result<()>;
+
+    /// Delete every session last updated before `before`, returning how many
+    /// were deleted
+    async fn prune(&self, before: DateTime<Utc>) -> Result<usize> {
+        let mut deleted = 0;
+        for session in self.list().await? {
+            if session.updated_at < before {
+                self.delete(&session.id).await?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+/// Stores each session as a JSON file in a directory
+pub struct FileSessionStore {
+    dir: PathBuf,
+}
+
+impl FileSessionStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Default store location: `<data dir>/open-llm-code/sessions`
+    pub fn default_dir() -> PathBuf {
+        let mut p = dirs::data_dir().expect("Cannot determine data directory");
+        p.push("open-llm-code");
+        p.push("sessions");
+        p
+    }
+
+    /// File for session `id`; ids that could name a file outside the
+    /// store directory are rejected
+    fn path_for(&self, id: &str) -> Result<PathBuf> {
+        if id.is_empty() || id.contains(['/', '\\']) || id.contains("..") {
+            return Err(OllmError::Session(format!("Invalid session id '{}'", id)));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+}
+
+#[async_trait]
+impl SessionStore for FileSessionStore {
+    async fn save(&self, session: &Session) -> Result<()> {
+        let path = self.path_for(&session.id)?;
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        let json = serde_json::to_string_pretty(session)?;
+        tokio::fs::write(path, json).await?;
+
+        debug!("Saved session {} to {}", session.id, self.dir.display());
+        Ok(())
+    }
+
+    async fn load(&self, id: &str) -> Result<Session> {
+        let path = self.path_for(id)?;
+        let json = tokio::fs::read_to_string(&path).await.map_err(|e| {
+            OllmError::Session(format!("Failed to read session {}: {}", id, e))
+        })?;
+
+        serde_json::from_str(&json)
+            .map_err(|e| OllmError::Session(format!("Failed to parse session {}: {}", id, e)))
+    }
+
+    async fn list(&self) -> Result<Vec<Session>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut sessions = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.dir).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+
+            let json = tokio::fs::read_to_string(&path).await?;
+            match serde_json::from_str::<Session>(&json) {
+                Ok(session) => sessions.push(session),
+                Err(e) => warn!("Skipping unreadable session file {}: {}", path.display(), e),
+            }
+        }
+
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        Ok(sessions)
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        tokio::fs::remove_file(self.path_for(id)?).await.map_err(|e| {
+            OllmError::Session(format!("Failed to delete session {}: {}", id, e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn temp_store() -> FileSessionStore {
+        FileSessionStore::new(
+            std::env::temp_dir().join(format!("ollm-sessions-{}", uuid::Uuid::new_v4())),
+        )
+    }
+
+    fn session(id: &str, updated_at: DateTime<Utc>) -> Session {
+        let mut session = Session::new("mock".to_string(), "mock".to_string());
+        session.id = id.to_string();
+        session.updated_at = updated_at;
+        session
+    }
+
+    #[tokio::test]
+    async fn ids_that_leave_the_store_directory_are_rejected() {
+        let store = temp_store();
+        for id in ["../escape", "a/b", "a\\b", "..", "/etc/passwd", ""] {
+            assert!(
+                matches!(store.load(id).await, Err(OllmError::Session(e)) if e.contains("Invalid")),
+                "{:?}",
+                id
+            );
+            assert!(store.delete(id).await.is_err(), "{:?}", id);
+            assert!(
+                store.save(&session(id, Utc::now())).await.is_err(),
+                "{:?}",
+                id
+            );
+        }
+        assert!(!store.dir.exists());
+    }
+
+    #[tokio::test]
+    async fn prune_keeps_sessions_updated_at_the_cutoff() {
+        let store = temp_store();
+        let cutoff = Utc::now();
+        store
+            .save(&session("before", cutoff - Duration::milliseconds(1)))
+            .await
+            .unwrap();
+        store.save(&session("at", cutoff)).await.unwrap();
+        store
+            .save(&session("after", cutoff + Duration::seconds(1)))
+            .await
+            .unwrap();
+
+        assert_eq!(store.prune(cutoff).await.unwrap(), 1);
+        let mut left: Vec<String> = store
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        left.sort();
+        assert_eq!(left, ["after", "at"]);
+
+        // Nothing is older now, so a second prune deletes nothing
+        assert_eq!(store.prune(cutoff).await.unwrap(), 0);
+        std::fs::remove_dir_all(&store.dir).unwrap();
+    }
+}
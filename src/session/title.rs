@@ -0,0 +1,59 @@
+use crate::error::Result;
+use crate::llm::LlmProvider;
+use crate::types::{ContentBlock, Message, Role, Session};
+use tracing::debug;
+
+/// Longest title kept from the model's suggestion
+const MAX_TITLE_CHARS: usize = 60;
+
+const TITLE_PROMPT: &str = "Write a short title (at most 6 words) summarizing this conversation. \
+Reply with the title only, no quotes or punctuation at the end.";
+
+/// Generate a title for the session from its opening exchange
+///
+/// Does nothing if the session already has a title (so the request is only
+/// made once) or if there is no complete user/assistant exchange yet.
+pub async fn ensure_title(session: &mut Session, provider: &dyn LlmProvider) -> Result<()> {
+    if session.title.is_some() {
+        return Ok(());
+    }
+
+    let (Some(user), Some(assistant)) = (
+        first_text(session, Role::User),
+        first_text(session, Role::Assistant),
+    ) else {
+        return Ok(());
+    };
+
+    let messages = vec![
+        Message::new_system(TITLE_PROMPT.to_string()),
+        Message::new_user(format!("User: {}\n\nAssistant: {}", user, assistant)),
+    ];
+
+    let response = provider.chat(messages, vec![]).await?;
+    // Only the first line, in case the model explains itself
+    let text = response.text();
+    let title = text.trim().lines().next().unwrap_or("");
+    let title = title.trim().trim_matches('"').trim();
+
+    if !title.is_empty() {
+        let title: String = title.chars().take(MAX_TITLE_CHARS).collect();
+        debug!("Generated title for session {}: {}", session.id, title);
+        session.title = Some(title);
+    }
+
+    Ok(())
+}
+
+fn first_text(session: &Session, role: Role) -> Option<String> {
+    session
+        .messages
+        .iter()
+        .find(|m| m.role == role)
+        .and_then(|m| {
+            m.content.iter().find_map(|c| match c {
+                ContentBlock::Text { text } => Some(text.clone()),
+                _ => None,
+            })
+        })
+}
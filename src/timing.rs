@@ -0,0 +1,137 @@
+use crate::format;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Wall-clock timing for a single request/turn
+#[derive(Debug, Clone)]
+pub struct Timing {
+    start: Instant,
+    first_token: Option<Duration>,
+    total: Option<Duration>,
+}
+
+impl Timing {
+    /// Start timing now
+    pub fn start() -> Self {
+        Self {
+            start: Instant::now(),
+            first_token: None,
+            total: None,
+        }
+    }
+
+    /// Record the first streamed token (later calls are ignored)
+    pub fn mark_first_token(&mut self) {
+        if self.first_token.is_none() {
+            self.first_token = Some(self.start.elapsed());
+        }
+    }
+
+    /// Record the end of the request
+    pub fn finish(&mut self) {
+        self.total = Some(self.start.elapsed());
+    }
+
+    /// Time to first token, if streaming was used
+    pub fn time_to_first_token(&self) -> Option<Duration> {
+        self.first_token
+    }
+
+    /// Total duration (elapsed so far if not finished)
+    pub fn total(&self) -> Duration {
+        self.total.unwrap_or_else(|| self.start.elapsed())
+    }
+
+    /// Output tokens per second over the total duration
+    pub fn tokens_per_second(&self, output_tokens: usize) -> Option<f64> {
+        let secs = self.total().as_secs_f64();
+        if secs > 0.0 {
+            Some(output_tokens as f64 / secs)
+        } else {
+            None
+        }
+    }
+
+    /// One-line human-readable summary
+    pub fn summary(&self, output_tokens: usize) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(ttft) = self.time_to_first_token() {
+            parts.push(format!("first token {:.2}s", ttft.as_secs_f64()));
+        }
+
+        parts.push(format!("total {:.2}s", self.total().as_secs_f64()));
+
+        if let Some(tps) = self.tokens_per_second(output_tokens) {
+            parts.push(format!("{} tokens/s", format::decimal(tps, 1)));
+        }
+
+        parts.join(", ")
+    }
+}
+
+/// Latency and throughput across repeated runs of the same request
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkStats {
+    pub runs: usize,
+    pub min_secs: f64,
+    pub max_secs: f64,
+    pub mean_secs: f64,
+    pub p50_secs: f64,
+    pub p95_secs: f64,
+    /// Mean of each run's output tokens per second
+    pub mean_tokens_per_second: f64,
+}
+
+impl BenchmarkStats {
+    /// Aggregate finished runs, given with each run's output token count
+    ///
+    /// Returns `None` when there are no runs.
+    pub fn from_runs(runs: &[(Timing, usize)]) -> Option<Self> {
+        if runs.is_empty() {
+            return None;
+        }
+
+        let mut latencies: Vec<f64> = runs.iter().map(|(t, _)| t.total().as_secs_f64()).collect();
+        latencies.sort_by(f64::total_cmp);
+
+        // Nearest-rank percentile over the sorted latencies
+        let percentile = |p: f64| {
+            let rank = (p * latencies.len() as f64).ceil() as usize;
+            latencies[rank.clamp(1, latencies.len()) - 1]
+        };
+
+        let throughputs: Vec<f64> = runs
+            .iter()
+            .filter_map(|(t, tokens)| t.tokens_per_second(*tokens))
+            .collect();
+
+        Some(Self {
+            runs: runs.len(),
+            min_secs: latencies[0],
+            max_secs: latencies[latencies.len() - 1],
+            mean_secs: latencies.iter().sum::<f64>() / latencies.len() as f64,
+            p50_secs: percentile(0.50),
+            p95_secs: percentile(0.95),
+            mean_tokens_per_second: if throughputs.is_empty() {
+                0.0
+            } else {
+                throughputs.iter().sum::<f64>() / throughputs.len() as f64
+            },
+        })
+    }
+
+    /// Multi-line human-readable summary
+    pub fn summary(&self) -> String {
+        format!(
+            "Runs: {}\nLatency: min {:.2}s, max {:.2}s, mean {:.2}s, p50 {:.2}s, p95 {:.2}s\nThroughput: {} tokens/s (mean)",
+            format::thousands(self.runs),
+            self.min_secs,
+            self.max_secs,
+            self.mean_secs,
+            self.p50_secs,
+            self.p95_secs,
+            format::decimal(self.mean_tokens_per_second, 1)
+        )
+    }
+}
@@ -0,0 +1,416 @@
+use crate::config::ToolsConfig;
+use crate::mcp::McpManager;
+use crate::types::{ContentBlock, Tool, ToolResultContent};
+use serde_json::Value;
+use tracing::{debug, warn};
+
+/// Dispatches model tool-use requests to MCP servers and builds the
+/// `ToolResult` blocks fed back to the model
+pub struct ToolExecutor {
+    tools: Vec<Tool>,
+    validate_input: bool,
+    max_result_chars: usize,
+}
+
+impl ToolExecutor {
+    pub fn new(tools: Vec<Tool>, config: &ToolsConfig) -> Self {
+        Self {
+            tools,
+            validate_input: config.validate_input,
+            max_result_chars: config.max_result_chars,
+        }
+    }
+
+    /// Tools available to the model
+    pub fn tools(&self) -> &[Tool] {
+        &self.tools
+    }
+
+    /// The same executor offering `tools` instead
+    pub fn with_tools(&self, tools: Vec<Tool>) -> Self {
+        Self { tools, ..*self }
+    }
+
+    /// Execute a `ContentBlock::ToolUse`, returning the matching
+    /// `ContentBlock::ToolResult` (or `None` for any other block)
+    pub async fn execute(&self, mcp: &McpManager, block: &ContentBlock) -> Option<ContentBlock> {
+        let ContentBlock::ToolUse { id, name, input } = block else {
+            return None;
+        };
+
+        if self.validate_input {
+            if let Some(tool) = self.tools.iter().find(|t| &t.name == name) {
+                if let Err(e) = validate_input(tool, input) {
+                    warn!("Tool '{}' input failed validation: {}", name, e);
+                    return Some(tool_result(id, vec![ToolResultContent::text(e)], true));
+                }
+            }
+        }
+
+        debug!("Dispatching tool '{}'", name);
+
+        Some(match mcp.call_tool(name, Some(input.clone())).await {
+            Ok(content) => tool_result(id, self.truncate(name, content), false),
+            Err(e) => tool_result(id, vec![ToolResultContent::text(e.to_string())], true),
+        })
+    }
+
+    /// Truncate the combined text of a tool result to `max_result_chars`,
+    /// keeping its head and tail as `truncate_middle` does for one text.
+    /// Text parts that fall entirely in the cut are dropped; other parts
+    /// (images) are kept as they are.
+    fn truncate(&self, name: &str, content: Vec<ToolResultContent>) -> Vec<ToolResultContent> {
+        let total: usize = content
+            .iter()
+            .filter_map(ToolResultContent::as_text)
+            .map(|text| text.chars().count())
+            .sum();
+        if total <= self.max_result_chars {
+            return content;
+        }
+
+        warn!(
+            "Tool '{}' result truncated from {} to {} chars",
+            name, total, self.max_result_chars
+        );
+
+        // Offsets into the combined text: the head ends at `head_end` and
+        // the tail starts at `tail_start`
+        let head_end = self.max_result_chars / 2;
+        let tail_start = total - (self.max_result_chars - head_end);
+        let mut offset = 0;
+        let mut marked = false;
+
+        content
+            .into_iter()
+            .filter_map(|part| {
+                let ToolResultContent::Text { text } = part else {
+                    return Some(part);
+                };
+
+                let len = text.chars().count();
+                let start = offset;
+                offset += len;
+                let head_len = head_end.saturating_sub(start).min(len);
+                let tail_from = tail_start.saturating_sub(start).min(len);
+                if head_len >= tail_from {
+                    return Some(ToolResultContent::Text { text });
+                }
+
+                let head: String = text.chars().take(head_len).collect();
+                let tail: String = text.chars().skip(tail_from).collect();
+                if !marked {
+                    marked = true;
+                    let omitted = total - self.max_result_chars;
+                    return Some(ToolResultContent::text(format!(
+                        "{}\n[truncated {} chars]\n{}",
+                        head, omitted, tail
+                    )));
+                }
+                (!tail.is_empty()).then(|| ToolResultContent::text(tail))
+            })
+            .collect()
+    }
+}
+
+/// Keep the head and tail of `text` (by chars) around a truncation marker
+pub fn truncate_middle(text: &str, max_chars: usize) -> String {
+    let total = text.chars().count();
+    if total <= max_chars {
+        return text.to_string();
+    }
+
+    let omitted = total - max_chars;
+    let head_len = max_chars / 2;
+    let tail_len = max_chars - head_len;
+
+    let head: String = text.chars().take(head_len).collect();
+    let tail: String = text.chars().skip(total - tail_len).collect();
+
+    format!("{}\n[truncated {} chars]\n{}", head, omitted, tail)
+}
+
+/// Cut `text` to `max_chars` chars, ending in an ellipsis
+pub fn truncate_end(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let mut cut: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+/// Validate tool input against the tool's declared `input_schema`
+pub fn validate_input(tool: &Tool, input: &Value) -> std::result::Result<(), String> {
+    let validator = match jsonschema::validator_for(&tool.input_schema) {
+        Ok(validator) => validator,
+        Err(e) => {
+            // A broken schema is the server's problem, not the model's
+            warn!("Tool '{}' has an invalid input schema: {}", tool.name, e);
+            return Ok(());
+        }
+    };
+
+    let errors: Vec<String> = validator
+        .iter_errors(input)
+        .map(|e| {
+            let path = e.instance_path.to_string();
+            if path.is_empty() {
+                e.to_string()
+            } else {
+                format!("{}: {}", path, e)
+            }
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid input for tool '{}': {}",
+            tool.name,
+            errors.join("; ")
+        ))
+    }
+}
+
+/// One line per argument in a tool's `input_schema`, required arguments
+/// first, e.g. `path: string (required)`
+///
+/// Schemas without `properties` (including ones that aren't objects) have
+/// no arguments to list and yield no lines.
+pub fn summarize_schema(schema: &Value) -> Vec<String> {
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    let mut names: Vec<&String> = properties.keys().collect();
+    names.sort_by_key(|name| !required.contains(&name.as_str()));
+
+    names
+        .into_iter()
+        .map(|name| {
+            let mut line = format!("{}: {}", name, schema_type(&properties[name]));
+            if required.contains(&name.as_str()) {
+                line.push_str(" (required)");
+            }
+            line
+        })
+        .collect()
+}
+
+/// Short type name for a property schema, e.g. `string`, `"a" | "b"`,
+/// `array of integer`
+fn schema_type(schema: &Value) -> String {
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        return values
+            .iter()
+            .map(Value::to_string)
+            .collect::<Vec<_>>()
+            .join(" | ");
+    }
+
+    for key in ["anyOf", "oneOf"] {
+        if let Some(options) = schema.get(key).and_then(Value::as_array) {
+            return options.iter().map(schema_type).collect::<Vec<_>>().join(" | ");
+        }
+    }
+
+    match schema.get("type") {
+        Some(Value::String(kind)) if kind == "array" => match schema.get("items") {
+            Some(items) => format!("array of {}", schema_type(items)),
+            None => "array".to_string(),
+        },
+        Some(Value::String(kind)) => kind.clone(),
+        Some(Value::Array(kinds)) => kinds
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" | "),
+        _ => "any".to_string(),
+    }
+}
+
+fn tool_result(tool_use_id: &str, content: Vec<ToolResultContent>, is_error: bool) -> ContentBlock {
+    ContentBlock::ToolResult {
+        tool_use_id: tool_use_id.to_string(),
+        content,
+        is_error: if is_error { Some(true) } else { None },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn read_file_tool() -> Tool {
+        Tool {
+            name: "fs::read_file".to_string(),
+            description: "Read a file".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": { "path": { "type": "string" } },
+                "required": ["path"],
+            }),
+        }
+    }
+
+    #[test]
+    fn missing_required_field_fails_validation() {
+        let tool = read_file_tool();
+        assert!(validate_input(&tool, &json!({ "path": "src/main.rs" })).is_ok());
+
+        let error = validate_input(&tool, &json!({})).unwrap_err();
+        assert!(error.starts_with("Invalid input for tool 'fs::read_file'"));
+        assert!(error.contains("\"path\" is a required property"));
+    }
+
+    #[tokio::test]
+    async fn invalid_input_becomes_an_error_result_without_dispatch() {
+        let executor = ToolExecutor::new(vec![read_file_tool()], &ToolsConfig::default());
+        let block = ContentBlock::ToolUse {
+            id: "call_1".to_string(),
+            name: "fs::read_file".to_string(),
+            input: json!({}),
+        };
+
+        // No servers are running, so a dispatched call would fail differently
+        let result = executor.execute(&McpManager::new(), &block).await;
+        let Some(ContentBlock::ToolResult {
+            tool_use_id,
+            content,
+            is_error,
+        }) = result
+        else {
+            panic!("expected a tool result");
+        };
+        assert_eq!(tool_use_id, "call_1");
+        assert_eq!(is_error, Some(true));
+        assert!(ToolResultContent::joined_text(&content).contains("required property"));
+    }
+
+    #[test]
+    fn oversized_result_keeps_head_and_tail() {
+        let config = ToolsConfig {
+            max_result_chars: 10,
+            ..ToolsConfig::default()
+        };
+        let executor = ToolExecutor::new(Vec::new(), &config);
+        let text = format!("HEAD{}TAIL", "x".repeat(100));
+
+        let content = executor.truncate(
+            "logs::fetch",
+            vec![
+                ToolResultContent::text(text),
+                ToolResultContent::text("short"),
+            ],
+        );
+
+        assert_eq!(
+            content[0].as_text(),
+            Some("HEADx\n[truncated 103 chars]\n")
+        );
+        assert_eq!(content[1].as_text(), Some("short"));
+
+        let content = executor.truncate(
+            "logs::fetch",
+            vec![ToolResultContent::text(format!("HEAD{}TAIL", "x".repeat(100)))],
+        );
+        assert_eq!(
+            content,
+            vec![ToolResultContent::text("HEADx\n[truncated 98 chars]\nxTAIL")]
+        );
+    }
+
+    #[test]
+    fn limit_applies_to_the_combined_text_of_all_parts() {
+        let config = ToolsConfig {
+            max_result_chars: 6,
+            ..ToolsConfig::default()
+        };
+        let executor = ToolExecutor::new(Vec::new(), &config);
+        let image = ToolResultContent::Image {
+            data: "iVBORw0KGgo=".to_string(),
+            media_type: "image/png".to_string(),
+        };
+
+        // Each part fits on its own, but together they don't
+        let content = executor.truncate(
+            "fs::read_many",
+            vec![
+                ToolResultContent::text("aaaa"),
+                ToolResultContent::text("bbbb"),
+                image.clone(),
+                ToolResultContent::text("cccc"),
+                ToolResultContent::text("dddd"),
+            ],
+        );
+
+        assert_eq!(
+            content,
+            vec![
+                ToolResultContent::text("aaa\n[truncated 10 chars]\n"),
+                image,
+                ToolResultContent::text("ddd"),
+            ]
+        );
+    }
+
+    #[test]
+    fn truncation_counts_chars_not_bytes() {
+        let text = "é".repeat(20);
+        let truncated = truncate_middle(&text, 4);
+        assert_eq!(truncated, "éé\n[truncated 16 chars]\néé");
+    }
+
+    #[test]
+    fn schema_summary_lists_required_arguments_first() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "encoding": { "type": "string", "enum": ["utf-8", "latin1"] },
+                "lines": { "type": "array", "items": { "type": "integer" } },
+                "path": { "type": "string" },
+                "timeout": { "anyOf": [{ "type": "number" }, { "type": "null" }] },
+            },
+            "required": ["path"],
+        });
+
+        assert_eq!(
+            summarize_schema(&schema),
+            [
+                "path: string (required)",
+                r#"encoding: "utf-8" | "latin1""#,
+                "lines: array of integer",
+                "timeout: number | null",
+            ]
+        );
+    }
+
+    #[test]
+    fn schema_summary_of_schemas_without_properties_is_empty() {
+        assert!(summarize_schema(&json!({ "type": "object" })).is_empty());
+        assert!(summarize_schema(&json!({ "type": "string" })).is_empty());
+        assert!(summarize_schema(&json!(true)).is_empty());
+    }
+
+    #[test]
+    fn schema_summary_falls_back_to_any() {
+        let schema = json!({
+            "properties": {
+                "filter": {},
+                "value": { "type": ["string", "integer"] },
+            },
+        });
+
+        assert_eq!(summarize_schema(&schema), ["filter: any", "value: string | integer"]);
+    }
+}
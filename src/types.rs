@@ -1,5 +1,6 @@
+use crate::llm::TokenUsage;
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 
@@ -10,6 +11,14 @@ pub struct Message {
     pub role: Role,
     pub content: Vec<ContentBlock>,
     pub timestamp: DateTime<Utc>,
+    /// The prompt prefix ending at this message is stable and may be cached
+    /// by providers that support prompt caching (set by `ConversationBuffer`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cache_breakpoint: bool,
+    /// Caller-defined tags (e.g. where the message came from); never sent
+    /// to providers
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, serde_json::Value>,
 }
 
 /// Message role
@@ -21,7 +30,7 @@ pub enum Role {
     System,
 }
 
-/// Content block (text or tool use/result)
+/// Content block (text, thinking, redacted thinking, or tool use/result)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ContentBlock {
@@ -35,10 +44,77 @@ pub enum ContentBlock {
     },
     ToolResult {
         tool_use_id: String,
-        content: String,
+        #[serde(deserialize_with = "deserialize_tool_result_content")]
+        content: Vec<ToolResultContent>,
         #[serde(skip_serializing_if = "Option::is_none")]
         is_error: Option<bool>,
     },
+    Thinking {
+        thinking: String,
+        #[serde(default)]
+        signature: String,
+    },
+    /// Thinking the provider encrypted; it must be sent back unchanged in
+    /// later turns but can't be shown
+    RedactedThinking {
+        data: String,
+    },
+}
+
+/// Content of a tool result (text or base64 image)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolResultContent {
+    Text {
+        text: String,
+    },
+    Image {
+        /// Base64-encoded image data
+        data: String,
+        media_type: String,
+    },
+}
+
+impl ToolResultContent {
+    pub fn text(text: impl Into<String>) -> Self {
+        ToolResultContent::Text { text: text.into() }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            ToolResultContent::Text { text } => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Join the text parts of a tool result, skipping images
+    pub fn joined_text(content: &[ToolResultContent]) -> String {
+        content
+            .iter()
+            .filter_map(ToolResultContent::as_text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Accept the older plain-string tool result content in saved sessions
+fn deserialize_tool_result_content<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<ToolResultContent>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Text(String),
+        Content(Vec<ToolResultContent>),
+    }
+
+    Ok(match Repr::deserialize(deserializer)? {
+        Repr::Text(text) => vec![ToolResultContent::Text { text }],
+        Repr::Content(content) => content,
+    })
 }
 
 impl Message {
@@ -48,6 +124,8 @@ impl Message {
             role: Role::User,
             content: vec![ContentBlock::Text { text }],
             timestamp: Utc::now(),
+            cache_breakpoint: false,
+            metadata: HashMap::new(),
         }
     }
 
@@ -57,6 +135,20 @@ impl Message {
             role: Role::Assistant,
             content,
             timestamp: Utc::now(),
+            cache_breakpoint: false,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// User turn carrying `ToolResult` blocks for the previous tool calls
+    pub fn new_tool_results(results: Vec<ContentBlock>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            role: Role::User,
+            content: results,
+            timestamp: Utc::now(),
+            cache_breakpoint: false,
+            metadata: HashMap::new(),
         }
     }
 
@@ -66,8 +158,20 @@ impl Message {
             role: Role::System,
             content: vec![ContentBlock::Text { text }],
             timestamp: Utc::now(),
+            cache_breakpoint: false,
+            metadata: HashMap::new(),
         }
     }
+
+    /// Set a metadata entry, e.g. `.with_metadata("source", "summary")`
+    pub fn with_metadata(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<serde_json::Value>,
+    ) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
 }
 
 /// Tool definition
@@ -82,6 +186,9 @@ pub struct Tool {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
     pub id: String,
+    /// Short human-readable title (generated after the first exchange)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub messages: Vec<Message>,
@@ -89,6 +196,9 @@ pub struct Session {
     pub provider: String,
     pub model: String,
     pub metadata: HashMap<String, serde_json::Value>,
+    /// Tokens used across all provider responses in this session
+    #[serde(default)]
+    pub total_usage: TokenUsage,
 }
 
 impl Session {
@@ -96,6 +206,7 @@ impl Session {
         let now = Utc::now();
         Self {
             id: Uuid::new_v4().to_string(),
+            title: None,
             created_at: now,
             updated_at: now,
             messages: Vec::new(),
@@ -103,6 +214,7 @@ impl Session {
             provider,
             model,
             metadata: HashMap::new(),
+            total_usage: TokenUsage::default(),
         }
     }
 
@@ -110,4 +222,134 @@ impl Session {
         self.messages.push(message);
         self.updated_at = Utc::now();
     }
+
+    /// A new session with a fresh id holding this session's first
+    /// `at_message_index` messages, with `parent_id` in its metadata
+    ///
+    /// The fork starts with no recorded usage. An index past the end copies
+    /// every message.
+    pub fn fork(&self, at_message_index: usize) -> Session {
+        let now = Utc::now();
+        let mut metadata = self.metadata.clone();
+        metadata.insert("parent_id".to_string(), serde_json::json!(self.id));
+
+        Session {
+            id: Uuid::new_v4().to_string(),
+            title: self.title.clone(),
+            created_at: now,
+            updated_at: now,
+            messages: self.messages[..at_message_index.min(self.messages.len())].to_vec(),
+            mcp_servers: self.mcp_servers.clone(),
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            metadata,
+            total_usage: TokenUsage::default(),
+        }
+    }
+
+    /// Add the usage reported for one provider response to the session total
+    pub fn record_usage(&mut self, usage: &TokenUsage) {
+        self.total_usage.input_tokens += usage.input_tokens;
+        self.total_usage.output_tokens += usage.output_tokens;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_usage_is_summed() {
+        let mut session = Session::new("anthropic".to_string(), "claude".to_string());
+        session.record_usage(&TokenUsage {
+            input_tokens: 100,
+            output_tokens: 20,
+        });
+        session.record_usage(&TokenUsage {
+            input_tokens: 150,
+            output_tokens: 35,
+        });
+
+        assert_eq!(session.total_usage.input_tokens, 250);
+        assert_eq!(session.total_usage.output_tokens, 55);
+    }
+
+    #[test]
+    fn session_saved_before_usage_tracking_loads() {
+        let json = r#"{
+            "id": "old",
+            "created_at": "2025-01-01T00:00:00Z",
+            "updated_at": "2025-01-01T00:00:00Z",
+            "messages": [],
+            "mcp_servers": [],
+            "provider": "ollama",
+            "model": "llama3",
+            "metadata": {}
+        }"#;
+
+        let session: Session = serde_json::from_str(json).unwrap();
+        assert_eq!(session.total_usage.total(), 0);
+    }
+
+    #[test]
+    fn message_metadata_round_trips() {
+        let message = Message::new_user("hi".to_string())
+            .with_metadata("source", "summary")
+            .with_metadata("turn", 3);
+
+        let json = serde_json::to_string(&message).unwrap();
+        let parsed: Message = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.metadata["source"], "summary");
+        assert_eq!(parsed.metadata["turn"], 3);
+
+        // Messages without metadata leave the field out entirely
+        let plain = serde_json::to_value(Message::new_user("hi".to_string())).unwrap();
+        assert!(plain.get("metadata").is_none());
+        let parsed: Message = serde_json::from_value(plain).unwrap();
+        assert!(parsed.metadata.is_empty());
+    }
+
+    fn session_with_messages(count: usize) -> Session {
+        let mut session = Session::new("anthropic".to_string(), "claude".to_string());
+        for i in 0..count {
+            session.messages.push(Message::new_user(format!("message {}", i)));
+        }
+        session.record_usage(&TokenUsage {
+            input_tokens: 100,
+            output_tokens: 20,
+        });
+        session
+    }
+
+    #[test]
+    fn fork_copies_messages_before_the_index() {
+        let session = session_with_messages(4);
+        let fork = session.fork(2);
+
+        assert_ne!(fork.id, session.id);
+        assert_eq!(fork.messages.len(), 2);
+        assert_eq!(
+            serde_json::to_value(&fork.messages).unwrap(),
+            serde_json::to_value(&session.messages[..2]).unwrap()
+        );
+        assert_eq!(fork.provider, session.provider);
+        assert_eq!(fork.model, session.model);
+    }
+
+    #[test]
+    fn fork_records_its_parent_and_starts_with_no_usage() {
+        let session = session_with_messages(2);
+        let fork = session.fork(1);
+
+        assert_eq!(fork.metadata["parent_id"], session.id.as_str());
+        assert_eq!(fork.total_usage.total(), 0);
+        assert_eq!(session.total_usage.total(), 120);
+    }
+
+    #[test]
+    fn fork_past_the_end_copies_every_message() {
+        let session = session_with_messages(3);
+        assert_eq!(session.fork(10).messages.len(), 3);
+        assert!(session.fork(0).messages.is_empty());
+    }
 }
@@ -0,0 +1,123 @@
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
+
+const FENCE: &str = "```";
+const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+/// Colorizes fenced code blocks in streamed markdown
+///
+/// Prose passes through as it arrives; code is buffered until its fence
+/// closes, since syntax highlighting needs whole lines of context.
+pub struct CodeHighlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+    /// Current incomplete line
+    line: String,
+    /// Whether part of the current line was already emitted as prose
+    line_flushed: bool,
+    /// Open code block as (language, buffered body)
+    code: Option<(String, String)>,
+}
+
+impl CodeHighlighter {
+    pub fn new() -> Self {
+        let themes = ThemeSet::load_defaults();
+        Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.themes[DEFAULT_THEME].clone(),
+            line: String::new(),
+            line_flushed: false,
+            code: None,
+        }
+    }
+
+    /// Feed streamed text, returning whatever is ready to print
+    pub fn push(&mut self, text: &str) -> String {
+        let mut out = String::new();
+
+        for ch in text.chars() {
+            self.line.push(ch);
+            if ch == '\n' {
+                let line = std::mem::take(&mut self.line);
+                out.push_str(&self.process_line(line));
+                self.line_flushed = false;
+            }
+        }
+
+        // Emit partial prose right away unless it could still become a fence
+        if self.code.is_none() && !self.line.is_empty() {
+            let trimmed = self.line.trim_start();
+            let maybe_fence = trimmed.starts_with(FENCE) || FENCE.starts_with(trimmed);
+            if self.line_flushed || !maybe_fence {
+                out.push_str(&std::mem::take(&mut self.line));
+                self.line_flushed = true;
+            }
+        }
+
+        out
+    }
+
+    /// Flush anything still buffered (e.g. an unclosed code block)
+    pub fn finish(&mut self) -> String {
+        let mut out = String::new();
+        if let Some((lang, body)) = self.code.take() {
+            out.push_str(&self.highlight(&lang, &body));
+        }
+        out.push_str(&std::mem::take(&mut self.line));
+        self.line_flushed = false;
+        out
+    }
+
+    fn process_line(&mut self, line: String) -> String {
+        if self.line_flushed {
+            return line;
+        }
+
+        let is_fence = line.trim_start().starts_with(FENCE);
+
+        match self.code.take() {
+            Some((lang, body)) if is_fence => {
+                let mut out = self.highlight(&lang, &body);
+                out.push_str(&line);
+                out
+            }
+            Some((lang, mut body)) => {
+                body.push_str(&line);
+                self.code = Some((lang, body));
+                String::new()
+            }
+            None if is_fence => {
+                let lang = line.trim_start()[FENCE.len()..].trim().to_string();
+                self.code = Some((lang, String::new()));
+                line
+            }
+            None => line,
+        }
+    }
+
+    fn highlight(&self, lang: &str, body: &str) -> String {
+        let syntax = self
+            .syntaxes
+            .find_syntax_by_token(lang)
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text());
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+
+        let mut out = String::new();
+        for line in LinesWithEndings::from(body) {
+            match highlighter.highlight_line(line, &self.syntaxes) {
+                Ok(ranges) => out.push_str(&as_24_bit_terminal_escaped(&ranges, false)),
+                Err(_) => out.push_str(line),
+            }
+        }
+        out.push_str("\x1b[0m");
+        out
+    }
+}
+
+impl Default for CodeHighlighter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,32 @@
+#[cfg(feature = "highlight")]
+pub mod highlight;
+pub mod renderer;
+pub mod repl;
+pub mod spinner;
+
+pub use renderer::{
+    drive_stream, render_response, Renderer, RenderingAccumulator, TerminalRenderer,
+};
+pub use spinner::Spinner;
+
+/// Whether terminal output should be colorized (honors `--no-color`/`NO_COLOR`)
+pub fn color_enabled() -> bool {
+    colored::control::SHOULD_COLORIZE.should_colorize()
+}
+
+/// Highlight the fenced code blocks in a complete response, for output that
+/// isn't streamed through `TerminalRenderer`
+///
+/// Returns the text unchanged without the `highlight` feature or when color
+/// is off.
+pub fn highlight(text: &str) -> String {
+    #[cfg(feature = "highlight")]
+    if color_enabled() {
+        let mut highlighter = highlight::CodeHighlighter::new();
+        let mut out = highlighter.push(text);
+        out.push_str(&highlighter.finish());
+        return out;
+    }
+
+    text.to_string()
+}
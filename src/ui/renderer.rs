@@ -0,0 +1,391 @@
+use crate::error::Result;
+use crate::llm::{ChatChunk, ChatResponse, ContentDelta, StopReason, StreamAccumulator, TokenUsage};
+use crate::types::ContentBlock;
+use colored::Colorize;
+use futures::{Stream, StreamExt};
+use std::collections::HashSet;
+use std::io::Write;
+
+/// Longest tool input JSON the terminal shows before eliding
+const MAX_INPUT_CHARS: usize = 80;
+
+/// Receives streaming output so the same driver can feed a terminal,
+/// a GUI, or a test recorder
+pub trait Renderer {
+    /// A piece of response text arrived
+    fn on_text_delta(&mut self, text: &str);
+
+    /// A piece of the model's thinking arrived
+    fn on_thinking_delta(&mut self, _thinking: &str) {}
+
+    /// The model finished emitting a tool-use block
+    fn on_tool_use(&mut self, name: &str, input: &serde_json::Value);
+
+    /// A tool call completed
+    fn on_tool_result(&mut self, name: &str, content: &str, is_error: bool);
+
+    /// The stream reported why generation stopped (before `on_complete`)
+    fn on_stop(&mut self, _reason: &StopReason) {}
+
+    /// The response finished
+    fn on_complete(&mut self, usage: &TokenUsage);
+}
+
+/// Consume a chat stream, dispatching chunks to the renderer, and return
+/// the assembled response (its `stop_reason` is the one the stream reported)
+pub async fn drive_stream<S>(
+    mut stream: S,
+    model: &str,
+    renderer: &mut dyn Renderer,
+) -> Result<ChatResponse>
+where
+    S: Stream<Item = Result<ChatChunk>> + Unpin,
+{
+    let mut accumulator = RenderingAccumulator::new(model.to_string(), renderer);
+    while let Some(chunk) = stream.next().await {
+        accumulator.push(chunk?)?;
+    }
+    accumulator.finish()
+}
+
+/// Report a complete (non-streamed) response to the renderer as if it had
+/// streamed in one piece
+pub fn render_response(response: &ChatResponse, renderer: &mut dyn Renderer) {
+    for block in &response.content {
+        if let ContentBlock::Thinking { thinking, .. } = block {
+            renderer.on_thinking_delta(thinking);
+        }
+    }
+    let text = response.text();
+    if !text.is_empty() {
+        renderer.on_text_delta(&text);
+    }
+    for block in response.tool_uses() {
+        if let ContentBlock::ToolUse { name, input, .. } = block {
+            renderer.on_tool_use(name, input);
+        }
+    }
+    if let Some(reason) = &response.stop_reason {
+        renderer.on_stop(reason);
+    }
+    renderer.on_complete(&response.usage);
+}
+
+/// A `StreamAccumulator` that also reports what arrives to a `Renderer`,
+/// for callers that need to look at the response while it streams
+pub struct RenderingAccumulator<'r> {
+    accumulator: StreamAccumulator,
+    renderer: &'r mut dyn Renderer,
+    /// Ids of tool-use blocks already passed to `on_tool_use`
+    shown_tool_uses: HashSet<String>,
+}
+
+impl<'r> RenderingAccumulator<'r> {
+    pub fn new(model: String, renderer: &'r mut dyn Renderer) -> Self {
+        Self {
+            accumulator: StreamAccumulator::new(model),
+            renderer,
+            shown_tool_uses: HashSet::new(),
+        }
+    }
+
+    /// Apply a chunk, rendering its text and any tool-use block it completes
+    pub fn push(&mut self, chunk: ChatChunk) -> Result<()> {
+        let (text_delta, thinking_delta) = match &chunk {
+            ChatChunk::ContentBlockDelta {
+                delta: ContentDelta::TextDelta { text },
+                ..
+            } => (Some(text.clone()), None),
+            ChatChunk::ContentBlockDelta {
+                delta: ContentDelta::ThinkingDelta { thinking },
+                ..
+            } => (None, Some(thinking.clone())),
+            _ => (None, None),
+        };
+        let stopped_index = match &chunk {
+            ChatChunk::ContentBlockStop { index } => Some(*index),
+            _ => None,
+        };
+
+        self.accumulator.push(chunk)?;
+
+        if let Some(thinking) = thinking_delta {
+            self.renderer.on_thinking_delta(&thinking);
+        }
+        if let Some(text) = text_delta {
+            self.renderer.on_text_delta(&text);
+        }
+
+        if let Some(index) = stopped_index {
+            if let Some(ContentBlock::ToolUse { id, name, input }) = self.accumulator.block(index) {
+                self.shown_tool_uses.insert(id.clone());
+                self.renderer.on_tool_use(name, input);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// See `StreamAccumulator::take_completed_tool_uses`
+    pub fn take_completed_tool_uses(&mut self) -> Vec<ContentBlock> {
+        self.accumulator.take_completed_tool_uses()
+    }
+
+    /// Assemble the response, rendering tool-use blocks the stream never
+    /// stopped, then its stop reason and usage
+    pub fn finish(self) -> Result<ChatResponse> {
+        let response = self.accumulator.finish()?;
+
+        for block in response.tool_uses() {
+            if let ContentBlock::ToolUse { id, name, input } = block {
+                if !self.shown_tool_uses.contains(id) {
+                    self.renderer.on_tool_use(name, input);
+                }
+            }
+        }
+        if let Some(reason) = &response.stop_reason {
+            self.renderer.on_stop(reason);
+        }
+        self.renderer.on_complete(&response.usage);
+
+        Ok(response)
+    }
+}
+
+/// Renders streaming output to the terminal: response text on stdout,
+/// thinking (dimmed) and tool activity on stderr
+pub struct TerminalRenderer {
+    #[cfg(feature = "highlight")]
+    highlighter: Option<super::highlight::CodeHighlighter>,
+    /// Printed before the first text of each response
+    label: Option<String>,
+    show_tools: bool,
+    show_thinking: bool,
+    /// Whether text was printed since the last `on_complete`
+    wrote_text: bool,
+    /// Whether a thinking line on stderr is still open
+    thinking: bool,
+    spinner_enabled: bool,
+    /// Shown while waiting for the model; cleared by any output
+    spinner: Option<super::Spinner>,
+}
+
+impl TerminalRenderer {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "highlight")]
+            highlighter: super::color_enabled().then(super::highlight::CodeHighlighter::new),
+            label: None,
+            show_tools: true,
+            show_thinking: true,
+            wrote_text: false,
+            thinking: false,
+            spinner_enabled: false,
+            spinner: None,
+        }
+    }
+
+    /// Print `label` (e.g. "assistant>") before the text of each response
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Whether to print tool calls and results (on by default)
+    pub fn show_tools(mut self, show: bool) -> Self {
+        self.show_tools = show;
+        self
+    }
+
+    /// Whether to print the model's thinking (on by default)
+    pub fn show_thinking(mut self, show: bool) -> Self {
+        self.show_thinking = show;
+        self
+    }
+
+    /// Whether `wait` shows a spinner (off by default)
+    pub fn spinner(mut self, enabled: bool) -> Self {
+        self.spinner_enabled = enabled;
+        self
+    }
+
+    /// Show a spinner until the next output, e.g. before starting a turn
+    ///
+    /// The spinner comes back after tool results while the model works on
+    /// its next turn.
+    pub fn wait(&mut self) {
+        self.stop_waiting();
+        if self.spinner_enabled {
+            self.spinner = Some(super::Spinner::start("Thinking...", true));
+        }
+    }
+
+    /// Clear the spinner, e.g. when a turn fails
+    pub fn stop_waiting(&mut self) {
+        self.spinner = None;
+    }
+
+    /// End an open thinking line before other output
+    fn end_thinking(&mut self) {
+        if std::mem::take(&mut self.thinking) {
+            eprintln!();
+        }
+    }
+}
+
+impl Default for TerminalRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer for TerminalRenderer {
+    fn on_text_delta(&mut self, text: &str) {
+        self.stop_waiting();
+        self.end_thinking();
+        if !self.wrote_text {
+            self.wrote_text = true;
+            if let Some(label) = &self.label {
+                print!("{} ", label.bold());
+            }
+        }
+
+        #[cfg(feature = "highlight")]
+        if let Some(highlighter) = self.highlighter.as_mut() {
+            print!("{}", highlighter.push(text));
+            let _ = std::io::stdout().flush();
+            return;
+        }
+
+        print!("{}", text);
+        let _ = std::io::stdout().flush();
+    }
+
+    // Thinking and tool activity go to stderr so piped stdout holds only
+    // the answer
+
+    fn on_thinking_delta(&mut self, thinking: &str) {
+        self.stop_waiting();
+        if !self.show_thinking {
+            return;
+        }
+        if !std::mem::replace(&mut self.thinking, true) {
+            eprint!("{} ", "💭".dimmed());
+        }
+        eprint!("{}", thinking.dimmed());
+    }
+
+    fn on_tool_use(&mut self, name: &str, input: &serde_json::Value) {
+        self.stop_waiting();
+        self.end_thinking();
+        if !self.show_tools {
+            return;
+        }
+        let input = crate::tools::truncate_end(&input.to_string(), MAX_INPUT_CHARS);
+        // End the text's line first
+        if std::mem::take(&mut self.wrote_text) {
+            println!();
+        }
+        eprintln!("{} {} {}", "🔧".cyan(), name.cyan().bold(), input.dimmed());
+    }
+
+    fn on_tool_result(&mut self, name: &str, content: &str, is_error: bool) {
+        self.stop_waiting();
+        if self.show_tools {
+            let first_line = content.lines().next().unwrap_or("");
+            if is_error {
+                eprintln!("   {} {}: {}", "❌".red(), name, first_line.red());
+            } else {
+                eprintln!("   {} {}: {}", "✅".green(), name, first_line.dimmed());
+            }
+        }
+        // The model's next turn starts once the results are in
+        if self.spinner_enabled {
+            self.wait();
+        }
+    }
+
+    fn on_stop(&mut self, reason: &StopReason) {
+        self.stop_waiting();
+        if *reason == StopReason::MaxTokens {
+            eprintln!();
+            eprintln!(
+                "{}",
+                "⚠️  Response truncated at max_tokens (raise it with --max-tokens)".yellow()
+            );
+        }
+    }
+
+    fn on_complete(&mut self, _usage: &TokenUsage) {
+        self.stop_waiting();
+        self.end_thinking();
+
+        #[cfg(feature = "highlight")]
+        if let Some(highlighter) = self.highlighter.as_mut() {
+            print!("{}", highlighter.finish());
+        }
+
+        // End the response's line; a response without text printed nothing
+        if std::mem::take(&mut self.wrote_text) {
+            println!();
+        }
+        let _ = std::io::stdout().flush();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::llm::LlmProvider;
+    use crate::types::Message;
+
+    /// Renderer that records the stop reasons it is told about
+    #[derive(Default)]
+    pub(crate) struct StopRecorder {
+        pub stops: Vec<StopReason>,
+        /// Whether `on_complete` came after the stop reason
+        pub completed: bool,
+    }
+
+    impl Renderer for StopRecorder {
+        fn on_text_delta(&mut self, _text: &str) {}
+
+        fn on_tool_use(&mut self, _name: &str, _input: &serde_json::Value) {}
+
+        fn on_tool_result(&mut self, _name: &str, _content: &str, _is_error: bool) {}
+
+        fn on_stop(&mut self, reason: &StopReason) {
+            assert!(!self.completed, "stop reason after on_complete");
+            self.stops.push(reason.clone());
+        }
+
+        fn on_complete(&mut self, _usage: &TokenUsage) {
+            self.completed = true;
+        }
+    }
+
+    /// Stream a reply to "hi" through `drive_stream`, returning the stop
+    /// reason of the response and the ones the renderer saw
+    pub(crate) async fn streamed_stop_reasons(
+        provider: &dyn LlmProvider,
+    ) -> (Option<StopReason>, Vec<StopReason>) {
+        let stream = provider
+            .stream_chat(vec![Message::new_user("hi".to_string())], vec![])
+            .await
+            .unwrap();
+        let mut recorder = StopRecorder::default();
+        let response = drive_stream(stream, provider.model(), &mut recorder)
+            .await
+            .unwrap();
+        assert!(recorder.completed);
+        (response.stop_reason, recorder.stops)
+    }
+
+    #[tokio::test]
+    async fn stop_reason_is_reported_before_completion() {
+        let provider = crate::llm::mock::MockProvider::echo();
+        let (stop_reason, stops) = streamed_stop_reasons(&provider).await;
+        assert_eq!(stop_reason, Some(StopReason::EndTurn));
+        assert_eq!(stops, [StopReason::EndTurn]);
+    }
+}
@@ -0,0 +1,45 @@
+use crate::error::{OllmError, Result};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::io::{BufRead, IsTerminal};
+
+/// Where REPL input comes from: an interactive line editor on a terminal,
+/// or plain lines when stdin is piped (`echo "hi" | ollm repl`)
+pub enum LineSource {
+    Interactive(Box<DefaultEditor>),
+    Piped(std::io::Lines<std::io::StdinLock<'static>>),
+}
+
+impl LineSource {
+    /// Pick the mode from whether stdin is a terminal
+    pub fn from_stdin() -> Result<Self> {
+        if !std::io::stdin().is_terminal() {
+            return Ok(LineSource::Piped(std::io::stdin().lock().lines()));
+        }
+
+        let editor = DefaultEditor::new()
+            .map_err(|e| OllmError::Other(anyhow::anyhow!("Failed to start line editor: {}", e)))?;
+        Ok(LineSource::Interactive(Box::new(editor)))
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        matches!(self, LineSource::Interactive(_))
+    }
+
+    /// Read the next line, or `None` at EOF (or Ctrl-C/Ctrl-D interactively)
+    pub fn read_line(&mut self, prompt: &str) -> Result<Option<String>> {
+        match self {
+            LineSource::Interactive(editor) => match editor.readline(prompt) {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        let _ = editor.add_history_entry(line.as_str());
+                    }
+                    Ok(Some(line))
+                }
+                Err(ReadlineError::Eof | ReadlineError::Interrupted) => Ok(None),
+                Err(e) => Err(OllmError::Other(anyhow::anyhow!("Failed to read input: {}", e))),
+            },
+            LineSource::Piped(lines) => lines.next().transpose().map_err(OllmError::Io),
+        }
+    }
+}
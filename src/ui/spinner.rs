@@ -0,0 +1,45 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::IsTerminal;
+use std::time::Duration;
+
+/// Spinner with elapsed time, drawn on stderr while waiting for a
+/// non-streaming response
+///
+/// The spinner is cleared when dropped, so error paths (`?`) remove it too.
+pub struct Spinner {
+    bar: Option<ProgressBar>,
+}
+
+impl Spinner {
+    /// Start a spinner showing `message`
+    ///
+    /// Does nothing when `enabled` is false or stdout/stderr isn't a
+    /// terminal, so piped output never contains spinner frames.
+    pub fn start(message: &str, enabled: bool) -> Self {
+        let interactive = std::io::stdout().is_terminal() && std::io::stderr().is_terminal();
+        if !enabled || !interactive {
+            return Self { bar: None };
+        }
+
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(
+            ProgressStyle::with_template("{spinner:.cyan} {msg} ({elapsed})")
+                .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+        );
+        bar.set_message(message.to_string());
+        bar.enable_steady_tick(Duration::from_millis(100));
+
+        Self { bar: Some(bar) }
+    }
+
+    /// Clear the spinner
+    pub fn stop(self) {}
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        if let Some(bar) = self.bar.take() {
+            bar.finish_and_clear();
+        }
+    }
+}
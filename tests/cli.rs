@@ -0,0 +1,144 @@
+//! End-to-end tests of the `ollm` binary against a mock Ollama server
+
+use open_llm_code::Config;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// A streamed `/api/chat` reply of `text`
+fn reply(text: &str) -> String {
+    let chunk = serde_json::json!({
+        "model": "m",
+        "message": { "role": "assistant", "content": text },
+        "done": false,
+    });
+    let done = serde_json::json!({
+        "model": "m",
+        "message": { "role": "assistant", "content": "" },
+        "done": true,
+        "done_reason": "stop",
+        "prompt_eval_count": 5,
+        "eval_count": 3,
+    });
+    format!("{}\n{}\n", chunk, done)
+}
+
+/// Mock server answering `expected` chat requests with `text`
+fn ollama(text: &str, expected: usize) -> (mockito::ServerGuard, mockito::Mock) {
+    let mut server = mockito::Server::new();
+    let mock = server
+        .mock("POST", "/api/chat")
+        .with_body(reply(text))
+        .expect(expected)
+        .create();
+    (server, mock)
+}
+
+/// The example config pointed at `server`, with MCP off and sessions kept
+/// out of the user's data dir
+fn config_file(server: &mockito::ServerGuard) -> PathBuf {
+    let mut config: Config = toml::from_str(&Config::example()).unwrap();
+    config.llm.provider = "ollama".to_string();
+    config.llm.model = "m".to_string();
+    config.llm.tool_prompting = false;
+    config.ollama.endpoint = server.url();
+    config.mcp_enabled = false;
+    config.mcp_servers.clear();
+    let id = uuid::Uuid::new_v4();
+    config.session.dir = Some(std::env::temp_dir().join(format!("ollm-cli-sessions-{}", id)));
+    let path = std::env::temp_dir().join(format!("ollm-cli-{}.toml", id));
+    std::fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+    path
+}
+
+/// Run `ollm` with `config`, feeding `stdin` through a pipe, then remove
+/// the config and any sessions it saved
+fn ollm(config: &Path, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ollm"))
+        .arg("--config")
+        .arg(config)
+        .arg("--no-color")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let sessions: Config = toml::from_str(&std::fs::read_to_string(config).unwrap()).unwrap();
+    if let Some(dir) = sessions.session.dir {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+    std::fs::remove_file(config).unwrap();
+    output
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+#[test]
+fn stdout_only_keeps_status_lines_off_both_streams() {
+    let (server, mock) = ollama("Hello from the mock", 2);
+
+    let output = ollm(&config_file(&server), &["test", "hi"], "");
+    assert!(output.status.success(), "{}", text(&output.stderr));
+    assert_eq!(text(&output.stdout).trim(), "Hello from the mock");
+    assert!(text(&output.stderr).contains("Provider: ollama (m)"));
+
+    let output = ollm(&config_file(&server), &["--stdout-only", "test", "hi"], "");
+    assert!(output.status.success(), "{}", text(&output.stderr));
+    assert_eq!(text(&output.stdout).trim(), "Hello from the mock");
+    assert!(!text(&output.stderr).contains("Provider:"));
+    assert!(!text(&output.stderr).contains("Hello from the mock"));
+    mock.assert();
+}
+
+#[test]
+fn output_appends_each_response_to_the_file() {
+    let (server, mock) = ollama("Hello from the mock", 2);
+    let path = std::env::temp_dir().join(format!("ollm-cli-output-{}.txt", uuid::Uuid::new_v4()));
+    let path_arg = path.to_str().unwrap();
+
+    for _ in 0..2 {
+        let output = ollm(&config_file(&server), &["test", "-o", path_arg, "hi"], "");
+        assert!(output.status.success(), "{}", text(&output.stderr));
+        assert!(!text(&output.stdout).contains("Hello from the mock"));
+        assert!(text(&output.stderr).contains("Appended 1 response to:"));
+    }
+
+    let written = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(written, "Hello from the mock\nHello from the mock\n");
+    mock.assert();
+}
+
+#[test]
+fn piped_repl_answers_each_line_and_exits_at_eof() {
+    let (server, mock) = ollama("Hello from the mock", 2);
+
+    let output = ollm(&config_file(&server), &["repl"], "first\nsecond\n");
+    assert!(output.status.success(), "{}", text(&output.stderr));
+    assert_eq!(text(&output.stdout).matches("Hello from the mock").count(), 2);
+    mock.assert();
+}
+
+#[test]
+fn count_prints_stats_across_the_runs() {
+    let (server, mock) = ollama("Hello from the mock", 6);
+
+    let output = ollm(&config_file(&server), &["test", "--count", "3", "hi"], "");
+    assert!(output.status.success(), "{}", text(&output.stderr));
+    let stdout = text(&output.stdout);
+    assert!(stdout.starts_with("Runs: 3\nLatency: min "), "{}", stdout);
+    assert!(stdout.contains("\nThroughput: "), "{}", stdout);
+    assert!(!stdout.contains("Hello from the mock"));
+
+    let output = ollm(&config_file(&server), &["test", "--count", "3", "--json", "hi"], "");
+    assert!(output.status.success(), "{}", text(&output.stderr));
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["runs"], 3);
+    assert!(stats["p95_secs"].as_f64().unwrap() >= stats["p50_secs"].as_f64().unwrap());
+    mock.assert();
+}